@@ -0,0 +1,505 @@
+//! Typed wrappers for invoking the echo program from other programs.
+//!
+//! Every function takes a `CpiContext` holding the echo program account, the accounts of the instruction and the
+//! seeds of any PDAs that sign for the caller, then builds the instruction and invokes it. The accounts are passed in
+//! the order documented on `EchoInstruction`, so callers only need to fill in the structs.
+//!
+//! Only available with the `cpi` feature, which also disables the entrypoint of this crate so it can be linked into
+//! another program.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    hash::Hash,
+    instruction::{AccountMeta, Instruction},
+    program::{get_return_data, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::instruction::{self, decode_read_echo, EchoInstruction};
+use crate::state::{ContentHeader, Expiry};
+
+/// Echo program account, accounts of one instruction and the signer seeds of the caller.
+pub struct CpiContext<'a, 'b, T> {
+    pub program: AccountInfo<'a>,
+    pub accounts: T,
+    /// Seeds of the caller PDAs that sign the instruction, empty if all signers signed the transaction.
+    pub signer_seeds: &'b [&'b [&'b [u8]]],
+}
+
+impl<'a, 'b, T> CpiContext<'a, 'b, T> {
+    pub fn new(program: AccountInfo<'a>, accounts: T) -> Self {
+        Self {
+            program,
+            accounts,
+            signer_seeds: &[],
+        }
+    }
+
+    pub fn new_with_signer(
+        program: AccountInfo<'a>,
+        accounts: T,
+        signer_seeds: &'b [&'b [&'b [u8]]],
+    ) -> Self {
+        Self {
+            program,
+            accounts,
+            signer_seeds,
+        }
+    }
+}
+
+/// Accounts of `EchoInstruction::Echo`.
+pub struct Echo<'a> {
+    pub echo_buffer: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::InitializeAuthorizedEcho`.
+pub struct InitializeAuthorizedEcho<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+    pub system_program: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::AuthorizedEcho` and `EchoInstruction::AuthorizedEchoAt`.
+pub struct AuthorizedEcho<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+    /// Authority of the buffer, or one of its writers.
+    pub authority: AccountInfo<'a>,
+    /// `BufferWriter` account of `authority`, only needed when it is a writer rather than the authority.
+    pub buffer_writer: Option<AccountInfo<'a>>,
+}
+
+/// Accounts of `EchoInstruction::VendingMachineEcho` and `EchoInstruction::VendingMachineEchoAt`.
+pub struct VendingMachineEcho<'a> {
+    pub vending_machine_buffer: AccountInfo<'a>,
+    pub user: AccountInfo<'a>,
+    /// Token account or credits account that pays for the write.
+    pub user_token_account: AccountInfo<'a>,
+    pub vending_machine_mint: AccountInfo<'a>,
+    pub token_program: AccountInfo<'a>,
+    /// Treasury of the machine, only needed in `PaymentMode::Treasury`.
+    pub treasury: Option<AccountInfo<'a>>,
+}
+
+/// Accounts of `EchoInstruction::CloseAuthorizedEcho`.
+pub struct CloseAuthorizedEcho<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+    pub recipient: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::ResizeAuthorizedEcho`.
+pub struct ResizeAuthorizedEcho<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+    pub system_program: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::SetBufferAuthority`.
+pub struct SetBufferAuthority<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::AcceptBufferAuthority`.
+pub struct AcceptBufferAuthority<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+    pub pending_authority: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::SealBuffer`.
+pub struct SealBuffer<'a> {
+    pub buffer: AccountInfo<'a>,
+    /// Authority of an `authorized_buffer`, or owner of a vending machine.
+    pub authority: AccountInfo<'a>,
+    /// Mint of the vending machine, only needed for a `vending_machine_buffer`.
+    pub vending_machine_mint: Option<AccountInfo<'a>>,
+}
+
+/// Accounts of `EchoInstruction::ReadEcho`.
+pub struct ReadEcho<'a> {
+    pub buffer: AccountInfo<'a>,
+    /// Mint of the vending machine, only needed for a `vending_machine_buffer`.
+    pub vending_machine_mint: Option<AccountInfo<'a>>,
+}
+
+/// Accounts of `EchoInstruction::VerifyEcho`.
+pub struct VerifyEcho<'a> {
+    pub buffer: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::VerifyChunk`.
+pub struct VerifyChunk<'a> {
+    pub authorized_buffer: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::AppendEcho`.
+pub struct AppendEcho<'a> {
+    pub log_buffer: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::CloseLogEcho`.
+pub struct CloseLogEcho<'a> {
+    pub log_buffer: AccountInfo<'a>,
+    pub authority: AccountInfo<'a>,
+    pub recipient: AccountInfo<'a>,
+}
+
+/// Accounts of `EchoInstruction::TokenGatedEcho`.
+pub struct TokenGatedEcho<'a> {
+    pub token_gated_buffer: AccountInfo<'a>,
+    pub holder: AccountInfo<'a>,
+    pub holder_token_account: AccountInfo<'a>,
+}
+
+/// Invokes `EchoInstruction::Echo`.
+pub fn echo<'a>(ctx: CpiContext<'a, '_, Echo<'a>>, data: Vec<u8>) -> ProgramResult {
+    let instruction = instruction::echo(ctx.program.key, ctx.accounts.echo_buffer.key, data);
+    invoke(
+        ctx.program,
+        instruction,
+        vec![ctx.accounts.echo_buffer],
+        ctx.signer_seeds,
+    )
+}
+
+/// Invokes `EchoInstruction::InitializeAuthorizedEcho`.
+pub fn initialize_authorized_echo<'a>(
+    ctx: CpiContext<'a, '_, InitializeAuthorizedEcho<'a>>,
+    buffer_seed: u64,
+    buffer_size: u64,
+    expiry: Option<Expiry>,
+    content: Option<ContentHeader>,
+) -> ProgramResult {
+    // Built by hand, the instruction builder would derive the buffer address again
+    let instruction = Instruction::new_with_borsh(
+        *ctx.program.key,
+        &EchoInstruction::InitializeAuthorizedEcho {
+            buffer_seed,
+            buffer_size,
+            expiry,
+            content,
+        },
+        vec![
+            AccountMeta::new(*ctx.accounts.authorized_buffer.key, false),
+            AccountMeta::new(*ctx.accounts.authority.key, true),
+            AccountMeta::new_readonly(*ctx.accounts.system_program.key, false),
+        ],
+    );
+    let account_infos = vec![
+        ctx.accounts.authorized_buffer,
+        ctx.accounts.authority,
+        ctx.accounts.system_program,
+    ];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::AuthorizedEcho`.
+pub fn authorized_echo<'a>(
+    ctx: CpiContext<'a, '_, AuthorizedEcho<'a>>,
+    data: Vec<u8>,
+) -> ProgramResult {
+    let instruction = instruction::authorized_echo(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        ctx.accounts.authority.key,
+        data,
+    );
+    invoke_authorized_echo(ctx, instruction)
+}
+
+/// Invokes `EchoInstruction::AuthorizedEchoAt`.
+pub fn authorized_echo_at<'a>(
+    ctx: CpiContext<'a, '_, AuthorizedEcho<'a>>,
+    offset: u64,
+    data: Vec<u8>,
+) -> ProgramResult {
+    let instruction = instruction::authorized_echo_at(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        ctx.accounts.authority.key,
+        offset,
+        data,
+    );
+    invoke_authorized_echo(ctx, instruction)
+}
+
+/// Invokes `EchoInstruction::VendingMachineEcho`.
+pub fn vending_machine_echo<'a>(
+    ctx: CpiContext<'a, '_, VendingMachineEcho<'a>>,
+    data: Vec<u8>,
+    max_charge: u64,
+) -> ProgramResult {
+    invoke_vending_machine_echo(
+        ctx,
+        &EchoInstruction::VendingMachineEcho { data, max_charge },
+    )
+}
+
+/// Invokes `EchoInstruction::VendingMachineEchoAt`.
+pub fn vending_machine_echo_at<'a>(
+    ctx: CpiContext<'a, '_, VendingMachineEcho<'a>>,
+    offset: u64,
+    data: Vec<u8>,
+    max_charge: u64,
+) -> ProgramResult {
+    invoke_vending_machine_echo(
+        ctx,
+        &EchoInstruction::VendingMachineEchoAt {
+            offset,
+            data,
+            max_charge,
+        },
+    )
+}
+
+/// Invokes `EchoInstruction::CloseAuthorizedEcho`.
+pub fn close_authorized_echo<'a>(
+    ctx: CpiContext<'a, '_, CloseAuthorizedEcho<'a>>,
+) -> ProgramResult {
+    let instruction = instruction::close_authorized_echo(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        ctx.accounts.authority.key,
+        ctx.accounts.recipient.key,
+    );
+    let account_infos = vec![
+        ctx.accounts.authorized_buffer,
+        ctx.accounts.authority,
+        ctx.accounts.recipient,
+    ];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::ResizeAuthorizedEcho`.
+pub fn resize_authorized_echo<'a>(
+    ctx: CpiContext<'a, '_, ResizeAuthorizedEcho<'a>>,
+    new_size: u64,
+) -> ProgramResult {
+    let instruction = instruction::resize_authorized_echo(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        ctx.accounts.authority.key,
+        new_size,
+    );
+    let account_infos = vec![
+        ctx.accounts.authorized_buffer,
+        ctx.accounts.authority,
+        ctx.accounts.system_program,
+    ];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::SetBufferAuthority`.
+pub fn set_buffer_authority<'a>(
+    ctx: CpiContext<'a, '_, SetBufferAuthority<'a>>,
+    new_authority: &Pubkey,
+) -> ProgramResult {
+    let instruction = instruction::set_buffer_authority(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        ctx.accounts.authority.key,
+        new_authority,
+    );
+    let account_infos = vec![ctx.accounts.authorized_buffer, ctx.accounts.authority];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::AcceptBufferAuthority`.
+pub fn accept_buffer_authority<'a>(
+    ctx: CpiContext<'a, '_, AcceptBufferAuthority<'a>>,
+) -> ProgramResult {
+    let instruction = instruction::accept_buffer_authority(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        ctx.accounts.pending_authority.key,
+    );
+    let account_infos = vec![
+        ctx.accounts.authorized_buffer,
+        ctx.accounts.pending_authority,
+    ];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::SealBuffer`.
+pub fn seal_buffer<'a>(ctx: CpiContext<'a, '_, SealBuffer<'a>>) -> ProgramResult {
+    let instruction = match &ctx.accounts.vending_machine_mint {
+        Some(vending_machine_mint) => instruction::seal_vending_machine_buffer(
+            ctx.program.key,
+            ctx.accounts.buffer.key,
+            ctx.accounts.authority.key,
+            vending_machine_mint.key,
+        ),
+        None => instruction::seal_authorized_buffer(
+            ctx.program.key,
+            ctx.accounts.buffer.key,
+            ctx.accounts.authority.key,
+        ),
+    };
+    let mut account_infos = vec![ctx.accounts.buffer, ctx.accounts.authority];
+    account_infos.extend(ctx.accounts.vending_machine_mint);
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::ReadEcho` and returns the `len` bytes read.
+pub fn read_echo<'a>(
+    ctx: CpiContext<'a, '_, ReadEcho<'a>>,
+    offset: u64,
+    len: u64,
+) -> Result<Vec<u8>, ProgramError> {
+    let instruction = match &ctx.accounts.vending_machine_mint {
+        Some(vending_machine_mint) => instruction::read_vending_machine_echo(
+            ctx.program.key,
+            ctx.accounts.buffer.key,
+            vending_machine_mint.key,
+            offset,
+            len,
+        ),
+        None => {
+            instruction::read_authorized_echo(ctx.program.key, ctx.accounts.buffer.key, offset, len)
+        }
+    };
+    let program_id = *ctx.program.key;
+    let mut account_infos = vec![ctx.accounts.buffer];
+    account_infos.extend(ctx.accounts.vending_machine_mint);
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)?;
+    decode_read_echo(&program_id, get_return_data(), len)
+}
+
+/// Invokes `EchoInstruction::VerifyEcho`, which fails unless the buffer holds data with the SHA-256 hash
+/// `expected_hash`.
+pub fn verify_echo<'a>(
+    ctx: CpiContext<'a, '_, VerifyEcho<'a>>,
+    expected_hash: Hash,
+) -> ProgramResult {
+    let instruction =
+        instruction::verify_echo(ctx.program.key, ctx.accounts.buffer.key, expected_hash);
+    let account_infos = vec![ctx.accounts.buffer];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::VerifyChunk`, which fails unless `proof` shows that `leaf` is the chunk at `index` of the
+/// Merkle tree committed in the buffer.
+pub fn verify_chunk<'a>(
+    ctx: CpiContext<'a, '_, VerifyChunk<'a>>,
+    index: u64,
+    leaf: Vec<u8>,
+    proof: Vec<Hash>,
+) -> ProgramResult {
+    let instruction = instruction::verify_chunk(
+        ctx.program.key,
+        ctx.accounts.authorized_buffer.key,
+        index,
+        leaf,
+        proof,
+    );
+    let account_infos = vec![ctx.accounts.authorized_buffer];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::AppendEcho`.
+pub fn append_echo<'a>(ctx: CpiContext<'a, '_, AppendEcho<'a>>, data: Vec<u8>) -> ProgramResult {
+    let instruction = instruction::append_echo(
+        ctx.program.key,
+        ctx.accounts.log_buffer.key,
+        ctx.accounts.authority.key,
+        data,
+    );
+    let account_infos = vec![ctx.accounts.log_buffer, ctx.accounts.authority];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::CloseLogEcho`.
+pub fn close_log_echo<'a>(ctx: CpiContext<'a, '_, CloseLogEcho<'a>>) -> ProgramResult {
+    let instruction = instruction::close_log_echo(
+        ctx.program.key,
+        ctx.accounts.log_buffer.key,
+        ctx.accounts.authority.key,
+        ctx.accounts.recipient.key,
+    );
+    let account_infos = vec![
+        ctx.accounts.log_buffer,
+        ctx.accounts.authority,
+        ctx.accounts.recipient,
+    ];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `EchoInstruction::TokenGatedEcho`. A program holding the gate tokens in a PDA token account signs for it
+/// with `CpiContext::new_with_signer`.
+pub fn token_gated_echo<'a>(
+    ctx: CpiContext<'a, '_, TokenGatedEcho<'a>>,
+    data: Vec<u8>,
+) -> ProgramResult {
+    let instruction = instruction::token_gated_echo(
+        ctx.program.key,
+        ctx.accounts.token_gated_buffer.key,
+        ctx.accounts.holder.key,
+        ctx.accounts.holder_token_account.key,
+        data,
+    );
+    let account_infos = vec![
+        ctx.accounts.token_gated_buffer,
+        ctx.accounts.holder,
+        ctx.accounts.holder_token_account,
+    ];
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Adds the `buffer_writer` account, if any, to an `AuthorizedEcho` or `AuthorizedEchoAt` instruction and invokes it.
+fn invoke_authorized_echo<'a>(
+    ctx: CpiContext<'a, '_, AuthorizedEcho<'a>>,
+    mut instruction: Instruction,
+) -> ProgramResult {
+    let mut account_infos = vec![ctx.accounts.authorized_buffer, ctx.accounts.authority];
+    if let Some(buffer_writer) = ctx.accounts.buffer_writer {
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(*buffer_writer.key, false));
+        account_infos.push(buffer_writer);
+    }
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes a `VendingMachineEcho` or `VendingMachineEchoAt` instruction. Unlike the instruction builders, the
+/// `treasury` is only passed when the caller has it.
+fn invoke_vending_machine_echo<'a>(
+    ctx: CpiContext<'a, '_, VendingMachineEcho<'a>>,
+    instruction: &EchoInstruction,
+) -> ProgramResult {
+    let accounts = ctx.accounts;
+    let mut account_metas = vec![
+        AccountMeta::new(*accounts.vending_machine_buffer.key, false),
+        AccountMeta::new_readonly(*accounts.user.key, true),
+        AccountMeta::new(*accounts.user_token_account.key, false),
+        AccountMeta::new(*accounts.vending_machine_mint.key, false),
+        AccountMeta::new_readonly(*accounts.token_program.key, false),
+    ];
+    let mut account_infos = vec![
+        accounts.vending_machine_buffer,
+        accounts.user,
+        accounts.user_token_account,
+        accounts.vending_machine_mint,
+        accounts.token_program,
+    ];
+    if let Some(treasury) = accounts.treasury {
+        account_metas.push(AccountMeta::new(*treasury.key, false));
+        account_infos.push(treasury);
+    }
+    let instruction = Instruction::new_with_borsh(*ctx.program.key, instruction, account_metas);
+    invoke(ctx.program, instruction, account_infos, ctx.signer_seeds)
+}
+
+/// Invokes `instruction` of the echo `program` with `account_infos`, signing with `signer_seeds`.
+fn invoke<'a>(
+    program: AccountInfo<'a>,
+    instruction: Instruction,
+    mut account_infos: Vec<AccountInfo<'a>>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    account_infos.push(program);
+    invoke_signed(&instruction, &account_infos, signer_seeds)
+}
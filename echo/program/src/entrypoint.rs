@@ -1,9 +1,8 @@
 use crate::processor::Processor;
-use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, msg, pubkey::Pubkey};
+use solana_program::{
+    account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, msg, pubkey::Pubkey,
+};
 
-#[cfg(not(feature = "no-entrypoint"))]
-use solana_program::entrypoint;
-#[cfg(not(feature = "no-entrypoint"))]
 entrypoint!(process_instruction);
 
 fn process_instruction(
@@ -6,6 +6,122 @@ use thiserror::Error;
 pub enum EchoError {
     #[error("Instruction not implemented.")]
     NotImplemented,
+    #[error("Write is out of the bounds of the echo buffer.")]
+    WriteOutOfBounds,
+    #[error("Account must be writable.")]
+    AccountMustBeWritable,
+    #[error("Missing required signature.")]
+    MissingRequiredSignature,
+    #[error("Account not initialized.")]
+    AccountNotInitialized,
+    #[error("Invalid account owner.")]
+    InvalidAccountOwner,
+    #[error("Expected the System Program.")]
+    InvalidSystemProgram,
+    #[error("Expected the Token Program.")]
+    InvalidTokenProgram,
+    #[error("Invalid instruction data.")]
+    InvalidInstructionData,
+    #[error("Buffer address does not match its seeds.")]
+    InvalidBufferAddress,
+    #[error("Buffer data could not be read or written.")]
+    InvalidBufferData,
+    #[error("Echo buffer is not empty.")]
+    BufferNotEmpty,
+    #[error("Buffer is too small.")]
+    BufferTooSmall,
+    #[error("Buffer can grow by at most 10KiB per instruction.")]
+    BufferGrowthTooLarge,
+    #[error("Echo data must not be empty.")]
+    EmptyData,
+    #[error("Signer is not the authority of the buffer.")]
+    InvalidAuthority,
+    #[error("Signer is not the pending authority of the buffer.")]
+    InvalidPendingAuthority,
+    #[error("Invalid mint account.")]
+    InvalidMint,
+    #[error("Signer is not the mint authority.")]
+    InvalidMintAuthority,
+    #[error("Recipient cannot be the account being closed.")]
+    InvalidRecipient,
+    #[error("Arithmetic overflow.")]
+    ArithmeticOverflow,
+    #[error("Account is not the expected kind of echo account.")]
+    InvalidAccountType,
+    #[error("Account layout version is not supported.")]
+    UnsupportedAccountVersion,
+    #[error("Treasury account does not belong to the vending machine.")]
+    InvalidTreasury,
+    #[error("Signer is not the owner of the vending machine.")]
+    InvalidOwner,
+    #[error("Credits account does not belong to the user and vending machine.")]
+    InvalidCredits,
+    #[error("Not enough prepaid credits for the write.")]
+    InsufficientCredits,
+    #[error("Prepaid credits have expired.")]
+    CreditsExpired,
+    #[error("Treasury tokens are held back for refundable credits.")]
+    ProceedsReservedForCredits,
+    #[error("Account is not a writer of the buffer.")]
+    InvalidWriter,
+    #[error("Buffer is sealed and can no longer change.")]
+    BufferSealed,
+    #[error("Buffer has expired.")]
+    BufferExpired,
+    #[error("Buffer has not expired yet.")]
+    BufferNotExpired,
+    #[error("Read is out of the bounds of the echo buffer.")]
+    ReadOutOfBounds,
+    #[error("Read is larger than the return data limit.")]
+    ReadTooLarge,
+    #[error("Return data was not set by a ReadEcho instruction of the program.")]
+    InvalidReturnData,
+    #[error("Account is not the staging buffer of the signer.")]
+    InvalidStagingBuffer,
+    #[error("Chunk is larger than the chunk size of the upload.")]
+    ChunkTooLarge,
+    #[error("Staged data does not match the hash given to BeginWrite.")]
+    StagedHashMismatch,
+    #[error("Record does not fit in the log buffer.")]
+    RecordTooLarge,
+    #[error("Account is not the mailbox of the recipient.")]
+    InvalidMailbox,
+    #[error("Mailbox has no room for the message.")]
+    MailboxFull,
+    #[error("Mailbox has no messages.")]
+    MailboxEmpty,
+    #[error("Message is larger than the return data limit.")]
+    MessageTooLarge,
+    #[error("Postage is below the minimum of the mailbox.")]
+    InsufficientPostage,
+    #[error("Account is not the postage account of the mailbox.")]
+    InvalidPostageAccount,
+    #[error("Instruction before is not a valid ed25519 signature check.")]
+    InvalidSignatureInstruction,
+    #[error("Signed message does not match the write.")]
+    InvalidSignedMessage,
+    #[error("Nonce does not match the nonce of the buffer.")]
+    InvalidNonce,
+    #[error("Buffer content does not match the expected hash.")]
+    ContentHashMismatch,
+    #[error("Buffer does not hold a Merkle root.")]
+    NotMerkleBuffer,
+    #[error("Merkle proof does not match the root of the buffer.")]
+    InvalidMerkleProof,
+    #[error("Token account is not of the gate mint or not owned by the signer.")]
+    InvalidGateTokenAccount,
+    #[error("Token account holds less than the minimum balance of the gate.")]
+    InsufficientGateBalance,
+    #[error("Echo data of a UTF-8 buffer is not valid UTF-8.")]
+    InvalidUtf8,
+    #[error("Buffer content is not of the expected type.")]
+    UnexpectedContentType,
+    #[error("Charge of the write is above the maximum charge of the instruction.")]
+    ChargeExceedsMax,
+    #[error("A Merkle tree needs at least one leaf.")]
+    EmptyMerkleTree,
+    #[error("Minimum balance of a token gate must be at least 1.")]
+    ZeroMinBalance,
 }
 
 impl From<EchoError> for ProgramError {
@@ -1,4 +1,20 @@
 use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{
+    ed25519_program,
+    hash::{hash, Hash},
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    system_program, sysvar,
+};
+
+use crate::error::EchoError;
+use crate::pda::{
+    find_authorized_buffer_address, find_buffer_writer_address, find_credits_address,
+    find_log_buffer_address, find_mailbox_address, find_staging_address, find_token_gated_address,
+    find_treasury_address, find_vending_machine_address,
+};
+use crate::state::{ContentHeader, Expiry, Message, PaymentMode, Pricing, MESSAGE_HEADER_SIZE};
 
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
 pub enum EchoInstruction {
@@ -7,7 +23,7 @@ pub enum EchoInstruction {
     /// If the `echo_buffer` account length ( N ) is smaller than the length of data, the instruction will copy the
     /// first N bytes of data into `echo_buffer`.
     ///
-    /// If `echo_buffer` has any non-zero data, the instruction will fail.
+    /// If `echo_buffer` has any non-zero data, the instruction will fail with `EchoError::BufferNotEmpty`.
     ///
     /// Accounts:
     /// | index | writable | signer | description                                  |
@@ -16,9 +32,30 @@ pub enum EchoInstruction {
     Echo { data: Vec<u8> },
     /// This instruction will allocate `buffer_size` bytes to the `authorized_buffer` account and assign it the Echo Program.
     ///
-    /// The first 9 bytes of authorized_buffer will be set with the following data:
-    ///     byte 0: bump_seed
-    ///     bytes 1-8: buffer_seed
+    /// The first `AuthorizedBuffer::HEADER_SIZE` (235) bytes of authorized_buffer will be set with the following data:
+    ///     byte 0: account_type, `AccountType::AuthorizedBuffer`
+    ///     byte 1: version, `CURRENT_VERSION`
+    ///     byte 2: bump_seed
+    ///     bytes 3-10: buffer_seed
+    ///     bytes 11-42: seed_authority, the `authority` used to derive the PDA
+    ///     bytes 43-74: authority, initially the same as seed_authority
+    ///     bytes 75-106: pending_authority, initially unset
+    ///     byte 107: sealed, zero until `SealBuffer`
+    ///     byte 108: expiry_kind, the `ExpiryKind` of `expiry`
+    ///     bytes 109-116: expires_at, the unix timestamp or slot of `expiry`
+    ///     bytes 117-124: nonce, the nonce of the next `AuthorizedEchoSigned`, initially 0
+    ///     bytes 125-156: content_hash, the SHA-256 hash of the echo data
+    ///     bytes 157-164: merkle_leaf_count, set by `SetMerkleRoot` and cleared by other writes
+    ///     byte 165: content_type, the `ContentType` of `content`, `ContentType::Bytes` without one
+    ///     bytes 166-173: schema_id, the schema id of `content`, zero without one
+    ///     bytes 174-234: reserved, zeroed
+    ///
+    /// With a `content` header of `ContentType::Utf8`, every write fails with `EchoError::InvalidUtf8` unless the
+    /// whole echo data is valid UTF-8 afterwards. `AuthorizedBuffer::content` decodes the echo data by its header.
+    ///
+    /// With an `expiry`, writes and resizes fail with `EchoError::BufferExpired` once the unix timestamp or slot of the
+    /// Clock sysvar is past it, and anyone can return the rent of the buffer to its `seed_authority` with
+    /// `ReclaimExpiredBuffer`, even after `authority` has been transferred.
     ///
     /// Accounts:
     /// | index | writable | signer | description                                                              |
@@ -26,54 +63,102 @@ pub enum EchoInstruction {
     /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
     /// | 1     | ❌       | ✅     | authority: Pubkey with sole write access to `authorized_buffer`           |
     /// | 2     | ❌       | ❌     | system_program: Used to allocate the buffer                               |
-    InitializeAuthorizedEcho { buffer_seed: u64, buffer_size: u64 },
+    InitializeAuthorizedEcho {
+        buffer_seed: u64,
+        buffer_size: u64,
+        expiry: Option<Expiry>,
+        content: Option<ContentHeader>,
+    },
     /// The contents of the data vector that is provided to the instruction will be copied into the `authorized_buffer` account
-    /// starting from index 9 (will NOT override the bump_seed and buffer_seed).
+    /// after the header (will NOT override the bump_seed, buffer_seed and authorities).
     ///
     /// If the remaining `authorized_buffer` account length ( N ) is smaller than the length of `data`, copy the first N bytes
     /// of data into `authorized_buffer`.
     ///
-    /// Initially, if `authorized_buffer` has any non-zero data past index 9, you should should zero out all of the data outside
-    /// of the first 9 bytes.
+    /// Initially, if `authorized_buffer` has any non-zero data past the header, you should should zero out all of the data
+    /// outside of the header.
+    ///
+    /// If any account besides the current `authority` or a writer added with `AddBufferWriter` attempts to write to the
+    /// `authorized_buffer`, the instruction will fail. Writers pass their `buffer_writer` account after their signature.
     ///
-    /// If any account besides the `authority` attempts to write to the `authorized_buffer`, the instruction will fail.
+    /// If `data` is empty, the instruction will fail with `EchoError::EmptyData`.
     ///
     /// Accounts:
     /// | index | writable | signer | description                                                              |
     /// |-------|----------|--------|--------------------------------------------------------------------------|
     /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
-    /// | 1     | ❌       | ✅     | authority: Pubkey with sole write access to `authorized_buffer`           |
+    /// | 1     | ❌       | ✅     | authority: Current authority, or a writer of `authorized_buffer`          |
+    /// | 2     | ❌       | ❌     | buffer_writer: PDA of the writer, only needed when a writer signs         |
     AuthorizedEcho { data: Vec<u8> },
     /// This instruction will allocate `buffer_size` bytes to the `vending_machine_buffer` account and assign it the Echo Program.
     ///
-    /// The first 9 bytes of `vending_machine_buffer` will be set with the following data:
-    ///     byte 0: bump_seed
-    ///     bytes 1-8: price
+    /// The first `VendingMachineBuffer::HEADER_SIZE` (171) bytes of `vending_machine_buffer` will be set with the
+    /// following data:
+    ///     byte 0: account_type, `AccountType::VendingMachineBuffer`
+    ///     byte 1: version, `CURRENT_VERSION`
+    ///     byte 2: bump_seed
+    ///     bytes 3-10: price, the base fee of `pricing`
+    ///     bytes 11-42: owner, the `payer`
+    ///     byte 43: payment_mode
+    ///     bytes 44-75: treasury, unset in `PaymentMode::Burn`
+    ///     bytes 76-83: machine_id
+    ///     bytes 84-91: price_per_byte, the per byte fee of `pricing`
+    ///     bytes 92-99: credit_lifetime, zero until `SetVendingMachineCreditPolicy`
+    ///     byte 100: refund_credits, zero until `SetVendingMachineCreditPolicy`
+    ///     bytes 101-108: outstanding_credits
+    ///     byte 109: sealed, zero until `SealBuffer`
+    ///     byte 110: content_type, the `ContentType` of `content`, `ContentType::Bytes` without one
+    ///     bytes 111-118: schema_id, the schema id of `content`, zero without one
+    ///     bytes 119-150: content_hash, the SHA-256 hash of the echo data
+    ///     bytes 151-170: reserved, zeroed
+    ///
+    /// As for `InitializeAuthorizedEcho`, writes to a buffer with `ContentType::Utf8` content must leave valid UTF-8.
+    ///
+    /// The buffer address is derived from `vending_machine_mint` and `machine_id`, so one mint can back several
+    /// machines and the owner can change the price later with `SetVendingMachinePrice`.
+    ///
+    /// With `PaymentMode::Treasury`, the `treasury` token account is created as well. Its owner is the
+    /// `vending_machine_buffer` PDA, so tokens only leave it through `WithdrawVendingMachineProceeds`.
     ///
     /// Accounts:
     /// | index | writable | signer | description                                                                                         |
     /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
     /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
     /// | 1     | ❌       | ❌     | vending_machine_mint: Pubkey with sole write access to `authorized_buffer`                           |
-    /// | 2     | ❌       | ✅     | payer: Pubkey that allocates the `vending_machine_buffer`                                            |
+    /// | 2     | ✅       | ✅     | payer: Pubkey that allocates the `vending_machine_buffer` and becomes its owner                      |
     /// | 3     | ❌       | ❌     | system_program: Used to allocate the buffer                                                          |
+    /// | 4     | ✅       | ❌     | treasury: PDA token account collecting payments, only for `PaymentMode::Treasury`                    |
+    /// | 5     | ❌       | ❌     | token_program: Used to initialize the `treasury`, only for `PaymentMode::Treasury`                   |
+    /// | 6     | ❌       | ❌     | rent_sysvar: Used to initialize the `treasury`, only for `PaymentMode::Treasury`                     |
     InitializeVendingMachineEcho {
+        machine_id: u64,
         // Number of tokens required change the buffer
-        price: u64,
+        pricing: Pricing,
         buffer_size: usize,
+        payment_mode: PaymentMode,
+        content: Option<ContentHeader>,
     },
     /// The contents of the data vector that is provided to the instruction should be copied into the account starting from
-    /// index 9 (you do NOT want to override the bump_seed and price).
+    /// the end of the header (you do NOT want to override the account header, bump_seed and price).
     ///
     /// If the remaining account length ( N ) is smaller than the length of data, copy the first N bytes of data into
     /// `vending_machine_buffer`.
     ///
-    /// Initially, if `vending_machine_buffer` has any non-zero data past index 9, you should should zero out all of the
-    /// data outside of the first 9 bytes.
+    /// Initially, if `vending_machine_buffer` has any non-zero data past the header, you should should zero out all of
+    /// the data outside of the header.
     ///
-    /// Before any data is copied over, the user must burn a `price` amount of tokens from the `user_token_account`.
+    /// Before any data is copied over, the user must burn the charge of the write from the `user_token_account`: the base
+    /// fee plus the per byte fee for every byte of echo data, as computed by `Pricing::charge`.
     /// This will require a cross program invocation to the Token Program. If this instruction succeed (verifies that the
-    /// user in fact has sufficient tokens), then the copy can occur.
+    /// user in fact has sufficient tokens), then the copy can occur. In `PaymentMode::Treasury` the tokens are
+    /// transferred to the `treasury` instead of being burned.
+    ///
+    /// Users with credits from `BuyVendingMachineCredits` pass their credits account as `user_token_account`
+    /// instead. The charge is then taken from their credits without any call to the Token Program.
+    ///
+    /// If the charge is above `max_charge`, the instruction fails with `EchoError::ChargeExceedsMax` before anything
+    /// is paid. Users set it to the charge at the price they saw, so a price raised by `SetVendingMachinePrice` in the
+    /// meantime cannot take more from them.
     ///
     /// This instruction should fail in the case that the mint of the `vending_machine_buffer` does not match the mint
     /// used to seed the PDA.  You can verify this by comparing the output of `Pubkey::create_program_address` with the correct
@@ -84,8 +169,1701 @@ pub enum EchoInstruction {
     /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
     /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
     /// | 1     | ❌       | ✅     | user: This is authority of the token account that is using the vending machine                       |
-    /// | 2     | ✅       | ❌     | user_token_account: This is the token account that will pay for the use of the vending machine       |
-    /// | 3     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
-    /// | 3     | ❌       | ❌     | token_program: Used to burn the vending machine tokens                                               |
-    VendingMachineEcho { data: Vec<u8> },
+    /// | 2     | ✅       | ❌     | user_token_account: Token account or credits account that pays for the use of the vending machine    |
+    /// | 3     | ✅       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 4     | ❌       | ❌     | token_program: Used to burn the vending machine tokens                                               |
+    /// | 5     | ✅       | ❌     | treasury: Token account collecting payments, only for `PaymentMode::Treasury`                       |
+    VendingMachineEcho { data: Vec<u8>, max_charge: u64 },
+    /// Closes an `authorized_buffer` created by `InitializeAuthorizedEcho` and returns its rent to `recipient`.
+    ///
+    /// The buffer data is zeroed, its length is reallocated to 0 and ownership is handed back to the System Program,
+    /// so the account cannot be used as an echo buffer again within the same transaction, even if it is re-funded.
+    ///
+    /// Only the `authority` of the buffer may close it. A `log_buffer` is closed with `CloseLogEcho` instead.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ❌       | ✅     | authority: Pubkey with sole write access to `authorized_buffer`           |
+    /// | 2     | ✅       | ❌     | recipient: Account that receives the lamports of `authorized_buffer`      |
+    CloseAuthorizedEcho,
+    /// Closes a `vending_machine_buffer` created by `InitializeVendingMachineEcho` and returns its rent to `owner`.
+    ///
+    /// The buffer data is zeroed, its length is reallocated to 0 and ownership is handed back to the System Program,
+    /// so the account cannot be used as an echo buffer again within the same transaction, even if it is re-funded.
+    ///
+    /// Only the `owner` of the machine may close it. In `PaymentMode::Treasury` the `treasury` is closed as well and
+    /// its rent also goes to `owner`, which fails unless the owner has withdrawn all proceeds.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 2     | ✅       | ✅     | owner: Owner of the machine, receives the lamports of `vending_machine_buffer`                       |
+    /// | 3     | ✅       | ❌     | treasury: Token account collecting payments, only for `PaymentMode::Treasury`                       |
+    /// | 4     | ❌       | ❌     | token_program: Used to close the `treasury`, only for `PaymentMode::Treasury`                       |
+    CloseVendingMachineEcho,
+    /// Resizes an `authorized_buffer` created by `InitializeAuthorizedEcho` to `new_size` bytes.
+    ///
+    /// `new_size` is the total account length, like `buffer_size` in `InitializeAuthorizedEcho`, so the echo data
+    /// holds `new_size - AuthorizedBuffer::HEADER_SIZE` bytes. Growing keeps the existing data and zero-fills the new
+    /// bytes, shrinking truncates the data. An account can grow by at most 10KiB per instruction.
+    ///
+    /// If the buffer needs more lamports to stay rent-exempt the `authority` pays the difference, and if it needs
+    /// fewer the excess is refunded to the `authority`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ✅       | ✅     | authority: Pubkey with sole write access to `authorized_buffer`           |
+    /// | 2     | ❌       | ❌     | system_program: Used to pay for a larger buffer                           |
+    ResizeAuthorizedEcho { new_size: u64 },
+    /// The contents of the data vector that is provided to the instruction will be copied into the echo data of the
+    /// `authorized_buffer` account starting at byte `offset` of the echo data (the header is not counted). Bytes outside of `offset..offset + data.len()` are left untouched.
+    ///
+    /// If the write does not fit in the echo data, the instruction will fail with `EchoError::WriteOutOfBounds`.
+    ///
+    /// If any account besides the current `authority` or a writer added with `AddBufferWriter` attempts to write to the
+    /// `authorized_buffer`, the instruction will fail. Writers pass their `buffer_writer` account after their signature.
+    ///
+    /// If `data` is empty, the instruction will fail with `EchoError::EmptyData`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ❌       | ✅     | authority: Current authority, or a writer of `authorized_buffer`          |
+    /// | 2     | ❌       | ❌     | buffer_writer: PDA of the writer, only needed when a writer signs         |
+    AuthorizedEchoAt { offset: u64, data: Vec<u8> },
+    /// The contents of the data vector that is provided to the instruction will be copied into the echo data of the
+    /// `vending_machine_buffer` account starting at byte `offset` of the echo data (the header is not counted). Bytes
+    /// outside of `offset..offset + data.len()` are left untouched.
+    ///
+    /// If the write does not fit in the echo data, the instruction will fail with `EchoError::WriteOutOfBounds`. If
+    /// `data` is empty, it will fail with `EchoError::EmptyData`.
+    ///
+    /// Like `VendingMachineEcho`, the user must pay from the `user_token_account` before any data is copied over, but
+    /// only the `data.len()` bytes written are charged the per byte fee. The charge must not be above `max_charge`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
+    /// | 1     | ❌       | ✅     | user: This is authority of the token account that is using the vending machine                       |
+    /// | 2     | ✅       | ❌     | user_token_account: Token account or credits account that pays for the use of the vending machine    |
+    /// | 3     | ✅       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 4     | ❌       | ❌     | token_program: Used to burn the vending machine tokens                                               |
+    /// | 5     | ✅       | ❌     | treasury: Token account collecting payments, only for `PaymentMode::Treasury`                       |
+    VendingMachineEchoAt {
+        offset: u64,
+        data: Vec<u8>,
+        max_charge: u64,
+    },
+    /// Proposes `new_authority` as the next authority of an `authorized_buffer`. The transfer only takes effect once
+    /// `new_authority` signs `AcceptBufferAuthority`, and until then the current `authority` keeps write access.
+    ///
+    /// Proposing `Pubkey::default()` cancels a pending transfer. The buffer keeps the address it was created with.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ❌       | ✅     | authority: Current authority of `authorized_buffer`                       |
+    SetBufferAuthority { new_authority: Pubkey },
+    /// Completes a transfer started by `SetBufferAuthority`: the pending authority becomes the `authority` of the
+    /// `authorized_buffer`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ❌       | ✅     | pending_authority: Pubkey proposed by the current authority               |
+    AcceptBufferAuthority,
+    /// Rewrites an `authorized_buffer` in an older layout in the current layout: version 1, the layout without an
+    /// `AccountHeader`, or the original layout that only held the seeds and the echo data. The echo data and
+    /// authorities are kept as they are.
+    ///
+    /// Every other instruction rejects buffers that are not at `CURRENT_VERSION`, so older buffers must be migrated
+    /// before use.
+    ///
+    /// Only the `authority` may migrate. It pays for a larger buffer and gets back the lamports a smaller one no
+    /// longer needs. Buffers in the original layout do not store their authority, the key that initialized them
+    /// signs and becomes their `authority`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: `authorized_buffer` to migrate                         |
+    /// | 1     | ✅       | ✅     | authority: Current authority of `authorized_buffer`, settles the rent     |
+    /// | 2     | ❌       | ❌     | system_program: Used to pay for the larger buffer                         |
+    MigrateAuthorizedEcho,
+    /// Rewrites a `vending_machine_buffer` in an older layout in the current layout, like `MigrateAuthorizedEcho`.
+    ///
+    /// Older buffers were derived from their price, which becomes their `machine_id` so the address stays the same.
+    /// They have no owner, so the mint authority of `vending_machine_mint` migrates them and becomes their `owner`.
+    /// It settles the rent like the authority in `MigrateAuthorizedEcho`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: `vending_machine_buffer` to migrate                                         |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 2     | ✅       | ✅     | mint_authority: Mint authority of `vending_machine_mint`, becomes the owner of the machine           |
+    /// | 3     | ❌       | ❌     | system_program: Used to pay for the larger buffer                                                    |
+    MigrateVendingMachineEcho,
+    /// Transfers `amount` tokens out of the `treasury` of a vending machine in `PaymentMode::Treasury`.
+    ///
+    /// Only the `owner` recorded at `InitializeVendingMachineEcho` may withdraw. While the machine refunds credits,
+    /// tokens paid for outstanding credits cannot be withdrawn.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | vending_machine_buffer: PDA of the Echo Program that owns the `treasury`                            |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 2     | ❌       | ✅     | owner: Owner of the `vending_machine_buffer`                                                        |
+    /// | 3     | ✅       | ❌     | treasury: Token account collecting payments                                                         |
+    /// | 4     | ✅       | ❌     | destination: Token account that receives the proceeds                                               |
+    /// | 5     | ❌       | ❌     | token_program: Used to transfer the proceeds                                                        |
+    WithdrawVendingMachineProceeds { amount: u64 },
+    /// Sets the `Pricing` of `VendingMachineEcho` and `VendingMachineEchoAt`. The buffer keeps its address and data.
+    ///
+    /// Only the `owner` recorded at `InitializeVendingMachineEcho` may change the price. The new price applies to the
+    /// next write, even one already sent, so writes cap their charge with `max_charge` and fail rather than pay a
+    /// price raised in front of them.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 2     | ❌       | ✅     | owner: Owner of the `vending_machine_buffer`                                                        |
+    SetVendingMachinePrice { pricing: Pricing },
+    /// Buys `amount` prepaid credits of the vending machine, worth one token each. The tokens are paid once, like a
+    /// write would pay them, and later writes spend the credits instead.
+    ///
+    /// The first purchase creates the `credits` PDA of the user, derived from `vending_machine_buffer` and `user`.
+    /// Every purchase restarts the `credit_lifetime` of the machine, and credits that already expired are forfeited.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
+    /// | 1     | ✅       | ✅     | user: Authority of `user_token_account`, pays for the `credits` account                              |
+    /// | 2     | ✅       | ❌     | user_token_account: This is the token account that will pay for the credits                          |
+    /// | 3     | ✅       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 4     | ❌       | ❌     | token_program: Used to burn the vending machine tokens                                               |
+    /// | 5     | ✅       | ❌     | credits: PDA of the Echo Program holding the credits of `user`                                       |
+    /// | 6     | ❌       | ❌     | system_program: Used to allocate the `credits` account                                               |
+    /// | 7     | ✅       | ❌     | treasury: Token account collecting payments, only for `PaymentMode::Treasury`                       |
+    BuyVendingMachineCredits { amount: u64 },
+    /// Closes the `credits` account of `user` and returns its rent to `user`.
+    ///
+    /// If the machine refunds credits, the unspent credits are transferred back from the `treasury` to
+    /// `user_token_account`, otherwise they are forfeited. Expired credits are never refunded, and anyone may close
+    /// them without the signature of `user`. Credits of a machine that was closed are simply closed as well.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: Vending machine the credits were bought at                                  |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 2     | ✅       | ✅     | user: Owner of the credits, receives the rent. Only signs while the credits have not expired         |
+    /// | 3     | ✅       | ❌     | credits: PDA of the Echo Program holding the credits of `user`                                       |
+    /// | 4     | ✅       | ❌     | user_token_account: Receives the refund, only read when credits are refunded                         |
+    /// | 5     | ✅       | ❌     | treasury: Token account the refund is paid from, only read when credits are refunded                 |
+    /// | 6     | ❌       | ❌     | token_program: Used to transfer the refund, only read when credits are refunded                       |
+    CloseVendingMachineCredits,
+    /// Sets how long credits bought from now on stay spendable, in seconds (zero for no expiry), and whether unspent
+    /// credits are refunded when closed.
+    ///
+    /// Only the `owner` may change the policy. Refunds are only possible in `PaymentMode::Treasury`, and can only be
+    /// enabled while the `treasury` holds at least the outstanding credits. While they are enabled,
+    /// `WithdrawVendingMachineProceeds` keeps the outstanding credits in the `treasury`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                                         |
+    /// |-------|----------|--------|-----------------------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | vending_machine_buffer: PDA of the Echo Program that only holders of a particular token can write to |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: This is the token mint that is accepted by the `vending_machine_buffer`        |
+    /// | 2     | ❌       | ✅     | owner: Owner of the `vending_machine_buffer`                                                        |
+    /// | 3     | ❌       | ❌     | treasury: Token account collecting payments, only read when enabling refunds                        |
+    SetVendingMachineCreditPolicy {
+        credit_lifetime: u64,
+        refund_credits: bool,
+    },
+    /// Adds `new_writer` to the allow-list of an `authorized_buffer`, so it can sign `AuthorizedEcho` and
+    /// `AuthorizedEchoAt` like the `authority`. Resizing, closing and authority changes stay with the `authority`.
+    ///
+    /// Every writer gets its own `buffer_writer` PDA, derived from `authorized_buffer` and `new_writer`, paid for by the
+    /// `authority`. Writers only count while the authority that added them is still the `authority` of the buffer.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ✅       | ✅     | authority: Current authority of `authorized_buffer`, pays for the writer  |
+    /// | 2     | ✅       | ❌     | buffer_writer: PDA of the writer to create                                |
+    /// | 3     | ❌       | ❌     | system_program: Used to allocate the `buffer_writer`                      |
+    AddBufferWriter { new_writer: Pubkey },
+    /// Removes a writer from the allow-list of an `authorized_buffer` and returns the rent of its `buffer_writer` to
+    /// the authority that added it.
+    ///
+    /// The current `authority` may remove any writer. The authority that added a writer may also remove it after
+    /// handing the buffer over or closing it.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | authorized_buffer: Buffer the writer was added to                         |
+    /// | 1     | ❌       | ✅     | authority: Current authority, or the authority that added the writer      |
+    /// | 2     | ✅       | ❌     | buffer_writer: PDA of the writer to remove                                |
+    /// | 3     | ✅       | ❌     | recipient: Authority that added the writer, receives the rent             |
+    RemoveBufferWriter,
+    /// Permanently makes an `authorized_buffer` or `vending_machine_buffer` read-only.
+    ///
+    /// Once sealed, every write, resize and close of the buffer fails with `EchoError::BufferSealed`. There is no way
+    /// to unseal a buffer, so its echo data and rent stay in place for good. Authority changes and writers of an
+    /// `authorized_buffer` are not affected, they just cannot write anymore.
+    ///
+    /// An `authorized_buffer` is sealed by its `authority`, a `vending_machine_buffer` by its `owner`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                        |
+    /// |-------|----------|--------|------------------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | buffer: `authorized_buffer` or `vending_machine_buffer` to seal                    |
+    /// | 1     | ❌       | ✅     | authority: Authority of the `authorized_buffer`, or owner of the vending machine    |
+    /// | 2     | ❌       | ❌     | vending_machine_mint: Mint of the `vending_machine_buffer`, only for vending machines |
+    SealBuffer,
+    /// Closes an `authorized_buffer` whose expiry has passed and returns its rent to the `seed_authority` of the buffer,
+    /// the authority that created it, rather than its current `authority`.
+    ///
+    /// Anyone may send this instruction. It fails with `EchoError::BufferNotExpired` for buffers without an expiry or
+    /// before it has passed, and with `EchoError::BufferSealed` for sealed buffers.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: Expired buffer to close                                |
+    /// | 1     | ✅       | ❌     | recipient: `seed_authority` of the buffer, receives its lamports          |
+    ReclaimExpiredBuffer,
+    /// Returns `len` bytes of the echo data of an `authorized_buffer` or `vending_machine_buffer`, starting at byte
+    /// `offset` of the echo data, through `set_return_data`.
+    ///
+    /// The account type, layout version and PDA of the buffer are checked first, so programs reading a buffer through
+    /// CPI do not need to parse the header themselves. `decode_read_echo` turns the return data back into the bytes.
+    ///
+    /// If the read does not fit in the echo data, the instruction will fail with `EchoError::ReadOutOfBounds`. At most
+    /// `MAX_RETURN_DATA` (1024) bytes can be read at once, larger reads fail with `EchoError::ReadTooLarge`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                                        |
+    /// |-------|----------|--------|------------------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | buffer: `authorized_buffer` or `vending_machine_buffer` to read                    |
+    /// | 1     | ❌       | ❌     | vending_machine_mint: Mint of the `vending_machine_buffer`, only for vending machines |
+    ReadEcho { offset: u64, len: u64 },
+    /// Opens an upload of `len` bytes to an `authorized_buffer`, for payloads that do not fit in one transaction.
+    ///
+    /// A `staging_buffer` PDA, derived from `authorized_buffer` and `owner`, is created to hold the upload and paid
+    /// for by `owner`. It starts with only its header, as accounts created through CPI are limited to 10KiB.
+    /// `WriteChunk` then fills and grows it in chunks of `chunk_size` bytes, at most 10KiB each, and `CommitWrite`
+    /// replaces the echo data once all `len` bytes are staged and have the SHA-256 `hash`. The echo data is not touched before the commit, so an
+    /// interrupted upload never leaves it half-written. `CancelWrite` drops an upload.
+    ///
+    /// `len` must fit in the echo data. Only the `authority` or a writer added with `AddBufferWriter` can upload, writers
+    /// pass their `buffer_writer` account last.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | authorized_buffer: Buffer the upload is for                               |
+    /// | 1     | ✅       | ✅     | owner: Current authority, or a writer of `authorized_buffer`              |
+    /// | 2     | ✅       | ❌     | staging_buffer: PDA of the upload to create                               |
+    /// | 3     | ❌       | ❌     | system_program: Used to allocate the `staging_buffer`                     |
+    /// | 4     | ❌       | ❌     | buffer_writer: PDA of the writer, only needed when a writer signs         |
+    BeginWrite {
+        len: u64,
+        chunk_size: u64,
+        hash: Hash,
+    },
+    /// Copies `bytes` into the `staging_buffer` at byte `index * chunk_size` of the upload.
+    ///
+    /// `bytes` can be at most `chunk_size` long, the last chunk is usually shorter. Chunks can be sent in any order and
+    /// sent again to fix them.
+    ///
+    /// The `staging_buffer` grows up to the end of the chunk, and `owner` pays the rent of the growth. An account can
+    /// only grow by 10KiB per instruction, so a chunk that lands further past the staged bytes fails with
+    /// `EchoError::BufferGrowthTooLarge` until the chunks before it are written.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | staging_buffer: PDA of the upload                                         |
+    /// | 1     | ✅       | ✅     | owner: Account that opened the upload with `BeginWrite`, pays for growth  |
+    /// | 2     | ❌       | ❌     | system_program: Used to pay for a larger `staging_buffer`                 |
+    WriteChunk { index: u64, bytes: Vec<u8> },
+    /// Replaces the echo data of the `authorized_buffer` with the upload in the `staging_buffer`, and returns the rent of
+    /// the `staging_buffer` to `owner`.
+    ///
+    /// Fails with `EchoError::StagedHashMismatch` unless the staged bytes have the `hash` given to `BeginWrite`. Bytes
+    /// of the echo data past the upload are zeroed. `owner` must still be allowed to write to the buffer.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: Buffer the upload is for                               |
+    /// | 1     | ✅       | ✅     | owner: Account that opened the upload, receives the rent                  |
+    /// | 2     | ✅       | ❌     | staging_buffer: PDA of the upload                                         |
+    /// | 3     | ❌       | ❌     | buffer_writer: PDA of the writer, only needed when a writer signs         |
+    CommitWrite,
+    /// Drops an upload without touching the `authorized_buffer`, and returns the rent of the `staging_buffer` to
+    /// `owner`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | staging_buffer: PDA of the upload                                         |
+    /// | 1     | ✅       | ✅     | owner: Account that opened the upload, receives the rent                  |
+    CancelWrite,
+    /// Allocates `buffer_size` bytes to a `log_buffer` account, which keeps the records appended by `AppendEcho`
+    /// instead of a single echo.
+    ///
+    /// The first `LogBuffer::HEADER_SIZE` (139) bytes of log_buffer will be set with the following data:
+    ///     byte 0: account_type, `AccountType::LogBuffer`
+    ///     byte 1: version, `CURRENT_VERSION`
+    ///     byte 2: bump_seed
+    ///     bytes 3-10: buffer_seed
+    ///     bytes 11-42: authority
+    ///     bytes 43-50: head, offset in the ring of the oldest record
+    ///     bytes 51-58: tail, offset in the ring of the next record
+    ///     bytes 59-66: records, number of records in the ring
+    ///     bytes 67-74: appended, number of records ever appended
+    ///     bytes 75-106: content_hash, the SHA-256 hash of the ring
+    ///     bytes 107-138: reserved, zeroed
+    ///
+    /// The remaining bytes are the ring. Each record is a little-endian `u32` length followed by its bytes, and may
+    /// wrap around the end of the ring. `LogBuffer::records` decodes them in order.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | log_buffer: PDA of Echo Program that only `authority` can append to       |
+    /// | 1     | ✅       | ✅     | authority: Pubkey with sole write access to `log_buffer`, pays the rent   |
+    /// | 2     | ❌       | ❌     | system_program: Used to allocate the buffer                               |
+    InitializeLogEcho { buffer_seed: u64, buffer_size: u64 },
+    /// Appends `data` as a new record of the `log_buffer`.
+    ///
+    /// When the ring is full, the oldest records are dropped until the new one fits. Fails with
+    /// `EchoError::RecordTooLarge` if the record and its 4 byte length do not fit in the whole ring.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | log_buffer: PDA of Echo Program that only `authority` can append to       |
+    /// | 1     | ❌       | ✅     | authority: Pubkey with sole write access to `log_buffer`                  |
+    AppendEcho { data: Vec<u8> },
+    /// Allocates `buffer_size` bytes to the `mailbox` of `recipient`, where anyone can leave messages with
+    /// `SendMessage`.
+    ///
+    /// The first `Mailbox::HEADER_SIZE` (123) bytes of mailbox will be set with the following data:
+    ///     byte 0: account_type, `AccountType::Mailbox`
+    ///     byte 1: version, `CURRENT_VERSION`
+    ///     byte 2: bump_seed
+    ///     bytes 3-34: recipient
+    ///     bytes 35-66: postage_account, or zero if none is passed
+    ///     bytes 67-74: min_postage
+    ///     bytes 75-82: len, bytes of the queue taken by messages
+    ///     bytes 83-90: messages, number of messages in the queue
+    ///     bytes 91-122: reserved, zeroed
+    ///
+    /// The remaining bytes are the queue of Borsh-encoded `Message`s, oldest first. `Mailbox::messages` decodes them.
+    ///
+    /// Senders can pay postage in the token of a vending machine: `postage_account` must be a token account of the mint
+    /// of `vending_machine_buffer`, or the instruction fails with `EchoError::InvalidPostageAccount`. Every message must
+    /// pay at least `min_postage`, which needs a `postage_account`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | mailbox: PDA of Echo Program derived from `recipient`                     |
+    /// | 1     | ✅       | ✅     | recipient: Owner of the mailbox, pays the rent                            |
+    /// | 2     | ❌       | ❌     | system_program: Used to allocate the mailbox                              |
+    /// | 3     | ❌       | ❌     | postage_account: Token account receiving postage, optional                |
+    /// | 4     | ❌       | ❌     | vending_machine_buffer: Machine whose token pays postage, with account 3  |
+    /// | 5     | ❌       | ❌     | vending_machine_mint: Mint of `vending_machine_buffer`, with account 3    |
+    InitializeMailbox { buffer_size: u64, min_postage: u64 },
+    /// Adds a message from `sender` with `data` to the back of the `mailbox`.
+    ///
+    /// With a non-zero `postage`, `postage` tokens move from `sender_token_account` to the `postage_account` of the
+    /// mailbox, and the amount is recorded in the message. Fails with `EchoError::MailboxFull` if the queue has no room
+    /// left, and with `EchoError::MessageTooLarge` if the encoded message is over `MAX_RETURN_DATA` (1024) bytes.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | mailbox: Mailbox of the recipient                                         |
+    /// | 1     | ❌       | ✅     | sender: Account recorded as the sender of the message                     |
+    /// | 2     | ✅       | ❌     | sender_token_account: Token account paying postage, only with postage     |
+    /// | 3     | ✅       | ❌     | postage_account: Postage account of the mailbox, only with postage        |
+    /// | 4     | ❌       | ❌     | token_program: Used to transfer postage, only with postage                |
+    SendMessage { data: Vec<u8>, postage: u64 },
+    /// Removes the oldest message of the `mailbox` and sets it as the return data of the transaction, Borsh-encoded.
+    /// `decode_pop_message` decodes it.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | mailbox: Mailbox of the recipient                                         |
+    /// | 1     | ❌       | ✅     | recipient: Owner of the mailbox                                           |
+    PopMessage,
+    /// Removes every message of the `mailbox`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | mailbox: Mailbox of the recipient                                         |
+    /// | 1     | ❌       | ✅     | recipient: Owner of the mailbox                                           |
+    ClearMailbox,
+    /// Writes `data` to an `authorized_buffer` like `AuthorizedEcho`, with the authority signing off-chain instead of
+    /// signing the transaction, so a relayer can submit and pay for the write.
+    ///
+    /// The instruction right before must be an ed25519 program instruction that checks the signature of the
+    /// `authority` over `signed_echo_message(authorized_buffer, nonce, data)`, see `ed25519_signature_instruction`.
+    /// `nonce` must be the `nonce` stored in the buffer, which then goes up by one so the signed write cannot be
+    /// replayed.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ❌       | ❌     | instructions: Instructions sysvar, to read the ed25519 instruction        |
+    AuthorizedEchoSigned { data: Vec<u8>, nonce: u64 },
+    /// Fails with `EchoError::ContentHashMismatch` unless the echo data of the `buffer` has the SHA-256 hash
+    /// `expected_hash`, so a transaction can assert on the contents of a buffer.
+    ///
+    /// `buffer` can be an `authorized_buffer`, a `vending_machine_buffer`, a `token_gated_buffer` or a `log_buffer`,
+    /// whose whole ring is hashed. The `content_hash` recorded in the header by every write is compared, the data is
+    /// not hashed again. It is zero for authorized buffers not written since hashes were added, which never match.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | buffer: Buffer to check                                                   |
+    VerifyEcho { expected_hash: Hash },
+    /// Puts the `authorized_buffer` in Merkle mode: the echo data becomes the `root` of a Merkle tree of `leaf_count`
+    /// chunks kept off-chain, followed by zeroes, and `VerifyChunk` checks chunks against it. See `merkle::MerkleTree`
+    /// to build the tree.
+    ///
+    /// Fails with `EchoError::BufferTooSmall` if the echo data is shorter than a hash, and with
+    /// `EchoError::EmptyMerkleTree` if `leaf_count` is zero. Writing the buffer in any other way clears the
+    /// leaf count, so `VerifyChunk` fails until the root is set again.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | authorized_buffer: PDA of Echo Program that only `authority` can write to |
+    /// | 1     | ❌       | ✅     | authority: Current authority, or a writer of `authorized_buffer`          |
+    /// | 2     | ❌       | ❌     | buffer_writer: PDA of the writer, only needed when a writer signs         |
+    SetMerkleRoot { root: Hash, leaf_count: u64 },
+    /// Fails with `EchoError::InvalidMerkleProof` unless `proof` shows that `leaf` is the chunk at `index` of the
+    /// Merkle tree whose root was set with `SetMerkleRoot`, and with `EchoError::NotMerkleBuffer` if none was.
+    ///
+    /// `proof` lists the sibling hashes from the leaf up to the root, as `merkle::MerkleTree::proof` returns them.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ❌       | ❌     | authorized_buffer: Buffer holding the Merkle root                        |
+    VerifyChunk {
+        index: u64,
+        leaf: Vec<u8>,
+        proof: Vec<Hash>,
+    },
+    /// Allocates `buffer_size` bytes to a `token_gated_buffer` account, which whoever holds at least `min_balance`
+    /// tokens of `mint` can write to with `TokenGatedEcho`. With a supply-1 mint and a `min_balance` of 1, control of
+    /// the buffer moves with the NFT.
+    ///
+    /// The first `TokenGatedBuffer::HEADER_SIZE` (147) bytes of token_gated_buffer will be set with the following data:
+    ///     byte 0: account_type, `AccountType::TokenGatedBuffer`
+    ///     byte 1: version, `CURRENT_VERSION`
+    ///     byte 2: bump_seed
+    ///     bytes 3-10: buffer_seed
+    ///     bytes 11-42: creator
+    ///     bytes 43-74: mint
+    ///     bytes 75-82: min_balance
+    ///     bytes 83-114: content_hash, the SHA-256 hash of the echo data
+    ///     byte 115: content_type, the `ContentType` of `content`, `ContentType::Bytes` without one
+    ///     bytes 116-123: schema_id, the schema id of `content`, zero without one
+    ///     bytes 124-146: reserved, zeroed
+    ///
+    /// Fails with `EchoError::ZeroMinBalance` if `min_balance` is zero. The `content` header is checked by
+    /// every `TokenGatedEcho` like for `InitializeAuthorizedEcho`.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | token_gated_buffer: PDA of Echo Program derived from `mint` and `creator` |
+    /// | 1     | ❌       | ❌     | mint: Mint whose holders can write to the buffer                          |
+    /// | 2     | ✅       | ✅     | creator: Pays the rent, has no control over the buffer afterwards         |
+    /// | 3     | ❌       | ❌     | system_program: Used to allocate the buffer                               |
+    InitializeTokenGatedEcho {
+        buffer_seed: u64,
+        buffer_size: u64,
+        min_balance: u64,
+        content: Option<ContentHeader>,
+    },
+    /// Writes `data` to a `token_gated_buffer` like `AuthorizedEcho`, for a `holder` whose `holder_token_account`
+    /// holds at least the `min_balance` of the mint of the buffer. Unlike `VendingMachineEcho`, no tokens are burned
+    /// or moved.
+    ///
+    /// Fails with `EchoError::InvalidGateTokenAccount` if the token account is not of the mint or not owned by
+    /// `holder`, with `EchoError::InsufficientGateBalance` if it holds too few tokens, and with
+    /// `EchoError::InvalidUtf8` if the buffer holds text and the echo data is not valid UTF-8 afterwards.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | token_gated_buffer: PDA of Echo Program for holders of a mint             |
+    /// | 1     | ❌       | ✅     | holder: Owner of `holder_token_account`                                   |
+    /// | 2     | ❌       | ❌     | holder_token_account: Token account of the mint of the buffer             |
+    TokenGatedEcho { data: Vec<u8> },
+    /// Closes a `log_buffer` created by `InitializeLogEcho` and returns its rent to `recipient`, like
+    /// `CloseAuthorizedEcho` does for an `authorized_buffer`.
+    ///
+    /// Only the `authority` that created the buffer may close it.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | log_buffer: PDA of Echo Program that only `authority` can append to       |
+    /// | 1     | ❌       | ✅     | authority: Pubkey with sole write access to `log_buffer`                  |
+    /// | 2     | ✅       | ❌     | recipient: Account that receives the lamports of `log_buffer`             |
+    CloseLogEcho,
+    /// Closes the `mailbox` of `recipient` and returns its rent to `destination`, like `CloseAuthorizedEcho` does for
+    /// an `authorized_buffer`. Messages still in the queue are dropped.
+    ///
+    /// Only the `recipient` of the mailbox may close it, and can open a new one with `InitializeMailbox` afterwards.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | mailbox: Mailbox of the recipient                                         |
+    /// | 1     | ❌       | ✅     | recipient: Owner of the mailbox                                           |
+    /// | 2     | ✅       | ❌     | destination: Account that receives the lamports of `mailbox`              |
+    CloseMailbox,
+    /// Closes a `token_gated_buffer` created by `InitializeTokenGatedEcho` and returns its rent to `recipient`, like
+    /// `CloseAuthorizedEcho` does for an `authorized_buffer`.
+    ///
+    /// Only the `creator` that paid for the buffer may close it, holders of the mint can only write to it.
+    ///
+    /// Accounts:
+    /// | index | writable | signer | description                                                              |
+    /// |-------|----------|--------|--------------------------------------------------------------------------|
+    /// | 0     | ✅       | ❌     | token_gated_buffer: PDA of Echo Program derived from `mint` and `creator` |
+    /// | 1     | ❌       | ✅     | creator: Pubkey that initialized `token_gated_buffer`                     |
+    /// | 2     | ✅       | ❌     | recipient: Account that receives the lamports of `token_gated_buffer`     |
+    CloseTokenGatedEcho,
+}
+
+/// Creates an `Echo` instruction.
+pub fn echo(program_id: &Pubkey, echo_buffer: &Pubkey, data: Vec<u8>) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::Echo { data },
+        vec![AccountMeta::new(*echo_buffer, false)],
+    )
+}
+
+/// Creates an `InitializeAuthorizedEcho` instruction for the `authorized_buffer` derived from `authority` and
+/// `buffer_seed`.
+pub fn initialize_authorized_echo(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    buffer_size: u64,
+    expiry: Option<Expiry>,
+    content: Option<ContentHeader>,
+) -> Instruction {
+    let (authorized_buffer, _) = find_authorized_buffer_address(program_id, authority, buffer_seed);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::InitializeAuthorizedEcho {
+            buffer_seed,
+            buffer_size,
+            expiry,
+            content,
+        },
+        vec![
+            AccountMeta::new(authorized_buffer, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates an `AuthorizedEcho` instruction.
+pub fn authorized_echo(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AuthorizedEcho { data },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// Creates an `AuthorizedEcho` instruction signed by `writer`, a writer added with `AddBufferWriter`.
+pub fn authorized_writer_echo(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    writer: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    let (buffer_writer, _) = find_buffer_writer_address(program_id, authorized_buffer, writer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AuthorizedEcho { data },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*writer, true),
+            AccountMeta::new_readonly(buffer_writer, false),
+        ],
+    )
+}
+
+/// Creates an `InitializeVendingMachineEcho` instruction for the `vending_machine_buffer` derived from
+/// `vending_machine_mint` and `machine_id`.
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_vending_machine_echo(
+    program_id: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    payer: &Pubkey,
+    machine_id: u64,
+    pricing: Pricing,
+    buffer_size: usize,
+    payment_mode: PaymentMode,
+    content: Option<ContentHeader>,
+) -> Instruction {
+    let (vending_machine_buffer, _) =
+        find_vending_machine_address(program_id, vending_machine_mint, machine_id);
+    let mut accounts = vec![
+        AccountMeta::new(vending_machine_buffer, false),
+        AccountMeta::new_readonly(*vending_machine_mint, false),
+        AccountMeta::new(*payer, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if payment_mode == PaymentMode::Treasury {
+        let (treasury, _) = find_treasury_address(program_id, &vending_machine_buffer);
+        accounts.extend([
+            AccountMeta::new(treasury, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+        ]);
+    }
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::InitializeVendingMachineEcho {
+            machine_id,
+            pricing,
+            buffer_size,
+            payment_mode,
+            content,
+        },
+        accounts,
+    )
+}
+
+/// Creates a `VendingMachineEcho` instruction. To pay with prepaid credits, pass the credits account from
+/// `find_credits_address` as `user_token_account`.
+pub fn vending_machine_echo(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    data: Vec<u8>,
+    max_charge: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::VendingMachineEcho { data, max_charge },
+        vending_machine_write_accounts(
+            program_id,
+            vending_machine_buffer,
+            user,
+            user_token_account,
+            vending_machine_mint,
+        ),
+    )
+}
+
+/// Creates a `CloseAuthorizedEcho` instruction.
+pub fn close_authorized_echo(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CloseAuthorizedEcho,
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Creates a `CloseVendingMachineEcho` instruction. The treasury accounts are always passed, the program only reads
+/// them in `PaymentMode::Treasury`.
+pub fn close_vending_machine_echo(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    let (treasury, _) = find_treasury_address(program_id, vending_machine_buffer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CloseVendingMachineEcho,
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// Creates a `ResizeAuthorizedEcho` instruction.
+pub fn resize_authorized_echo(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    new_size: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::ResizeAuthorizedEcho { new_size },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates an `AuthorizedEchoAt` instruction.
+pub fn authorized_echo_at(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    offset: u64,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AuthorizedEchoAt { offset, data },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// Creates an `AuthorizedEchoAt` instruction signed by `writer`, a writer added with `AddBufferWriter`.
+pub fn authorized_writer_echo_at(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    writer: &Pubkey,
+    offset: u64,
+    data: Vec<u8>,
+) -> Instruction {
+    let (buffer_writer, _) = find_buffer_writer_address(program_id, authorized_buffer, writer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AuthorizedEchoAt { offset, data },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*writer, true),
+            AccountMeta::new_readonly(buffer_writer, false),
+        ],
+    )
+}
+
+/// Creates a `VendingMachineEchoAt` instruction. To pay with prepaid credits, pass the credits account from
+/// `find_credits_address` as `user_token_account`.
+#[allow(clippy::too_many_arguments)]
+pub fn vending_machine_echo_at(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    offset: u64,
+    data: Vec<u8>,
+    max_charge: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::VendingMachineEchoAt {
+            offset,
+            data,
+            max_charge,
+        },
+        vending_machine_write_accounts(
+            program_id,
+            vending_machine_buffer,
+            user,
+            user_token_account,
+            vending_machine_mint,
+        ),
+    )
+}
+
+/// Creates a `SetBufferAuthority` instruction.
+pub fn set_buffer_authority(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    new_authority: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SetBufferAuthority {
+            new_authority: *new_authority,
+        },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// Creates an `AcceptBufferAuthority` instruction.
+pub fn accept_buffer_authority(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    pending_authority: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AcceptBufferAuthority,
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*pending_authority, true),
+        ],
+    )
+}
+
+/// Creates a `MigrateAuthorizedEcho` instruction.
+pub fn migrate_authorized_echo(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::MigrateAuthorizedEcho,
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `MigrateVendingMachineEcho` instruction.
+pub fn migrate_vending_machine_echo(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    mint_authority: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::MigrateVendingMachineEcho,
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+            AccountMeta::new(*mint_authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `WithdrawVendingMachineProceeds` instruction.
+pub fn withdraw_vending_machine_proceeds(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    owner: &Pubkey,
+    destination: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (treasury, _) = find_treasury_address(program_id, vending_machine_buffer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::WithdrawVendingMachineProceeds { amount },
+        vec![
+            AccountMeta::new_readonly(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new(*destination, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// Creates a `SetVendingMachinePrice` instruction.
+pub fn set_vending_machine_price(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    owner: &Pubkey,
+    pricing: Pricing,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SetVendingMachinePrice { pricing },
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+            AccountMeta::new_readonly(*owner, true),
+        ],
+    )
+}
+
+/// Creates a `BuyVendingMachineCredits` instruction for the credits account of `user`.
+pub fn buy_vending_machine_credits(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    amount: u64,
+) -> Instruction {
+    let (credits, _) = find_credits_address(program_id, vending_machine_buffer, user);
+    let (treasury, _) = find_treasury_address(program_id, vending_machine_buffer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::BuyVendingMachineCredits { amount },
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new(*user, true),
+            AccountMeta::new(*user_token_account, false),
+            AccountMeta::new(*vending_machine_mint, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(credits, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new(treasury, false),
+        ],
+    )
+}
+
+/// Creates a `CloseVendingMachineCredits` instruction signed by `user`. The refund accounts are always passed, the
+/// program only reads them when the machine refunds credits.
+pub fn close_vending_machine_credits(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+) -> Instruction {
+    let (credits, _) = find_credits_address(program_id, vending_machine_buffer, user);
+    let (treasury, _) = find_treasury_address(program_id, vending_machine_buffer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CloseVendingMachineCredits,
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+            AccountMeta::new(*user, true),
+            AccountMeta::new(credits, false),
+            AccountMeta::new(*user_token_account, false),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// Creates a `SetVendingMachineCreditPolicy` instruction.
+pub fn set_vending_machine_credit_policy(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    owner: &Pubkey,
+    credit_lifetime: u64,
+    refund_credits: bool,
+) -> Instruction {
+    let (treasury, _) = find_treasury_address(program_id, vending_machine_buffer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SetVendingMachineCreditPolicy {
+            credit_lifetime,
+            refund_credits,
+        },
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(treasury, false),
+        ],
+    )
+}
+
+/// Creates an `AddBufferWriter` instruction.
+pub fn add_buffer_writer(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    writer: &Pubkey,
+) -> Instruction {
+    let (buffer_writer, _) = find_buffer_writer_address(program_id, authorized_buffer, writer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AddBufferWriter {
+            new_writer: *writer,
+        },
+        vec![
+            AccountMeta::new_readonly(*authorized_buffer, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(buffer_writer, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `RemoveBufferWriter` instruction that returns the rent to `recipient`, the authority that added
+/// `writer`.
+pub fn remove_buffer_writer(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    writer: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    let (buffer_writer, _) = find_buffer_writer_address(program_id, authorized_buffer, writer);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::RemoveBufferWriter,
+        vec![
+            AccountMeta::new_readonly(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(buffer_writer, false),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Creates a `SealBuffer` instruction for an `authorized_buffer`.
+pub fn seal_authorized_buffer(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SealBuffer,
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// Creates a `SealBuffer` instruction for a `vending_machine_buffer`.
+pub fn seal_vending_machine_buffer(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    owner: &Pubkey,
+    vending_machine_mint: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SealBuffer,
+        vec![
+            AccountMeta::new(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+        ],
+    )
+}
+
+/// Creates a `ReclaimExpiredBuffer` instruction that returns the rent to `seed_authority`, the authority that created
+/// the buffer.
+pub fn reclaim_expired_buffer(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    seed_authority: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::ReclaimExpiredBuffer,
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new(*seed_authority, false),
+        ],
+    )
+}
+
+/// Creates a `ReadEcho` instruction for an `authorized_buffer`.
+pub fn read_authorized_echo(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    offset: u64,
+    len: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::ReadEcho { offset, len },
+        vec![AccountMeta::new_readonly(*authorized_buffer, false)],
+    )
+}
+
+/// Creates a `ReadEcho` instruction for a `vending_machine_buffer`.
+pub fn read_vending_machine_echo(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    vending_machine_mint: &Pubkey,
+    offset: u64,
+    len: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::ReadEcho { offset, len },
+        vec![
+            AccountMeta::new_readonly(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+        ],
+    )
+}
+
+/// Decodes the return data of a `ReadEcho` instruction of `len` bytes sent to `program_id`, as returned by
+/// `get_return_data` right after the CPI.
+///
+/// Fails with `EchoError::InvalidReturnData` if the return data was set by another program or is longer than `len`.
+/// Trailing zero bytes missing from the return data are restored, since some RPC responses trim them.
+pub fn decode_read_echo(
+    program_id: &Pubkey,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+    len: u64,
+) -> Result<Vec<u8>, ProgramError> {
+    let len = usize::try_from(len).map_err(|_| EchoError::InvalidReturnData)?;
+    match return_data {
+        Some((return_program_id, mut data))
+            if return_program_id == *program_id && data.len() <= len =>
+        {
+            data.resize(len, 0);
+            Ok(data)
+        }
+        // Nothing is returned for a read of zero bytes
+        None if len == 0 => Ok(Vec::new()),
+        _ => Err(EchoError::InvalidReturnData.into()),
+    }
+}
+
+/// Creates a `BeginWrite` instruction signed by `owner`, the authority of the buffer.
+pub fn begin_write(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+    len: u64,
+    chunk_size: u64,
+    hash: Hash,
+) -> Instruction {
+    let (staging_buffer, _) = find_staging_address(program_id, authorized_buffer, owner);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::BeginWrite {
+            len,
+            chunk_size,
+            hash,
+        },
+        vec![
+            AccountMeta::new_readonly(*authorized_buffer, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(staging_buffer, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `WriteChunk` instruction.
+pub fn write_chunk(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+    index: u64,
+    bytes: Vec<u8>,
+) -> Instruction {
+    let (staging_buffer, _) = find_staging_address(program_id, authorized_buffer, owner);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::WriteChunk { index, bytes },
+        vec![
+            AccountMeta::new(staging_buffer, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `CommitWrite` instruction signed by `owner`, the authority of the buffer.
+pub fn commit_write(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    let (staging_buffer, _) = find_staging_address(program_id, authorized_buffer, owner);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CommitWrite,
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new(*owner, true),
+            AccountMeta::new(staging_buffer, false),
+        ],
+    )
+}
+
+/// Creates a `CancelWrite` instruction.
+pub fn cancel_write(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+) -> Instruction {
+    let (staging_buffer, _) = find_staging_address(program_id, authorized_buffer, owner);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CancelWrite,
+        vec![
+            AccountMeta::new(staging_buffer, false),
+            AccountMeta::new(*owner, true),
+        ],
+    )
+}
+
+/// Creates the instructions that upload `data` to an `authorized_buffer` in chunks of `chunk_size` bytes:
+/// `BeginWrite`, one `WriteChunk` per chunk, then `CommitWrite`.
+///
+/// Each instruction is meant for its own transaction. The chunks can be sent in any order, but all of them must land
+/// after `BeginWrite` and before `CommitWrite`.
+///
+/// Returns `None` if `chunk_size` is zero, which `BeginWrite` rejects.
+pub fn staged_write(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+    data: &[u8],
+    chunk_size: usize,
+) -> Option<Vec<Instruction>> {
+    if chunk_size == 0 {
+        return None;
+    }
+    let mut instructions = vec![begin_write(
+        program_id,
+        authorized_buffer,
+        owner,
+        data.len() as u64,
+        chunk_size as u64,
+        hash(data),
+    )];
+    instructions.extend(data.chunks(chunk_size).enumerate().map(|(index, bytes)| {
+        write_chunk(
+            program_id,
+            authorized_buffer,
+            owner,
+            index as u64,
+            bytes.to_vec(),
+        )
+    }));
+    instructions.push(commit_write(program_id, authorized_buffer, owner));
+    Some(instructions)
+}
+
+/// Creates an `InitializeLogEcho` instruction.
+pub fn initialize_log_echo(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    buffer_size: u64,
+) -> Instruction {
+    let (log_buffer, _) = find_log_buffer_address(program_id, authority, buffer_seed);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::InitializeLogEcho {
+            buffer_seed,
+            buffer_size,
+        },
+        vec![
+            AccountMeta::new(log_buffer, false),
+            AccountMeta::new(*authority, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates an `AppendEcho` instruction.
+pub fn append_echo(
+    program_id: &Pubkey,
+    log_buffer: &Pubkey,
+    authority: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AppendEcho { data },
+        vec![
+            AccountMeta::new(*log_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// Creates an `InitializeMailbox` instruction. `postage` is only needed to accept postage, as the
+/// `(postage_account, vending_machine_buffer, vending_machine_mint)` the postage is paid to and in.
+pub fn initialize_mailbox(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    buffer_size: u64,
+    min_postage: u64,
+    postage: Option<(&Pubkey, &Pubkey, &Pubkey)>,
+) -> Instruction {
+    let (mailbox, _) = find_mailbox_address(program_id, recipient);
+    let mut accounts = vec![
+        AccountMeta::new(mailbox, false),
+        AccountMeta::new(*recipient, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some((postage_account, vending_machine_buffer, vending_machine_mint)) = postage {
+        accounts.extend([
+            AccountMeta::new_readonly(*postage_account, false),
+            AccountMeta::new_readonly(*vending_machine_buffer, false),
+            AccountMeta::new_readonly(*vending_machine_mint, false),
+        ]);
+    }
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::InitializeMailbox {
+            buffer_size,
+            min_postage,
+        },
+        accounts,
+    )
+}
+
+/// Creates a `SendMessage` instruction without postage.
+pub fn send_message(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    sender: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    let (mailbox, _) = find_mailbox_address(program_id, recipient);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SendMessage { data, postage: 0 },
+        vec![
+            AccountMeta::new(mailbox, false),
+            AccountMeta::new_readonly(*sender, true),
+        ],
+    )
+}
+
+/// Creates a `SendMessage` instruction that pays `postage` tokens from `sender_token_account`.
+pub fn send_message_with_postage(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    sender: &Pubkey,
+    sender_token_account: &Pubkey,
+    postage_account: &Pubkey,
+    data: Vec<u8>,
+    postage: u64,
+) -> Instruction {
+    let (mailbox, _) = find_mailbox_address(program_id, recipient);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SendMessage { data, postage },
+        vec![
+            AccountMeta::new(mailbox, false),
+            AccountMeta::new_readonly(*sender, true),
+            AccountMeta::new(*sender_token_account, false),
+            AccountMeta::new(*postage_account, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+/// Creates a `PopMessage` instruction.
+pub fn pop_message(program_id: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let (mailbox, _) = find_mailbox_address(program_id, recipient);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::PopMessage,
+        vec![
+            AccountMeta::new(mailbox, false),
+            AccountMeta::new_readonly(*recipient, true),
+        ],
+    )
+}
+
+/// Creates a `ClearMailbox` instruction.
+pub fn clear_mailbox(program_id: &Pubkey, recipient: &Pubkey) -> Instruction {
+    let (mailbox, _) = find_mailbox_address(program_id, recipient);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::ClearMailbox,
+        vec![
+            AccountMeta::new(mailbox, false),
+            AccountMeta::new_readonly(*recipient, true),
+        ],
+    )
+}
+
+/// Creates a `CloseMailbox` instruction for the mailbox of `recipient`.
+pub fn close_mailbox(program_id: &Pubkey, recipient: &Pubkey, destination: &Pubkey) -> Instruction {
+    let (mailbox, _) = find_mailbox_address(program_id, recipient);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CloseMailbox,
+        vec![
+            AccountMeta::new(mailbox, false),
+            AccountMeta::new_readonly(*recipient, true),
+            AccountMeta::new(*destination, false),
+        ],
+    )
+}
+
+/// Decodes the return data of a `PopMessage` instruction sent to `program_id`, as returned by `get_return_data`.
+///
+/// Like `decode_read_echo`, trailing zero bytes missing from the return data are restored.
+pub fn decode_pop_message(
+    program_id: &Pubkey,
+    return_data: Option<(Pubkey, Vec<u8>)>,
+) -> Result<Message, ProgramError> {
+    let mut data = match return_data {
+        Some((return_program_id, data)) if return_program_id == *program_id => data,
+        _ => return Err(EchoError::InvalidReturnData.into()),
+    };
+    if data.len() < MESSAGE_HEADER_SIZE {
+        data.resize(MESSAGE_HEADER_SIZE, 0);
+    }
+    let mut data_len = [0; 4];
+    data_len.copy_from_slice(&data[MESSAGE_HEADER_SIZE - 4..MESSAGE_HEADER_SIZE]);
+    let message_len = MESSAGE_HEADER_SIZE + u32::from_le_bytes(data_len) as usize;
+    if data.len() < message_len {
+        data.resize(message_len, 0);
+    }
+    Message::try_from_slice(&data).map_err(|_| EchoError::InvalidReturnData.into())
+}
+
+/// Creates an `AuthorizedEchoSigned` instruction. It must come right after the `ed25519_signature_instruction` of the
+/// authority's signature over `signed_echo_message(authorized_buffer, nonce, &data)`.
+pub fn authorized_echo_signed(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    data: Vec<u8>,
+    nonce: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::AuthorizedEchoSigned { data, nonce },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        ],
+    )
+}
+
+/// Creates an ed25519 program instruction that checks the `signature` of `signer` over `message`, with the key,
+/// signature and message all in its own data as `AuthorizedEchoSigned` expects.
+pub fn ed25519_signature_instruction(
+    signer: &Pubkey,
+    signature: &[u8; 64],
+    message: &[u8],
+) -> Instruction {
+    // Signature count and padding, then the offsets, then the key, signature and message they point at
+    let public_key_offset: u16 = 16;
+    let signature_offset = public_key_offset + 32;
+    let message_offset = signature_offset + 64;
+    let mut data = vec![1, 0];
+    for offset in [
+        signature_offset,
+        u16::MAX,
+        public_key_offset,
+        u16::MAX,
+        message_offset,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: ed25519_program::id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Creates a `VerifyEcho` instruction.
+pub fn verify_echo(program_id: &Pubkey, buffer: &Pubkey, expected_hash: Hash) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::VerifyEcho { expected_hash },
+        vec![AccountMeta::new_readonly(*buffer, false)],
+    )
+}
+
+/// Creates a `SetMerkleRoot` instruction signed by `authority`.
+pub fn set_merkle_root(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    authority: &Pubkey,
+    root: Hash,
+    leaf_count: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::SetMerkleRoot { root, leaf_count },
+        vec![
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+        ],
+    )
+}
+
+/// Creates a `VerifyChunk` instruction.
+pub fn verify_chunk(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    index: u64,
+    leaf: Vec<u8>,
+    proof: Vec<Hash>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::VerifyChunk { index, leaf, proof },
+        vec![AccountMeta::new_readonly(*authorized_buffer, false)],
+    )
+}
+
+/// Creates an `InitializeTokenGatedEcho` instruction for the `token_gated_buffer` of `mint` at `buffer_seed`.
+pub fn initialize_token_gated_echo(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    creator: &Pubkey,
+    buffer_seed: u64,
+    buffer_size: u64,
+    min_balance: u64,
+    content: Option<ContentHeader>,
+) -> Instruction {
+    let (token_gated_buffer, _) = find_token_gated_address(program_id, mint, creator, buffer_seed);
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::InitializeTokenGatedEcho {
+            buffer_seed,
+            buffer_size,
+            min_balance,
+            content,
+        },
+        vec![
+            AccountMeta::new(token_gated_buffer, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new(*creator, true),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+    )
+}
+
+/// Creates a `TokenGatedEcho` instruction signed by `holder`.
+pub fn token_gated_echo(
+    program_id: &Pubkey,
+    token_gated_buffer: &Pubkey,
+    holder: &Pubkey,
+    holder_token_account: &Pubkey,
+    data: Vec<u8>,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::TokenGatedEcho { data },
+        vec![
+            AccountMeta::new(*token_gated_buffer, false),
+            AccountMeta::new_readonly(*holder, true),
+            AccountMeta::new_readonly(*holder_token_account, false),
+        ],
+    )
+}
+
+/// Creates a `CloseLogEcho` instruction.
+pub fn close_log_echo(
+    program_id: &Pubkey,
+    log_buffer: &Pubkey,
+    authority: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CloseLogEcho,
+        vec![
+            AccountMeta::new(*log_buffer, false),
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Creates a `CloseTokenGatedEcho` instruction.
+pub fn close_token_gated_echo(
+    program_id: &Pubkey,
+    token_gated_buffer: &Pubkey,
+    creator: &Pubkey,
+    recipient: &Pubkey,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &EchoInstruction::CloseTokenGatedEcho,
+        vec![
+            AccountMeta::new(*token_gated_buffer, false),
+            AccountMeta::new_readonly(*creator, true),
+            AccountMeta::new(*recipient, false),
+        ],
+    )
+}
+
+/// Accounts of `VendingMachineEcho` and `VendingMachineEchoAt`. The treasury is always passed, the program only
+/// reads it in `PaymentMode::Treasury`.
+fn vending_machine_write_accounts(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    user: &Pubkey,
+    user_token_account: &Pubkey,
+    vending_machine_mint: &Pubkey,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(*vending_machine_buffer, false),
+        AccountMeta::new_readonly(*user, true),
+        AccountMeta::new(*user_token_account, false),
+        AccountMeta::new(*vending_machine_mint, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(
+            find_treasury_address(program_id, vending_machine_buffer).0,
+            false,
+        ),
+    ]
 }
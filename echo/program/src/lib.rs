@@ -1,5 +1,11 @@
+#[cfg(feature = "cpi")]
+pub mod cpi;
+#[cfg(not(feature = "no-entrypoint"))]
 pub mod entrypoint;
 pub mod error;
 pub mod instruction;
+pub mod merkle;
+pub mod pda;
 pub mod processor;
 pub mod state;
+pub mod utils;
@@ -0,0 +1,110 @@
+//! SHA-256 Merkle trees over chunks of data, for buffers that only keep the root on-chain.
+//!
+//! Leaves and inner nodes are hashed with different prefixes, so a leaf can never be passed off as a node. A node
+//! without a sibling at the end of a level moves up unchanged rather than being paired with itself. Its proofs
+//! therefore skip that level, which is why verifying needs the number of leaves.
+//!
+//! `VerifyChunk` checks proofs with `verify_proof`, and clients build the same trees with `MerkleTree`.
+
+use solana_program::hash::{hashv, Hash};
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Hash of a leaf with the bytes `leaf`.
+pub fn hash_leaf(leaf: &[u8]) -> Hash {
+    hashv(&[LEAF_PREFIX, leaf])
+}
+
+/// Hash of the node above `left` and `right`.
+pub fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    hashv(&[NODE_PREFIX, left.as_ref(), right.as_ref()])
+}
+
+/// Checks that `proof` shows `leaf` at `index` of a tree of `leaf_count` leaves with the given `root`.
+pub fn verify_proof(root: &Hash, leaf_count: u64, index: u64, leaf: &[u8], proof: &[Hash]) -> bool {
+    if index >= leaf_count {
+        return false;
+    }
+    let mut siblings = proof.iter();
+    let mut node = hash_leaf(leaf);
+    let (mut index, mut width) = (index, leaf_count);
+    while width > 1 {
+        if index % 2 == 1 {
+            match siblings.next() {
+                Some(sibling) => node = hash_node(sibling, &node),
+                None => return false,
+            }
+        } else if index + 1 < width {
+            match siblings.next() {
+                Some(sibling) => node = hash_node(&node, sibling),
+                None => return false,
+            }
+        }
+        index /= 2;
+        width = (width + 1) / 2;
+    }
+    siblings.next().is_none() && node == *root
+}
+
+/// Merkle tree with every level kept in memory, to get the root and the proof of any leaf.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleTree {
+    /// Levels from the leaf hashes up to the root.
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleTree {
+    /// Builds the tree of `leaves`, or returns `None` if there are none.
+    pub fn new<T: AsRef<[u8]>>(leaves: impl IntoIterator<Item = T>) -> Option<Self> {
+        let mut levels = vec![leaves
+            .into_iter()
+            .map(|leaf| hash_leaf(leaf.as_ref()))
+            .collect::<Vec<_>>()];
+        if levels[0].is_empty() {
+            return None;
+        }
+        while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+            let next = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => hash_node(left, right),
+                    [node] => *node,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(next);
+        }
+        Some(Self { levels })
+    }
+
+    /// Builds the tree whose leaves are the chunks of `chunk_size` bytes of `data`, the last one possibly shorter.
+    /// Returns `None` if `data` is empty or `chunk_size` is zero.
+    pub fn from_chunks(data: &[u8], chunk_size: usize) -> Option<Self> {
+        if chunk_size == 0 {
+            return None;
+        }
+        Self::new(data.chunks(chunk_size))
+    }
+
+    pub fn root(&self) -> Hash {
+        self.levels[self.levels.len() - 1][0]
+    }
+
+    pub fn leaf_count(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    /// Sibling hashes from the leaf at `index` up to the root, as `verify_proof` expects them.
+    pub fn proof(&self, index: usize) -> Vec<Hash> {
+        let mut index = index;
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
@@ -0,0 +1,282 @@
+use solana_program::pubkey::{Pubkey, PubkeyError};
+
+/// Seed prefix of `authorized_buffer` PDAs.
+pub const AUTHORIZED_BUFFER_SEED: &[u8] = b"authority";
+/// Seed prefix of `vending_machine_buffer` PDAs.
+pub const VENDING_MACHINE_SEED: &[u8] = b"vending_machine";
+/// Seed prefix of vending machine treasury token account PDAs.
+pub const TREASURY_SEED: &[u8] = b"treasury";
+/// Seed prefix of prepaid vending machine credits PDAs.
+pub const CREDITS_SEED: &[u8] = b"credits";
+/// Seed prefix of `authorized_buffer` writer PDAs.
+pub const BUFFER_WRITER_SEED: &[u8] = b"writer";
+/// Seed prefix of staging buffer PDAs.
+pub const STAGING_SEED: &[u8] = b"staging";
+/// Seed prefix of `log_buffer` PDAs.
+pub const LOG_BUFFER_SEED: &[u8] = b"log";
+/// Seed prefix of `mailbox` PDAs.
+pub const MAILBOX_SEED: &[u8] = b"mailbox";
+/// Seed prefix of `token_gated_buffer` PDAs.
+pub const TOKEN_GATED_SEED: &[u8] = b"token_gated";
+
+/// Finds the `authorized_buffer` created by `authority` with `buffer_seed`.
+///
+/// `authority` is the key that initialized the buffer (its `seed_authority`), even if the buffer authority was
+/// transferred since.
+pub fn find_authorized_buffer_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            AUTHORIZED_BUFFER_SEED,
+            authority.as_ref(),
+            &buffer_seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the `authorized_buffer` address from a known bump seed.
+pub fn create_authorized_buffer_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            AUTHORIZED_BUFFER_SEED,
+            authority.as_ref(),
+            &buffer_seed.to_le_bytes(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// Finds the allow-list entry of `writer` on `authorized_buffer`.
+pub fn find_buffer_writer_address(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    writer: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            BUFFER_WRITER_SEED,
+            authorized_buffer.as_ref(),
+            writer.as_ref(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the writer address from a known bump seed.
+pub fn create_buffer_writer_address(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    writer: &Pubkey,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            BUFFER_WRITER_SEED,
+            authorized_buffer.as_ref(),
+            writer.as_ref(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// Finds the staging buffer that `owner` uploads to before committing to `authorized_buffer`.
+pub fn find_staging_address(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[STAGING_SEED, authorized_buffer.as_ref(), owner.as_ref()],
+        program_id,
+    )
+}
+
+/// Derives the staging buffer address from a known bump seed.
+pub fn create_staging_address(
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            STAGING_SEED,
+            authorized_buffer.as_ref(),
+            owner.as_ref(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// Finds the `log_buffer` created by `authority` with `buffer_seed`.
+pub fn find_log_buffer_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            LOG_BUFFER_SEED,
+            authority.as_ref(),
+            &buffer_seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the `log_buffer` address from a known bump seed.
+pub fn create_log_buffer_address(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            LOG_BUFFER_SEED,
+            authority.as_ref(),
+            &buffer_seed.to_le_bytes(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// Finds the `mailbox` of `recipient`.
+pub fn find_mailbox_address(program_id: &Pubkey, recipient: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[MAILBOX_SEED, recipient.as_ref()], program_id)
+}
+
+/// Derives the `mailbox` address from a known bump seed.
+pub fn create_mailbox_address(
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[MAILBOX_SEED, recipient.as_ref(), &[bump_seed]],
+        program_id,
+    )
+}
+
+/// Finds the `token_gated_buffer` for holders of `mint` created by `creator` with `buffer_seed`.
+pub fn find_token_gated_address(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    creator: &Pubkey,
+    buffer_seed: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            TOKEN_GATED_SEED,
+            mint.as_ref(),
+            creator.as_ref(),
+            &buffer_seed.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the `token_gated_buffer` address from a known bump seed.
+pub fn create_token_gated_address(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    creator: &Pubkey,
+    buffer_seed: u64,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            TOKEN_GATED_SEED,
+            mint.as_ref(),
+            creator.as_ref(),
+            &buffer_seed.to_le_bytes(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// Finds the `vending_machine_buffer` that accepts `mint` with `machine_id`.
+pub fn find_vending_machine_address(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    machine_id: u64,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            VENDING_MACHINE_SEED,
+            mint.as_ref(),
+            &machine_id.to_le_bytes(),
+        ],
+        program_id,
+    )
+}
+
+/// Derives the `vending_machine_buffer` address from a known bump seed.
+pub fn create_vending_machine_address(
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    machine_id: u64,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            VENDING_MACHINE_SEED,
+            mint.as_ref(),
+            &machine_id.to_le_bytes(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
+
+/// Finds the treasury token account of `vending_machine_buffer`.
+pub fn find_treasury_address(program_id: &Pubkey, vending_machine_buffer: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[TREASURY_SEED, vending_machine_buffer.as_ref()],
+        program_id,
+    )
+}
+
+/// Finds the prepaid credits account of `user` at `vending_machine_buffer`.
+pub fn find_credits_address(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    user: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[CREDITS_SEED, vending_machine_buffer.as_ref(), user.as_ref()],
+        program_id,
+    )
+}
+
+/// Derives the prepaid credits address from a known bump seed.
+pub fn create_credits_address(
+    program_id: &Pubkey,
+    vending_machine_buffer: &Pubkey,
+    user: &Pubkey,
+    bump_seed: u8,
+) -> Result<Pubkey, PubkeyError> {
+    Pubkey::create_program_address(
+        &[
+            CREDITS_SEED,
+            vending_machine_buffer.as_ref(),
+            user.as_ref(),
+            &[bump_seed],
+        ],
+        program_id,
+    )
+}
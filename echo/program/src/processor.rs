@@ -1,66 +1,52 @@
-// use core::slice::SlicePattern;
-// use std::thread::AccessError;
-
-use borsh::{BorshDeserialize, BorshSerialize};
-// use num_traits::ToPrimitive;
-use solana_program::{
-    account_info::{next_account_info, AccountInfo},
-    entrypoint::ProgramResult,
-    msg,
-    program::{invoke, invoke_signed},
-    program_error::ProgramError,
-    pubkey::Pubkey,
-    system_instruction,
-    system_program::ID as SYSTEM_PROGRAM_ID,
-    sysvar::{rent::Rent, Sysvar},
-};
-
-use spl_token::{instruction::burn, ID as TOKEN_PROGRAM_ID};
+use borsh::BorshDeserialize;
+use solana_program::{account_info::AccountInfo, entrypoint::ProgramResult, msg, pubkey::Pubkey};
 
 use crate::error::EchoError;
 use crate::instruction::EchoInstruction;
-use crate::state::{AuthorizedBuffer, VendingMachineBuffer};
-
-pub fn assert_with_msg(statement: bool, err: ProgramError, msg: &str) -> ProgramResult {
-    if !statement {
-        msg!(msg);
-        Err(err)
-    } else {
-        Ok(())
-    }
-}
-
-pub fn assert_is_signer(account_info: &AccountInfo) -> ProgramResult {
-    assert_with_msg(
-        account_info.is_signer,
-        ProgramError::MissingRequiredSignature,
-        &format!("Missing signature for account {}.", account_info.key),
-    )
-}
-
-pub fn assert_is_writable(account_info: &AccountInfo) -> ProgramResult {
-    assert_with_msg(
-        account_info.is_writable,
-        ProgramError::InvalidArgument,
-        &format!("Account {} must be writable.", account_info.key),
-    )
-}
 
-pub fn assert_is_system_program(account_info: &AccountInfo) -> ProgramResult {
-    assert_with_msg(
-        *account_info.key == SYSTEM_PROGRAM_ID,
-        ProgramError::InvalidArgument,
-        &format!("Expected System Program, received: {}", account_info.key),
-    )
-}
-
-pub fn assert_is_initialized(account_info: &AccountInfo) -> ProgramResult {
-    assert_with_msg(
-        **account_info.lamports.borrow() > 0,
-        ProgramError::UninitializedAccount,
-        &format!("Account {} is uninitialized.", account_info.key),
-    )
-}
+pub mod accept_buffer_authority;
+pub mod add_buffer_writer;
+pub mod append_echo;
+pub mod authorized_echo;
+pub mod authorized_echo_at;
+pub mod authorized_echo_signed;
+pub mod begin_write;
+pub mod buy_vending_machine_credits;
+pub mod cancel_write;
+pub mod clear_mailbox;
+pub mod close_authorized_echo;
+pub mod close_log_echo;
+pub mod close_mailbox;
+pub mod close_token_gated_echo;
+pub mod close_vending_machine_credits;
+pub mod close_vending_machine_echo;
+pub mod commit_write;
+pub mod echo;
+pub mod initialize_authorized_echo;
+pub mod initialize_log_echo;
+pub mod initialize_mailbox;
+pub mod initialize_token_gated_echo;
+pub mod initialize_vending_machine_echo;
+pub mod migrate_authorized_echo;
+pub mod migrate_vending_machine_echo;
+pub mod pop_message;
+pub mod read_echo;
+pub mod reclaim_expired_buffer;
+pub mod remove_buffer_writer;
+pub mod resize_authorized_echo;
+pub mod seal_buffer;
+pub mod send_message;
+pub mod set_buffer_authority;
+pub mod set_merkle_root;
+pub mod set_vending_machine_credit_policy;
+pub mod set_vending_machine_price;
+pub mod token_gated_echo;
+pub mod vending_machine_echo;
+pub mod vending_machine_echo_at;
+pub mod verify_chunk;
+pub mod verify_echo;
+pub mod withdraw_vending_machine_proceeds;
+pub mod write_chunk;
 
 pub struct Processor {}
 
@@ -71,282 +57,243 @@ impl Processor {
         instruction_data: &[u8],
     ) -> ProgramResult {
         let instruction = EchoInstruction::try_from_slice(instruction_data)
-            .map_err(|_| ProgramError::InvalidInstructionData)?;
+            .map_err(|_| EchoError::InvalidInstructionData)?;
 
-        let accounts_iter = &mut accounts.iter();
         match instruction {
             EchoInstruction::Echo { data } => {
                 msg!("Instruction: Echo");
-                // Get account info for each account
-                let echo_buffer_info = next_account_info(accounts_iter)?;
-
-                // Validate accounts input
-                // TODO: handle this with a real error
-                assert_is_writable(echo_buffer_info)?;
-                assert_is_initialized(echo_buffer_info)?;
-
-                // Write the data to the buffer
-                let mut echo_buffer_data = echo_buffer_info.data.borrow_mut();
-                for (i, b) in echo_buffer_data.into_iter().enumerate() {
-                    if *b != 0 {
-                        // TODO: handle with a real error
-                        panic!("Echo buffer is not empty.");
-                    }
-                    if let Some(val) = data.get(i) {
-                        *b = *val;
-                    }
-                }
-                Ok(())
+                echo::process(program_id, accounts, &data)?;
             }
             EchoInstruction::InitializeAuthorizedEcho {
                 buffer_seed,
                 buffer_size,
+                expiry,
+                content,
             } => {
                 msg!("Instruction: InitializeAuthorizedEcho");
-
-                let buffer_seed_bytes = u64::to_le_bytes(buffer_seed);
-
-                // Get account info for each account
-                let authorized_buffer_info = next_account_info(accounts_iter)?;
-                let authority_info = next_account_info(accounts_iter)?;
-                let system_program_info = next_account_info(accounts_iter)?;
-
-                // Validate account inputs
-                assert_is_writable(authorized_buffer_info)?;
-                assert_is_signer(authority_info)?;
-                assert_is_system_program(system_program_info)?;
-
-                // Find PDA address for authorized_buffer
-                let mut seeds: Vec<&[u8]> = vec![
-                    b"authority",
-                    authority_info.key.as_ref(),
-                    &buffer_seed_bytes,
-                ];
-
-                let (authorized_buffer_key, bump_seed) =
-                    Pubkey::find_program_address(seeds.as_slice(), program_id);
-
-                // Confirm that the PDA address we found matches the one passed into the program
-                assert_with_msg(
-                    authorized_buffer_key == *authorized_buffer_info.key,
-                    ProgramError::InvalidArgument,
-                    "Invalid authorized_buffer address.",
-                )?;
-
-                // Create the authorized_buffer account
-                let bump_seed_bytes = [bump_seed];
-                seeds.push(&bump_seed_bytes);
-                assert_with_msg(
-                    buffer_size > 9,
-                    ProgramError::InvalidInstructionData,
-                    "Buffer size must be > 9.",
-                )?;
-                invoke_signed(
-                    &system_instruction::create_account(
-                        // Set authority as fee payer
-                        authority_info.key,
-                        &authorized_buffer_key,
-                        Rent::get()?.minimum_balance(buffer_size as usize),
-                        buffer_size,
-                        program_id,
-                    ),
-                    &[
-                        authority_info.clone(),
-                        authorized_buffer_info.clone(),
-                        system_program_info.clone(),
-                    ],
-                    &[seeds.as_slice()],
-                )?;
-
-                // Get authorized_buffer account data
-                let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
-
-                // Get size of echo_buffer: length_of_account_data - bytes_allocated_for_seeds - bytes_used_for_vec_size
-                let echo_buffer_size = authorized_buffer_data.len() - 9 - 4 as usize;
-
-                // Set first byte of authorized buffer to bump seed
-                let auth_buffer_struct = AuthorizedBuffer {
-                    bump_seed,
+                initialize_authorized_echo::process(
+                    program_id,
+                    accounts,
                     buffer_seed,
-                    data: vec![0; echo_buffer_size],
-                };
-                auth_buffer_struct.serialize(&mut *authorized_buffer_data)?;
-                // authorized_buffer_data[0] = bump_seed;
-                // authorized_buffer_data[1..9].copy_from_slice(&buffer_seed_bytes);
-
-                Ok(())
+                    buffer_size,
+                    expiry,
+                    content,
+                )?;
             }
             EchoInstruction::AuthorizedEcho { data } => {
                 msg!("Instruction: AuthorizedEcho");
-                // TODO: is it secure to not store the authority PK when we create the PDA?
-                // I think so, because using the authority PK as a seed effectively stores the PK in the PDA
-
-                // Get account info for each account
-                let authorized_buffer_info = next_account_info(accounts_iter)?;
-                let authority_info = next_account_info(accounts_iter)?;
-
-                // Validate account inputs
-                assert_is_writable(authorized_buffer_info)?;
-                assert_is_signer(authority_info)?;
-
-                // Derive PDA to confirm authority
-                // Get bump_seed and buffer_seed from authorized_buffer data
-                let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
-                let mut auth_buffer_struct =
-                    AuthorizedBuffer::try_from_slice(&authorized_buffer_data)?;
-                // let bump_seed = authorized_buffer_data[0];
-                let buffer_seed_bytes = auth_buffer_struct.buffer_seed.to_le_bytes();
-                let seeds = &[
-                    b"authority",
-                    authority_info.key.as_ref(),
-                    &buffer_seed_bytes,
-                    &[auth_buffer_struct.bump_seed],
-                ];
-                let authorized_buffer_key = Pubkey::create_program_address(seeds, program_id)?;
-
-                // Confirm that the PDA address we found matches the one passed into the program
-                assert_with_msg(
-                    authorized_buffer_key == *authorized_buffer_info.key,
-                    ProgramError::InvalidArgument,
-                    "Invalid authorized_buffer address.",
-                )?;
-
-                // all checks are done, write to the buffer
-                // let mut buffer_data: Vec<u8> = Vec::new();
-                for i in 0..auth_buffer_struct.data.len() {
-                    let data_i = i % data.len();
-                    println!(
-                        "authorized_buffer_len: {}\ndata_len: {}\ni: {}\ndata_i: {}",
-                        auth_buffer_struct.data.len(),
-                        data.len(),
-                        i,
-                        data_i
-                    );
-                    auth_buffer_struct.data[i] = data[data_i];
-                }
-                auth_buffer_struct.serialize(&mut *authorized_buffer_data)?;
-
-                Ok(())
+                authorized_echo::process(program_id, accounts, &data)?;
             }
-            EchoInstruction::InitializeVendingMachineEcho { price, buffer_size } => {
+            EchoInstruction::InitializeVendingMachineEcho {
+                machine_id,
+                pricing,
+                buffer_size,
+                payment_mode,
+                content,
+            } => {
                 msg!("Instruction: InitializeVendingMachineEcho");
-                let vm_buffer_info = next_account_info(accounts_iter)?;
-                let vm_mint_info = next_account_info(accounts_iter)?;
-                let payer_info = next_account_info(accounts_iter)?;
-                let system_program_info = next_account_info(accounts_iter)?;
-
-                assert_is_writable(vm_buffer_info)?;
-                assert_is_signer(payer_info)?;
-                assert_is_system_program(system_program_info)?;
-
-                let price_bytes = price.to_le_bytes();
-                let mut seeds: Vec<&[u8]> =
-                    vec![b"vending_machine", vm_mint_info.key.as_ref(), &price_bytes];
-
-                let (vm_buffer_key, bump_seed) =
-                    Pubkey::find_program_address(seeds.as_slice(), program_id);
-
-                assert_with_msg(
-                    vm_buffer_key == *vm_buffer_info.key,
-                    ProgramError::InvalidArgument,
-                    "Invalid Vending Machine Buffer account.",
-                )?;
-                let bump_seed_bytes = [bump_seed];
-                seeds.push(&bump_seed_bytes);
-
-                invoke_signed(
-                    &system_instruction::create_account(
-                        // Set authority as fee payer
-                        payer_info.key,
-                        &vm_buffer_key,
-                        Rent::get()?.minimum_balance(buffer_size as usize),
-                        buffer_size as u64,
-                        program_id,
-                    ),
-                    &[
-                        payer_info.clone(),
-                        vm_buffer_info.clone(),
-                        system_program_info.clone(),
-                    ],
-                    &[seeds.as_slice()],
+                initialize_vending_machine_echo::process(
+                    program_id,
+                    accounts,
+                    machine_id,
+                    pricing,
+                    buffer_size,
+                    payment_mode,
+                    content,
                 )?;
-
-                // Get authorized_buffer account data
-                let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
-
-                // Get size of echo_buffer: length_of_account_data - bytes_allocated_for_seeds - bytes_used_for_vec_size
-                let buffer_data_size = vm_buffer_data.len() - 9 - 4 as usize;
-
-                // Set first byte of authorized buffer to bump seed
-                let vm_buffer_struct = VendingMachineBuffer {
-                    bump_seed,
-                    price,
-                    data: vec![0; buffer_data_size],
-                };
-                vm_buffer_struct.serialize(&mut *vm_buffer_data)?;
-                // authorized_buffer_data[0] = bump_seed;
-                // authorized_buffer_data[1..9].copy_from_slice(&buffer_seed_bytes);
-
-                Ok(())
             }
-            EchoInstruction::VendingMachineEcho { data } => {
+            EchoInstruction::VendingMachineEcho { data, max_charge } => {
                 msg!("Instruction: VendingMachineEcho");
-                let vm_buffer_info = next_account_info(accounts_iter)?;
-                let user_info = next_account_info(accounts_iter)?;
-                let user_token_account_info = next_account_info(accounts_iter)?;
-                let vm_mint_info = next_account_info(accounts_iter)?;
-                let token_program_info = next_account_info(accounts_iter)?;
-
-                assert_is_writable(vm_buffer_info)?;
-                assert_is_writable(user_token_account_info)?;
-                assert_is_writable(vm_mint_info)?;
-                assert_is_signer(user_info)?;
-
-                let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
-                let mut vm_buffer_struct = VendingMachineBuffer::try_from_slice(&vm_buffer_data)?;
-                let price_bytes = vm_buffer_struct.price.to_le_bytes();
-                let seeds = &[
-                    b"vending_machine",
-                    vm_mint_info.key.as_ref(),
-                    &price_bytes,
-                    &[vm_buffer_struct.bump_seed],
-                ];
-                let vm_buffer_key = Pubkey::create_program_address(seeds, program_id)?;
-
-                // Confirm that the PDA address we found matches the one passed into the program
-                assert_with_msg(
-                    vm_buffer_key == *vm_buffer_info.key,
-                    ProgramError::InvalidArgument,
-                    "Invalid vm_buffer address.",
+                vending_machine_echo::process(program_id, accounts, &data, max_charge)?;
+            }
+            EchoInstruction::CloseAuthorizedEcho => {
+                msg!("Instruction: CloseAuthorizedEcho");
+                close_authorized_echo::process(program_id, accounts)?;
+            }
+            EchoInstruction::CloseVendingMachineEcho => {
+                msg!("Instruction: CloseVendingMachineEcho");
+                close_vending_machine_echo::process(program_id, accounts)?;
+            }
+            EchoInstruction::ResizeAuthorizedEcho { new_size } => {
+                msg!("Instruction: ResizeAuthorizedEcho");
+                resize_authorized_echo::process(program_id, accounts, new_size)?;
+            }
+            EchoInstruction::AuthorizedEchoAt { offset, data } => {
+                msg!("Instruction: AuthorizedEchoAt");
+                authorized_echo_at::process(program_id, accounts, offset, &data)?;
+            }
+            EchoInstruction::VendingMachineEchoAt {
+                offset,
+                data,
+                max_charge,
+            } => {
+                msg!("Instruction: VendingMachineEchoAt");
+                vending_machine_echo_at::process(program_id, accounts, offset, &data, max_charge)?;
+            }
+            EchoInstruction::SetBufferAuthority { new_authority } => {
+                msg!("Instruction: SetBufferAuthority");
+                set_buffer_authority::process(program_id, accounts, &new_authority)?;
+            }
+            EchoInstruction::AcceptBufferAuthority => {
+                msg!("Instruction: AcceptBufferAuthority");
+                accept_buffer_authority::process(program_id, accounts)?;
+            }
+            EchoInstruction::MigrateAuthorizedEcho => {
+                msg!("Instruction: MigrateAuthorizedEcho");
+                migrate_authorized_echo::process(program_id, accounts)?;
+            }
+            EchoInstruction::MigrateVendingMachineEcho => {
+                msg!("Instruction: MigrateVendingMachineEcho");
+                migrate_vending_machine_echo::process(program_id, accounts)?;
+            }
+            EchoInstruction::WithdrawVendingMachineProceeds { amount } => {
+                msg!("Instruction: WithdrawVendingMachineProceeds");
+                withdraw_vending_machine_proceeds::process(program_id, accounts, amount)?;
+            }
+            EchoInstruction::SetVendingMachinePrice { pricing } => {
+                msg!("Instruction: SetVendingMachinePrice");
+                set_vending_machine_price::process(program_id, accounts, pricing)?;
+            }
+            EchoInstruction::BuyVendingMachineCredits { amount } => {
+                msg!("Instruction: BuyVendingMachineCredits");
+                buy_vending_machine_credits::process(program_id, accounts, amount)?;
+            }
+            EchoInstruction::CloseVendingMachineCredits => {
+                msg!("Instruction: CloseVendingMachineCredits");
+                close_vending_machine_credits::process(program_id, accounts)?;
+            }
+            EchoInstruction::SetVendingMachineCreditPolicy {
+                credit_lifetime,
+                refund_credits,
+            } => {
+                msg!("Instruction: SetVendingMachineCreditPolicy");
+                set_vending_machine_credit_policy::process(
+                    program_id,
+                    accounts,
+                    credit_lifetime,
+                    refund_credits,
                 )?;
-
-                // All checks done, burn token
-                invoke(
-                    &burn(
-                        &TOKEN_PROGRAM_ID,
-                        &user_token_account_info.key,
-                        &vm_mint_info.key,
-                        &user_info.key,
-                        &[user_info.key],
-                        vm_buffer_struct.price,
-                    )?,
-                    &[
-                        user_token_account_info.clone(),
-                        vm_mint_info.clone(),
-                        user_info.clone(),
-                        token_program_info.clone(),
-                    ],
+            }
+            EchoInstruction::AddBufferWriter { new_writer } => {
+                msg!("Instruction: AddBufferWriter");
+                add_buffer_writer::process(program_id, accounts, &new_writer)?;
+            }
+            EchoInstruction::RemoveBufferWriter => {
+                msg!("Instruction: RemoveBufferWriter");
+                remove_buffer_writer::process(program_id, accounts)?;
+            }
+            EchoInstruction::SealBuffer => {
+                msg!("Instruction: SealBuffer");
+                seal_buffer::process(program_id, accounts)?;
+            }
+            EchoInstruction::ReclaimExpiredBuffer => {
+                msg!("Instruction: ReclaimExpiredBuffer");
+                reclaim_expired_buffer::process(program_id, accounts)?;
+            }
+            EchoInstruction::ReadEcho { offset, len } => {
+                msg!("Instruction: ReadEcho");
+                read_echo::process(program_id, accounts, offset, len)?;
+            }
+            EchoInstruction::BeginWrite {
+                len,
+                chunk_size,
+                hash,
+            } => {
+                msg!("Instruction: BeginWrite");
+                begin_write::process(program_id, accounts, len, chunk_size, hash)?;
+            }
+            EchoInstruction::WriteChunk { index, bytes } => {
+                msg!("Instruction: WriteChunk");
+                write_chunk::process(program_id, accounts, index, &bytes)?;
+            }
+            EchoInstruction::CommitWrite => {
+                msg!("Instruction: CommitWrite");
+                commit_write::process(program_id, accounts)?;
+            }
+            EchoInstruction::CancelWrite => {
+                msg!("Instruction: CancelWrite");
+                cancel_write::process(program_id, accounts)?;
+            }
+            EchoInstruction::InitializeLogEcho {
+                buffer_seed,
+                buffer_size,
+            } => {
+                msg!("Instruction: InitializeLogEcho");
+                initialize_log_echo::process(program_id, accounts, buffer_seed, buffer_size)?;
+            }
+            EchoInstruction::AppendEcho { data } => {
+                msg!("Instruction: AppendEcho");
+                append_echo::process(program_id, accounts, &data)?;
+            }
+            EchoInstruction::InitializeMailbox {
+                buffer_size,
+                min_postage,
+            } => {
+                msg!("Instruction: InitializeMailbox");
+                initialize_mailbox::process(program_id, accounts, buffer_size, min_postage)?;
+            }
+            EchoInstruction::SendMessage { data, postage } => {
+                msg!("Instruction: SendMessage");
+                send_message::process(program_id, accounts, data, postage)?;
+            }
+            EchoInstruction::PopMessage => {
+                msg!("Instruction: PopMessage");
+                pop_message::process(program_id, accounts)?;
+            }
+            EchoInstruction::ClearMailbox => {
+                msg!("Instruction: ClearMailbox");
+                clear_mailbox::process(program_id, accounts)?;
+            }
+            EchoInstruction::AuthorizedEchoSigned { data, nonce } => {
+                msg!("Instruction: AuthorizedEchoSigned");
+                authorized_echo_signed::process(program_id, accounts, &data, nonce)?;
+            }
+            EchoInstruction::VerifyEcho { expected_hash } => {
+                msg!("Instruction: VerifyEcho");
+                verify_echo::process(program_id, accounts, expected_hash)?;
+            }
+            EchoInstruction::SetMerkleRoot { root, leaf_count } => {
+                msg!("Instruction: SetMerkleRoot");
+                set_merkle_root::process(program_id, accounts, root, leaf_count)?;
+            }
+            EchoInstruction::VerifyChunk { index, leaf, proof } => {
+                msg!("Instruction: VerifyChunk");
+                verify_chunk::process(program_id, accounts, index, &leaf, &proof)?;
+            }
+            EchoInstruction::InitializeTokenGatedEcho {
+                buffer_seed,
+                buffer_size,
+                min_balance,
+                content,
+            } => {
+                msg!("Instruction: InitializeTokenGatedEcho");
+                initialize_token_gated_echo::process(
+                    program_id,
+                    accounts,
+                    buffer_seed,
+                    buffer_size,
+                    min_balance,
+                    content,
                 )?;
-
-                // Copy data to buffer
-                for i in 0..vm_buffer_struct.data.len() {
-                    let data_i = i % data.len();
-                    vm_buffer_struct.data[i] = data[data_i];
-                }
-                vm_buffer_struct.serialize(&mut *vm_buffer_data)?;
-
-                Ok(())
+            }
+            EchoInstruction::TokenGatedEcho { data } => {
+                msg!("Instruction: TokenGatedEcho");
+                token_gated_echo::process(program_id, accounts, &data)?;
+            }
+            EchoInstruction::CloseLogEcho => {
+                msg!("Instruction: CloseLogEcho");
+                close_log_echo::process(program_id, accounts)?;
+            }
+            EchoInstruction::CloseMailbox => {
+                msg!("Instruction: CloseMailbox");
+                close_mailbox::process(program_id, accounts)?;
+            }
+            EchoInstruction::CloseTokenGatedEcho => {
+                msg!("Instruction: CloseTokenGatedEcho");
+                close_token_gated_echo::process(program_id, accounts)?;
             }
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,39 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let pending_authority_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_signer(pending_authority_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    let (auth_buffer_struct, _) = load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+
+    // Confirm that the signer is the proposed authority of this buffer
+    assert_authorized_buffer_address(program_id, authorized_buffer_info, auth_buffer_struct)?;
+    assert_with_msg(
+        auth_buffer_struct.pending_authority != Pubkey::default()
+            && auth_buffer_struct.pending_authority == *pending_authority_info.key,
+        EchoError::InvalidPendingAuthority.into(),
+        &format!(
+            "Account {} is not the pending authority of the authorized_buffer.",
+            pending_authority_info.key
+        ),
+    )?;
+
+    auth_buffer_struct.authority = auth_buffer_struct.pending_authority;
+    auth_buffer_struct.pending_authority = Pubkey::default();
+    Ok(())
+}
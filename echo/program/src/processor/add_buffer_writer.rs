@@ -0,0 +1,81 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::{find_buffer_writer_address, BUFFER_WRITER_SEED};
+use crate::state::{AccountHeader, AccountType, AuthorizedBuffer, BufferWriter};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], writer: &Pubkey) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let buffer_writer_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authority_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_writable(buffer_writer_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Confirm authority
+    let auth_buffer_struct =
+        *load_buffer::<AuthorizedBuffer>(&authorized_buffer_info.try_borrow_data()?)?.0;
+    assert_buffer_authority(
+        program_id,
+        authorized_buffer_info,
+        &auth_buffer_struct,
+        authority_info,
+    )?;
+
+    let (buffer_writer_key, bump_seed) =
+        find_buffer_writer_address(program_id, authorized_buffer_info.key, writer);
+    assert_with_msg(
+        buffer_writer_key == *buffer_writer_info.key,
+        EchoError::InvalidWriter.into(),
+        "Invalid buffer_writer address.",
+    )?;
+
+    // The authority pays for the allow-list entry and gets the rent back when it is removed
+    invoke_signed(
+        &system_instruction::create_account(
+            authority_info.key,
+            &buffer_writer_key,
+            Rent::get()?.minimum_balance(BufferWriter::LEN),
+            BufferWriter::LEN as u64,
+            program_id,
+        ),
+        &[
+            authority_info.clone(),
+            buffer_writer_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            BUFFER_WRITER_SEED,
+            authorized_buffer_info.key.as_ref(),
+            writer.as_ref(),
+            &[bump_seed],
+        ]],
+    )?;
+
+    let buffer_writer_struct = BufferWriter {
+        header: AccountHeader::new(AccountType::BufferWriter),
+        bump_seed,
+        authorized_buffer: *authorized_buffer_info.key,
+        writer: *writer,
+        added_by: *authority_info.key,
+        reserved: [0; 32],
+    };
+    initialize_buffer(
+        &mut buffer_writer_info.try_borrow_mut_data()?,
+        &buffer_writer_struct,
+    )
+}
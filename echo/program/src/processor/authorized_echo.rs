@@ -0,0 +1,43 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+
+    // Get account info for each account
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Confirm authority, or a writer on the allow-list of the buffer
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    let (auth_buffer_struct, echo_data) =
+        load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+    assert_buffer_writer(
+        program_id,
+        authorized_buffer_info,
+        auth_buffer_struct,
+        authority_info,
+        accounts_iter.next(),
+    )?;
+    assert_not_sealed(auth_buffer_struct.sealed)?;
+    assert_not_expired(auth_buffer_struct)?;
+
+    // all checks are done, write to the buffer
+    write_repeated(echo_data, data)?;
+    assert_valid_content(auth_buffer_struct.content_type, echo_data)?;
+    auth_buffer_struct.update_content_hash(echo_data);
+    // The echo data is no longer a Merkle root
+    auth_buffer_struct.merkle_leaf_count = 0.into();
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{signed_echo_message, AuthorizedBuffer};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+    nonce: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let instructions_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs, the authority signs the write off-chain instead of the transaction
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    let (auth_buffer_struct, echo_data) =
+        load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+    assert_authorized_buffer_address(program_id, authorized_buffer_info, auth_buffer_struct)?;
+    assert_not_sealed(auth_buffer_struct.sealed)?;
+    assert_not_expired(auth_buffer_struct)?;
+
+    let (signer, message) = load_ed25519_signed_message(instructions_info)?;
+    assert_with_msg(
+        signer == auth_buffer_struct.authority,
+        EchoError::InvalidAuthority.into(),
+        &format!(
+            "Account {} is not the authority of the authorized_buffer.",
+            signer
+        ),
+    )?;
+    assert_with_msg(
+        message == signed_echo_message(authorized_buffer_info.key, nonce, data),
+        EchoError::InvalidSignedMessage.into(),
+        "The signed message does not match the buffer, nonce and data.",
+    )?;
+
+    // Each nonce is accepted once, so the signed write cannot be replayed
+    let expected_nonce = u64::from(auth_buffer_struct.nonce);
+    assert_with_msg(
+        nonce == expected_nonce,
+        EchoError::InvalidNonce.into(),
+        &format!("Expected nonce {}.", expected_nonce),
+    )?;
+    auth_buffer_struct.nonce = nonce.checked_add(1).ok_or(EchoError::InvalidNonce)?.into();
+
+    write_repeated(echo_data, data)?;
+    assert_valid_content(auth_buffer_struct.content_type, echo_data)?;
+    auth_buffer_struct.update_content_hash(echo_data);
+    // The echo data is no longer a Merkle root
+    auth_buffer_struct.merkle_leaf_count = 0.into();
+    Ok(())
+}
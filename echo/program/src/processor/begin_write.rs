@@ -0,0 +1,116 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    hash::Hash,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::{find_staging_address, STAGING_SEED};
+use crate::state::{AccountHeader, AccountType, AuthorizedBuffer, StagingBuffer};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    len: u64,
+    chunk_size: u64,
+    hash: Hash,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+    let staging_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(owner_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_writable(staging_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Confirm authority, or a writer on the allow-list of the buffer. `CommitWrite` checks again, so an upload cannot
+    // be committed once the owner lost write access.
+    let authorized_buffer_data = authorized_buffer_info.try_borrow_data()?;
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&authorized_buffer_data)?;
+    assert_buffer_writer(
+        program_id,
+        authorized_buffer_info,
+        auth_buffer_struct,
+        owner_info,
+        accounts_iter.next(),
+    )?;
+    assert_not_sealed(auth_buffer_struct.sealed)?;
+    assert_not_expired(auth_buffer_struct)?;
+
+    // The upload replaces the whole echo data, zero padded, so it has to fit
+    let len = usize::try_from(len).map_err(|_| EchoError::BufferTooSmall)?;
+    assert_with_msg(
+        len <= echo_data.len(),
+        EchoError::BufferTooSmall.into(),
+        &format!(
+            "Cannot stage {} bytes for a {} byte buffer.",
+            len,
+            echo_data.len()
+        ),
+    )?;
+    assert_with_msg(
+        chunk_size > 0,
+        EchoError::InvalidInstructionData.into(),
+        "Chunk size must not be zero.",
+    )?;
+    // `WriteChunk` grows the staging buffer, so a chunk must fit in one instruction's growth
+    assert_with_msg(
+        chunk_size <= MAX_PERMITTED_DATA_INCREASE as u64,
+        EchoError::ChunkTooLarge.into(),
+        "Chunks can be at most 10KiB.",
+    )?;
+
+    let (staging_key, bump_seed) =
+        find_staging_address(program_id, authorized_buffer_info.key, owner_info.key);
+    assert_with_msg(
+        staging_key == *staging_info.key,
+        EchoError::InvalidStagingBuffer.into(),
+        "Invalid staging buffer address.",
+    )?;
+
+    // Only the header is allocated here, as a CPI cannot create accounts over 10KiB. The owner pays for the staging
+    // buffer and gets the rent back on commit or cancel.
+    let staging_size = StagingBuffer::HEADER_SIZE;
+    invoke_signed(
+        &system_instruction::create_account(
+            owner_info.key,
+            &staging_key,
+            Rent::get()?.minimum_balance(staging_size),
+            staging_size as u64,
+            program_id,
+        ),
+        &[
+            owner_info.clone(),
+            staging_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            STAGING_SEED,
+            authorized_buffer_info.key.as_ref(),
+            owner_info.key.as_ref(),
+            &[bump_seed],
+        ]],
+    )?;
+
+    let staging_struct = StagingBuffer {
+        header: AccountHeader::new(AccountType::StagingBuffer),
+        bump_seed,
+        authorized_buffer: *authorized_buffer_info.key,
+        owner: *owner_info.key,
+        len: (len as u64).into(),
+        chunk_size: chunk_size.into(),
+        hash: hash.to_bytes(),
+        reserved: [0; 32],
+    };
+    initialize_buffer(&mut staging_info.try_borrow_mut_data()?, &staging_struct)
+}
@@ -0,0 +1,126 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{clock::Clock, rent::Rent, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::{find_credits_address, CREDITS_SEED};
+use crate::state::{AccountHeader, AccountType, VendingMachineBuffer, VendingMachineCredits};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let user_info = next_account_info(accounts_iter)?;
+    let user_token_account_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let token_program_info = next_account_info(accounts_iter)?;
+    let credits_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_writable(user_info)?;
+    assert_is_signer(user_info)?;
+    assert_is_writable(user_token_account_info)?;
+    assert_is_writable(vm_mint_info)?;
+    assert_is_writable(credits_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+
+    let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
+    let (vm_buffer_struct, _) = load_buffer_mut::<VendingMachineBuffer>(&mut vm_buffer_data)?;
+    assert_vending_machine_address(program_id, vm_buffer_info, vm_buffer_struct, vm_mint_info)?;
+
+    // The first purchase creates the credits account, paid for by the user
+    if credits_info.data_is_empty() {
+        let (credits_key, bump_seed) =
+            find_credits_address(program_id, vm_buffer_info.key, user_info.key);
+        assert_with_msg(
+            credits_key == *credits_info.key,
+            EchoError::InvalidCredits.into(),
+            "Invalid credits account.",
+        )?;
+        invoke_signed(
+            &system_instruction::create_account(
+                user_info.key,
+                &credits_key,
+                Rent::get()?.minimum_balance(VendingMachineCredits::LEN),
+                VendingMachineCredits::LEN as u64,
+                program_id,
+            ),
+            &[
+                user_info.clone(),
+                credits_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&[
+                CREDITS_SEED,
+                vm_buffer_info.key.as_ref(),
+                user_info.key.as_ref(),
+                &[bump_seed],
+            ]],
+        )?;
+        let credits_struct = VendingMachineCredits {
+            header: AccountHeader::new(AccountType::VendingMachineCredits),
+            bump_seed,
+            vending_machine: *vm_buffer_info.key,
+            user: *user_info.key,
+            credits: 0.into(),
+            expires_at: 0.into(),
+            reserved: [0; 32],
+        };
+        initialize_buffer(&mut credits_info.try_borrow_mut_data()?, &credits_struct)?;
+    }
+
+    let mut credits_data = credits_info.try_borrow_mut_data()?;
+    let (credits_struct, _) = load_buffer_mut::<VendingMachineCredits>(&mut credits_data)?;
+    assert_credits(
+        program_id,
+        credits_info,
+        credits_struct,
+        vm_buffer_info.key,
+        user_info.key,
+    )?;
+
+    // Expired credits are forfeited, a purchase starts over and restarts the lifetime of the machine
+    let now = Clock::get()?.unix_timestamp;
+    let mut credits = u64::from(credits_struct.credits);
+    let mut outstanding_credits = u64::from(vm_buffer_struct.outstanding_credits);
+    if credits_struct.is_expired(now) {
+        outstanding_credits = outstanding_credits
+            .checked_sub(credits)
+            .ok_or(EchoError::ArithmeticOverflow)?;
+        credits = 0;
+    }
+    credits = credits
+        .checked_add(amount)
+        .ok_or(EchoError::ArithmeticOverflow)?;
+    outstanding_credits = outstanding_credits
+        .checked_add(amount)
+        .ok_or(EchoError::ArithmeticOverflow)?;
+    let expires_at = match u64::from(vm_buffer_struct.credit_lifetime) {
+        0 => 0,
+        credit_lifetime => i64::try_from(credit_lifetime)
+            .ok()
+            .and_then(|credit_lifetime| now.checked_add(credit_lifetime))
+            .ok_or(EchoError::ArithmeticOverflow)?,
+    };
+    credits_struct.credits = credits.into();
+    credits_struct.expires_at = expires_at.into();
+    vm_buffer_struct.outstanding_credits = outstanding_credits.into();
+
+    // Pay for all credits at once, the same way a write would
+    pay_vending_machine(
+        vm_buffer_struct,
+        amount,
+        user_token_account_info,
+        vm_mint_info,
+        user_info,
+        token_program_info,
+        accounts_iter.next(),
+    )
+}
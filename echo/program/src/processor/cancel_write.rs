@@ -0,0 +1,26 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::StagingBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let staging_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(staging_info)?;
+    assert_is_writable(owner_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_owned_by(staging_info, program_id)?;
+
+    let staging_struct = *load_buffer::<StagingBuffer>(&staging_info.try_borrow_data()?)?.0;
+    assert_staging_buffer(program_id, staging_info, &staging_struct, owner_info)?;
+
+    // The authorized_buffer was never touched, so the upload can simply be dropped
+    close_account(staging_info, owner_info)
+}
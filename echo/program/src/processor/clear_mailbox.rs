@@ -0,0 +1,28 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::Mailbox;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let mailbox_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(mailbox_info)?;
+    assert_is_signer(recipient_info)?;
+    assert_is_owned_by(mailbox_info, program_id)?;
+
+    let mut mailbox_data = mailbox_info.try_borrow_mut_data()?;
+    let (mailbox_struct, queue) = load_buffer_mut::<Mailbox>(&mut mailbox_data)?;
+    assert_mailbox_recipient(program_id, mailbox_info, mailbox_struct, recipient_info)?;
+
+    queue.fill(0);
+    mailbox_struct.len = 0.into();
+    mailbox_struct.messages = 0.into();
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_writable(recipient_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Confirm authority
+    {
+        let authorized_buffer_data = authorized_buffer_info.try_borrow_data()?;
+        let (auth_buffer_struct, _) = load_buffer::<AuthorizedBuffer>(&authorized_buffer_data)?;
+        assert_buffer_authority(
+            program_id,
+            authorized_buffer_info,
+            auth_buffer_struct,
+            authority_info,
+        )?;
+        assert_not_sealed(auth_buffer_struct.sealed)?;
+    }
+
+    close_account(authorized_buffer_info, recipient_info)
+}
@@ -0,0 +1,35 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::LogBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let log_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(log_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_writable(recipient_info)?;
+    assert_is_owned_by(log_buffer_info, program_id)?;
+
+    // Confirm authority
+    {
+        let log_buffer_data = log_buffer_info.try_borrow_data()?;
+        let (log_buffer_struct, _) = load_buffer::<LogBuffer>(&log_buffer_data)?;
+        assert_log_buffer_authority(
+            program_id,
+            log_buffer_info,
+            log_buffer_struct,
+            authority_info,
+        )?;
+    }
+
+    close_account(log_buffer_info, recipient_info)
+}
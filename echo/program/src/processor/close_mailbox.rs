@@ -0,0 +1,30 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::Mailbox;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let mailbox_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+    let destination_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(mailbox_info)?;
+    assert_is_signer(recipient_info)?;
+    assert_is_writable(destination_info)?;
+    assert_is_owned_by(mailbox_info, program_id)?;
+
+    // Confirm recipient, messages still in the queue are dropped with the mailbox
+    {
+        let mailbox_data = mailbox_info.try_borrow_data()?;
+        let (mailbox_struct, _) = load_buffer::<Mailbox>(&mailbox_data)?;
+        assert_mailbox_recipient(program_id, mailbox_info, mailbox_struct, recipient_info)?;
+    }
+
+    close_account(mailbox_info, destination_info)
+}
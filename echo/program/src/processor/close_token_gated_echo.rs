@@ -0,0 +1,36 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::TokenGatedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let token_gated_buffer_info = next_account_info(accounts_iter)?;
+    let creator_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(token_gated_buffer_info)?;
+    assert_is_signer(creator_info)?;
+    assert_is_writable(recipient_info)?;
+    assert_is_owned_by(token_gated_buffer_info, program_id)?;
+
+    // Confirm creator, holders of the mint cannot close the buffer
+    {
+        let token_gated_buffer_data = token_gated_buffer_info.try_borrow_data()?;
+        let (token_gated_buffer_struct, _) =
+            load_buffer::<TokenGatedBuffer>(&token_gated_buffer_data)?;
+        assert_token_gated_creator(
+            program_id,
+            token_gated_buffer_info,
+            token_gated_buffer_struct,
+            creator_info,
+        )?;
+    }
+
+    close_account(token_gated_buffer_info, recipient_info)
+}
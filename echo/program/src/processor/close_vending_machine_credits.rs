@@ -0,0 +1,79 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+    sysvar::{clock::Clock, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::VENDING_MACHINE_SEED;
+use crate::state::{VendingMachineBuffer, VendingMachineCredits};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let user_info = next_account_info(accounts_iter)?;
+    let credits_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(user_info)?;
+    assert_is_writable(credits_info)?;
+    assert_is_owned_by(credits_info, program_id)?;
+
+    let credits_struct = *load_buffer::<VendingMachineCredits>(&credits_info.try_borrow_data()?)?.0;
+    assert_credits(
+        program_id,
+        credits_info,
+        &credits_struct,
+        vm_buffer_info.key,
+        user_info.key,
+    )?;
+
+    // Anyone may clean up expired credits, the rent still goes back to the user
+    let expired = credits_struct.is_expired(Clock::get()?.unix_timestamp);
+    if !expired {
+        assert_is_signer(user_info)?;
+    }
+
+    // Credits of a closed vending machine are worthless, there is nothing left to refund them from
+    if vm_buffer_info.owner != program_id {
+        return close_account(credits_info, user_info);
+    }
+
+    assert_is_writable(vm_buffer_info)?;
+    let credits = u64::from(credits_struct.credits);
+    let vm_buffer_struct = {
+        let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
+        let (vm_buffer_struct, _) = load_buffer_mut::<VendingMachineBuffer>(&mut vm_buffer_data)?;
+        assert_vending_machine_address(program_id, vm_buffer_info, vm_buffer_struct, vm_mint_info)?;
+        vm_buffer_struct.outstanding_credits = u64::from(vm_buffer_struct.outstanding_credits)
+            .checked_sub(credits)
+            .ok_or(EchoError::ArithmeticOverflow)?
+            .into();
+        // Copy the header out, the buffer signs the refund below
+        *vm_buffer_struct
+    };
+
+    if vm_buffer_struct.refund_credits != 0 && !expired && credits > 0 {
+        let user_token_account_info = next_account_info(accounts_iter)?;
+        let treasury_info = next_account_info(accounts_iter)?;
+        let token_program_info = next_account_info(accounts_iter)?;
+        assert_treasury(&vm_buffer_struct, treasury_info)?;
+        transfer_tokens(
+            treasury_info,
+            user_token_account_info,
+            vm_buffer_info,
+            token_program_info,
+            credits,
+            &[&[
+                VENDING_MACHINE_SEED,
+                vm_mint_info.key.as_ref(),
+                &u64::from(vm_buffer_struct.machine_id).to_le_bytes(),
+                &[vm_buffer_struct.bump_seed],
+            ]],
+        )?;
+    }
+
+    close_account(credits_info, user_info)
+}
@@ -0,0 +1,48 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::pda::VENDING_MACHINE_SEED;
+use crate::state::{PaymentMode, VendingMachineBuffer};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_writable(owner_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+
+    let vm_buffer_struct =
+        *load_buffer::<VendingMachineBuffer>(&vm_buffer_info.try_borrow_data()?)?.0;
+    assert_vending_machine_address(program_id, vm_buffer_info, &vm_buffer_struct, vm_mint_info)?;
+    assert_not_sealed(vm_buffer_struct.sealed)?;
+    assert_vending_machine_owner(&vm_buffer_struct, owner_info)?;
+
+    // The treasury must be emptied by the owner first, the token program refuses to close it otherwise
+    if vm_buffer_struct.payment_mode() == Some(PaymentMode::Treasury) {
+        let treasury_info = next_account_info(accounts_iter)?;
+        let token_program_info = next_account_info(accounts_iter)?;
+        assert_treasury(&vm_buffer_struct, treasury_info)?;
+        close_token_account(
+            treasury_info,
+            owner_info,
+            vm_buffer_info,
+            token_program_info,
+            &[&[
+                VENDING_MACHINE_SEED,
+                vm_mint_info.key.as_ref(),
+                &u64::from(vm_buffer_struct.machine_id).to_le_bytes(),
+                &[vm_buffer_struct.bump_seed],
+            ]],
+        )?;
+    }
+
+    close_account(vm_buffer_info, owner_info)
+}
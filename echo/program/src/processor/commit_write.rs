@@ -0,0 +1,88 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash::hash,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{AuthorizedBuffer, StagingBuffer};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+    let staging_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_writable(owner_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_writable(staging_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+    assert_is_owned_by(staging_info, program_id)?;
+
+    {
+        let staging_data = staging_info.try_borrow_data()?;
+        let (staging_struct, staged_data) = load_buffer::<StagingBuffer>(&staging_data)?;
+        assert_staging_buffer(program_id, staging_info, staging_struct, owner_info)?;
+        assert_with_msg(
+            staging_struct.authorized_buffer == *authorized_buffer_info.key,
+            EchoError::InvalidStagingBuffer.into(),
+            "Staging buffer belongs to another authorized_buffer.",
+        )?;
+
+        // The owner must still be allowed to write when the upload lands
+        let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+        let (auth_buffer_struct, echo_data) =
+            load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+        assert_buffer_writer(
+            program_id,
+            authorized_buffer_info,
+            auth_buffer_struct,
+            owner_info,
+            accounts_iter.next(),
+        )?;
+        assert_not_sealed(auth_buffer_struct.sealed)?;
+        assert_not_expired(auth_buffer_struct)?;
+
+        // Every byte of the upload must have been written, the staging buffer only grows with the chunks
+        assert_with_msg(
+            staged_data.len() as u64 == u64::from(staging_struct.len),
+            EchoError::StagedHashMismatch.into(),
+            &format!(
+                "Only {} of the {} bytes of the upload were staged.",
+                staged_data.len(),
+                u64::from(staging_struct.len)
+            ),
+        )?;
+
+        // The buffer may have shrunk since `BeginWrite`
+        assert_with_msg(
+            staged_data.len() <= echo_data.len(),
+            EchoError::BufferTooSmall.into(),
+            &format!(
+                "Cannot commit {} bytes to a {} byte buffer.",
+                staged_data.len(),
+                echo_data.len()
+            ),
+        )?;
+        assert_with_msg(
+            hash(staged_data).to_bytes() == staging_struct.hash,
+            EchoError::StagedHashMismatch.into(),
+            "Staged data does not match the hash given to BeginWrite.",
+        )?;
+
+        // Replace the whole echo data, bytes past the upload are zeroed
+        let (uploaded, rest) = echo_data.split_at_mut(staged_data.len());
+        uploaded.copy_from_slice(staged_data);
+        rest.fill(0);
+        assert_valid_content(auth_buffer_struct.content_type, echo_data)?;
+        auth_buffer_struct.update_content_hash(echo_data);
+        // The echo data is no longer a Merkle root
+        auth_buffer_struct.merkle_leaf_count = 0.into();
+    }
+
+    close_account(staging_info, owner_info)
+}
@@ -0,0 +1,31 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::utils::*;
+
+pub fn process(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+
+    // Get account info for each account
+    let echo_buffer_info = next_account_info(accounts_iter)?;
+
+    // Validate accounts input
+    assert_is_writable(echo_buffer_info)?;
+    assert_is_initialized(echo_buffer_info)?;
+
+    // Write the data to the buffer
+    let mut echo_buffer_data = echo_buffer_info.try_borrow_mut_data()?;
+    assert_with_msg(
+        echo_buffer_data.iter().all(|b| *b == 0),
+        EchoError::BufferNotEmpty.into(),
+        "Echo buffer is not empty.",
+    )?;
+    let len = echo_buffer_data.len().min(data.len());
+    echo_buffer_data[..len].copy_from_slice(&data[..len]);
+
+    Ok(())
+}
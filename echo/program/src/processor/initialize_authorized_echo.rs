@@ -0,0 +1,105 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::{find_authorized_buffer_address, AUTHORIZED_BUFFER_SEED};
+use crate::state::{
+    AccountHeader, AccountType, AuthorizedBuffer, ContentHeader, ContentType, Expiry, ExpiryKind,
+};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    buffer_seed: u64,
+    buffer_size: u64,
+    expiry: Option<Expiry>,
+    content: Option<ContentHeader>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let buffer_seed_bytes = u64::to_le_bytes(buffer_seed);
+
+    // Get account info for each account
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_system_program(system_program_info)?;
+
+    // Find PDA address for authorized_buffer
+    let (authorized_buffer_key, bump_seed) =
+        find_authorized_buffer_address(program_id, authority_info.key, buffer_seed);
+
+    // Confirm that the PDA address we found matches the one passed into the program
+    assert_with_msg(
+        authorized_buffer_key == *authorized_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid authorized_buffer address.",
+    )?;
+
+    // Create the authorized_buffer account
+    let buffer_size = usize::try_from(buffer_size).map_err(|_| EchoError::BufferTooSmall)?;
+    assert_with_msg(
+        buffer_size >= AuthorizedBuffer::HEADER_SIZE,
+        EchoError::BufferTooSmall.into(),
+        &format!("Buffer size must be >= {}.", AuthorizedBuffer::HEADER_SIZE),
+    )?;
+    invoke_signed(
+        &system_instruction::create_account(
+            // Set authority as fee payer
+            authority_info.key,
+            &authorized_buffer_key,
+            Rent::get()?.minimum_balance(buffer_size),
+            buffer_size as u64,
+            program_id,
+        ),
+        &[
+            authority_info.clone(),
+            authorized_buffer_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            AUTHORIZED_BUFFER_SEED,
+            authority_info.key.as_ref(),
+            &buffer_seed_bytes,
+            &[bump_seed],
+        ]],
+    )?;
+
+    // Write the header, the echo data after it is already zeroed by the System Program
+    let mut auth_buffer_struct = AuthorizedBuffer {
+        header: AccountHeader::new(AccountType::AuthorizedBuffer),
+        bump_seed,
+        buffer_seed: buffer_seed.into(),
+        seed_authority: *authority_info.key,
+        authority: *authority_info.key,
+        pending_authority: Pubkey::default(),
+        sealed: 0,
+        expiry_kind: ExpiryKind::None as u8,
+        expires_at: 0.into(),
+        nonce: 0.into(),
+        content_hash: [0; 32],
+        merkle_leaf_count: 0.into(),
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        reserved: [0; 61],
+    };
+    auth_buffer_struct.set_expiry(expiry);
+    auth_buffer_struct.set_content_header(content);
+    auth_buffer_struct.update_content_hash(
+        &authorized_buffer_info.try_borrow_data()?[AuthorizedBuffer::HEADER_SIZE..],
+    );
+    initialize_buffer(
+        &mut authorized_buffer_info.try_borrow_mut_data()?,
+        &auth_buffer_struct,
+    )
+}
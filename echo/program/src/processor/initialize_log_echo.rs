@@ -0,0 +1,89 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::{find_log_buffer_address, LOG_BUFFER_SEED};
+use crate::state::{AccountHeader, AccountType, LogBuffer};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    buffer_seed: u64,
+    buffer_size: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let buffer_seed_bytes = u64::to_le_bytes(buffer_seed);
+
+    // Get account info for each account
+    let log_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(log_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_system_program(system_program_info)?;
+
+    let (log_buffer_key, bump_seed) =
+        find_log_buffer_address(program_id, authority_info.key, buffer_seed);
+    assert_with_msg(
+        log_buffer_key == *log_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid log_buffer address.",
+    )?;
+
+    // Create the log_buffer account
+    let buffer_size = usize::try_from(buffer_size).map_err(|_| EchoError::BufferTooSmall)?;
+    assert_with_msg(
+        buffer_size >= LogBuffer::HEADER_SIZE,
+        EchoError::BufferTooSmall.into(),
+        &format!("Buffer size must be >= {}.", LogBuffer::HEADER_SIZE),
+    )?;
+    invoke_signed(
+        &system_instruction::create_account(
+            authority_info.key,
+            &log_buffer_key,
+            Rent::get()?.minimum_balance(buffer_size),
+            buffer_size as u64,
+            program_id,
+        ),
+        &[
+            authority_info.clone(),
+            log_buffer_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            LOG_BUFFER_SEED,
+            authority_info.key.as_ref(),
+            &buffer_seed_bytes,
+            &[bump_seed],
+        ]],
+    )?;
+
+    // The ring starts empty, with both cursors at its start
+    let mut log_buffer_struct = LogBuffer {
+        header: AccountHeader::new(AccountType::LogBuffer),
+        bump_seed,
+        buffer_seed: buffer_seed.into(),
+        authority: *authority_info.key,
+        head: 0.into(),
+        tail: 0.into(),
+        records: 0.into(),
+        appended: 0.into(),
+        content_hash: [0; 32],
+        reserved: [0; 32],
+    };
+    log_buffer_struct
+        .update_content_hash(&log_buffer_info.try_borrow_data()?[LogBuffer::HEADER_SIZE..]);
+    initialize_buffer(
+        &mut log_buffer_info.try_borrow_mut_data()?,
+        &log_buffer_struct,
+    )
+}
@@ -0,0 +1,113 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+
+use crate::error::EchoError;
+use crate::pda::{find_mailbox_address, MAILBOX_SEED};
+use crate::state::{AccountHeader, AccountType, Mailbox, VendingMachineBuffer};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    buffer_size: u64,
+    min_postage: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let mailbox_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+    let postage_account_info = accounts_iter.next();
+
+    // Validate account inputs
+    assert_is_writable(mailbox_info)?;
+    assert_is_signer(recipient_info)?;
+    assert_is_system_program(system_program_info)?;
+
+    let (mailbox_key, bump_seed) = find_mailbox_address(program_id, recipient_info.key);
+    assert_with_msg(
+        mailbox_key == *mailbox_info.key,
+        EchoError::InvalidMailbox.into(),
+        "Invalid mailbox address.",
+    )?;
+
+    // Without a postage account the mailbox cannot be paid, so it cannot ask for postage either
+    let postage_account = postage_account_info.map_or(Pubkey::default(), |info| *info.key);
+    assert_with_msg(
+        min_postage == 0 || postage_account != Pubkey::default(),
+        EchoError::InvalidPostageAccount.into(),
+        "A postage_account is needed to ask for postage.",
+    )?;
+
+    // Postage is paid in the token of a vending machine, so the postage account must be a token account of its mint
+    if let Some(postage_account_info) = postage_account_info {
+        let vm_buffer_info = next_account_info(accounts_iter)?;
+        let vm_mint_info = next_account_info(accounts_iter)?;
+        assert_is_owned_by(vm_buffer_info, program_id)?;
+        let vm_buffer_data = vm_buffer_info.try_borrow_data()?;
+        let (vm_buffer_struct, _) = load_buffer::<VendingMachineBuffer>(&vm_buffer_data)?;
+        assert_vending_machine_address(program_id, vm_buffer_info, vm_buffer_struct, vm_mint_info)?;
+
+        assert_with_msg(
+            *postage_account_info.owner == spl_token::id(),
+            EchoError::InvalidPostageAccount.into(),
+            &format!(
+                "Account {} is not owned by the Token Program.",
+                postage_account_info.key
+            ),
+        )?;
+        let postage_token_account =
+            spl_token::state::Account::unpack(&postage_account_info.try_borrow_data()?)
+                .map_err(|_| EchoError::InvalidPostageAccount)?;
+        assert_with_msg(
+            postage_token_account.mint == *vm_mint_info.key,
+            EchoError::InvalidPostageAccount.into(),
+            &format!(
+                "Account {} is not a token account of {}.",
+                postage_account_info.key, vm_mint_info.key
+            ),
+        )?;
+    }
+
+    let buffer_size = usize::try_from(buffer_size).map_err(|_| EchoError::BufferTooSmall)?;
+    assert_with_msg(
+        buffer_size >= Mailbox::HEADER_SIZE,
+        EchoError::BufferTooSmall.into(),
+        &format!("Buffer size must be >= {}.", Mailbox::HEADER_SIZE),
+    )?;
+    invoke_signed(
+        &system_instruction::create_account(
+            recipient_info.key,
+            &mailbox_key,
+            Rent::get()?.minimum_balance(buffer_size),
+            buffer_size as u64,
+            program_id,
+        ),
+        &[
+            recipient_info.clone(),
+            mailbox_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[MAILBOX_SEED, recipient_info.key.as_ref(), &[bump_seed]]],
+    )?;
+
+    initialize_buffer(
+        &mut mailbox_info.try_borrow_mut_data()?,
+        &Mailbox {
+            header: AccountHeader::new(AccountType::Mailbox),
+            bump_seed,
+            recipient: *recipient_info.key,
+            postage_account,
+            min_postage: min_postage.into(),
+            len: 0.into(),
+            messages: 0.into(),
+            reserved: [0; 32],
+        },
+    )
+}
@@ -0,0 +1,104 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::invoke_signed,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+use spl_token::state::Mint;
+
+use crate::error::EchoError;
+use crate::pda::{find_token_gated_address, TOKEN_GATED_SEED};
+use crate::state::{AccountHeader, AccountType, ContentHeader, ContentType, TokenGatedBuffer};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    buffer_seed: u64,
+    buffer_size: u64,
+    min_balance: u64,
+    content: Option<ContentHeader>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let buffer_seed_bytes = u64::to_le_bytes(buffer_seed);
+
+    // Get account info for each account
+    let token_gated_buffer_info = next_account_info(accounts_iter)?;
+    let mint_info = next_account_info(accounts_iter)?;
+    let creator_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(token_gated_buffer_info)?;
+    assert_is_signer(creator_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(mint_info, &spl_token::id())?;
+    Mint::unpack(&mint_info.try_borrow_data()?).map_err(|_| EchoError::InvalidMint)?;
+    assert_with_msg(
+        min_balance > 0,
+        EchoError::ZeroMinBalance.into(),
+        "Minimum balance must be at least 1, or anyone could write.",
+    )?;
+
+    let (token_gated_buffer_key, bump_seed) =
+        find_token_gated_address(program_id, mint_info.key, creator_info.key, buffer_seed);
+    assert_with_msg(
+        token_gated_buffer_key == *token_gated_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid token_gated_buffer address.",
+    )?;
+
+    // Create the token_gated_buffer account
+    let buffer_size = usize::try_from(buffer_size).map_err(|_| EchoError::BufferTooSmall)?;
+    assert_with_msg(
+        buffer_size >= TokenGatedBuffer::HEADER_SIZE,
+        EchoError::BufferTooSmall.into(),
+        &format!("Buffer size must be >= {}.", TokenGatedBuffer::HEADER_SIZE),
+    )?;
+    invoke_signed(
+        &system_instruction::create_account(
+            creator_info.key,
+            &token_gated_buffer_key,
+            Rent::get()?.minimum_balance(buffer_size),
+            buffer_size as u64,
+            program_id,
+        ),
+        &[
+            creator_info.clone(),
+            token_gated_buffer_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            TOKEN_GATED_SEED,
+            mint_info.key.as_ref(),
+            creator_info.key.as_ref(),
+            &buffer_seed_bytes,
+            &[bump_seed],
+        ]],
+    )?;
+
+    // Write the header, the echo data after it is already zeroed by the System Program
+    let mut token_gated_buffer_struct = TokenGatedBuffer {
+        header: AccountHeader::new(AccountType::TokenGatedBuffer),
+        bump_seed,
+        buffer_seed: buffer_seed.into(),
+        creator: *creator_info.key,
+        mint: *mint_info.key,
+        min_balance: min_balance.into(),
+        content_hash: [0; 32],
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        reserved: [0; 23],
+    };
+    token_gated_buffer_struct.set_content_header(content);
+    token_gated_buffer_struct.update_content_hash(
+        &token_gated_buffer_info.try_borrow_data()?[TokenGatedBuffer::HEADER_SIZE..],
+    );
+    initialize_buffer(
+        &mut token_gated_buffer_info.try_borrow_mut_data()?,
+        &token_gated_buffer_struct,
+    )
+}
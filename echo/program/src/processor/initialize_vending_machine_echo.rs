@@ -0,0 +1,159 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::{invoke, invoke_signed},
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction,
+    sysvar::{rent::Rent, Sysvar},
+};
+use spl_token::instruction::initialize_account2;
+
+use crate::error::EchoError;
+use crate::pda::{
+    find_treasury_address, find_vending_machine_address, TREASURY_SEED, VENDING_MACHINE_SEED,
+};
+use crate::state::{
+    AccountHeader, AccountType, ContentHeader, ContentType, PaymentMode, Pricing,
+    VendingMachineBuffer,
+};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    machine_id: u64,
+    pricing: Pricing,
+    buffer_size: usize,
+    payment_mode: PaymentMode,
+    content: Option<ContentHeader>,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let payer_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_signer(payer_info)?;
+    assert_is_system_program(system_program_info)?;
+
+    let (vm_buffer_key, bump_seed) =
+        find_vending_machine_address(program_id, vm_mint_info.key, machine_id);
+
+    assert_with_msg(
+        vm_buffer_key == *vm_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid Vending Machine Buffer account.",
+    )?;
+
+    assert_with_msg(
+        buffer_size >= VendingMachineBuffer::HEADER_SIZE,
+        EchoError::BufferTooSmall.into(),
+        &format!(
+            "Buffer size must be >= {}.",
+            VendingMachineBuffer::HEADER_SIZE
+        ),
+    )?;
+    invoke_signed(
+        &system_instruction::create_account(
+            payer_info.key,
+            &vm_buffer_key,
+            Rent::get()?.minimum_balance(buffer_size),
+            buffer_size as u64,
+            program_id,
+        ),
+        &[
+            payer_info.clone(),
+            vm_buffer_info.clone(),
+            system_program_info.clone(),
+        ],
+        &[&[
+            VENDING_MACHINE_SEED,
+            vm_mint_info.key.as_ref(),
+            &machine_id.to_le_bytes(),
+            &[bump_seed],
+        ]],
+    )?;
+
+    // In treasury mode, create the token account that collects payments. The machine PDA is its owner, so only
+    // this program can move the tokens out.
+    let treasury = match payment_mode {
+        PaymentMode::Burn => Pubkey::default(),
+        PaymentMode::Treasury => {
+            let treasury_info = next_account_info(accounts_iter)?;
+            let token_program_info = next_account_info(accounts_iter)?;
+            let rent_sysvar_info = next_account_info(accounts_iter)?;
+
+            assert_is_writable(treasury_info)?;
+            assert_is_token_program(token_program_info)?;
+
+            let (treasury_key, treasury_bump_seed) =
+                find_treasury_address(program_id, &vm_buffer_key);
+            assert_with_msg(
+                treasury_key == *treasury_info.key,
+                EchoError::InvalidTreasury.into(),
+                "Invalid treasury account.",
+            )?;
+
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    &treasury_key,
+                    Rent::get()?.minimum_balance(spl_token::state::Account::LEN),
+                    spl_token::state::Account::LEN as u64,
+                    &spl_token::id(),
+                ),
+                &[
+                    payer_info.clone(),
+                    treasury_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[TREASURY_SEED, vm_buffer_key.as_ref(), &[treasury_bump_seed]]],
+            )?;
+            invoke(
+                &initialize_account2(
+                    token_program_info.key,
+                    &treasury_key,
+                    vm_mint_info.key,
+                    &vm_buffer_key,
+                )?,
+                &[
+                    treasury_info.clone(),
+                    vm_mint_info.clone(),
+                    rent_sysvar_info.clone(),
+                    token_program_info.clone(),
+                ],
+            )?;
+            treasury_key
+        }
+    };
+
+    // Write the header, the echo data after it is already zeroed by the System Program
+    let mut vm_buffer_struct = VendingMachineBuffer {
+        header: AccountHeader::new(AccountType::VendingMachineBuffer),
+        bump_seed,
+        price: pricing.base.into(),
+        owner: *payer_info.key,
+        payment_mode: payment_mode as u8,
+        treasury,
+        machine_id: machine_id.into(),
+        price_per_byte: pricing.per_byte.into(),
+        credit_lifetime: 0.into(),
+        refund_credits: 0,
+        outstanding_credits: 0.into(),
+        sealed: 0,
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        content_hash: [0; 32],
+        reserved: [0; 20],
+    };
+    vm_buffer_struct.set_content_header(content);
+    vm_buffer_struct.update_content_hash(
+        &vm_buffer_info.try_borrow_data()?[VendingMachineBuffer::HEADER_SIZE..],
+    );
+    initialize_buffer(
+        &mut vm_buffer_info.try_borrow_mut_data()?,
+        &vm_buffer_struct,
+    )
+}
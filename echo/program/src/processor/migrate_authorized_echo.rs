@@ -0,0 +1,83 @@
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::pda::create_authorized_buffer_address;
+use crate::state::{
+    AccountHeader, AccountType, AuthorizedBuffer, AuthorizedBufferV1, LegacyAuthorizedBuffer,
+    OriginalAuthorizedBuffer,
+};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_writable(authority_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Read the version 1 layout, or one of the layouts without a header. The original layout does not store the
+    // authority, the signer is taken as the key the address was derived from and checked below. Original buffers can
+    // also parse as the legacy layout, so that layout is only taken when its fields derive the buffer address.
+    let v1_struct = {
+        let authorized_buffer_data = authorized_buffer_info.try_borrow_data()?;
+        let v1_header = AccountHeader {
+            version: 1,
+            ..AccountHeader::new(AccountType::AuthorizedBuffer)
+        };
+        if authorized_buffer_data.get(..AccountHeader::LEN) == Some(bytemuck::bytes_of(&v1_header))
+        {
+            deserialize_legacy_buffer::<AuthorizedBufferV1>(&authorized_buffer_data)?
+        } else if let Some(legacy_struct) =
+            LegacyAuthorizedBuffer::try_from_slice(&authorized_buffer_data)
+                .ok()
+                .filter(|legacy_struct| {
+                    create_authorized_buffer_address(
+                        program_id,
+                        &legacy_struct.seed_authority,
+                        legacy_struct.buffer_seed,
+                        legacy_struct.bump_seed,
+                    )
+                    .map_or(false, |key| key == *authorized_buffer_info.key)
+                })
+        {
+            legacy_struct.into()
+        } else {
+            deserialize_legacy_buffer::<OriginalAuthorizedBuffer>(&authorized_buffer_data)?
+                .into_v1(*authority_info.key)
+        }
+    };
+
+    // The old fields must derive the buffer address, which rules out accounts that are already migrated, and only
+    // the authority may migrate
+    let auth_buffer_struct = AuthorizedBuffer::from(&v1_struct);
+    assert_buffer_authority(
+        program_id,
+        authorized_buffer_info,
+        &auth_buffer_struct,
+        authority_info,
+    )?;
+
+    // Rewrite the account as the zero-copy header followed by the echo data, the authority covers any extra rent
+    // and gets back any surplus
+    let new_size = AuthorizedBuffer::HEADER_SIZE + v1_struct.data.len();
+    rebalance_rent(
+        authorized_buffer_info,
+        authority_info,
+        system_program_info,
+        new_size,
+    )?;
+    authorized_buffer_info.realloc(new_size, false)?;
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    initialize_buffer(&mut authorized_buffer_data, &auth_buffer_struct)?;
+    authorized_buffer_data[AuthorizedBuffer::HEADER_SIZE..].copy_from_slice(&v1_struct.data);
+    Ok(())
+}
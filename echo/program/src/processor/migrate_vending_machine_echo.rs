@@ -0,0 +1,73 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use spl_token::state::Mint;
+
+use crate::error::EchoError;
+use crate::state::{
+    AccountHeader, AccountType, LegacyVendingMachineBuffer, VendingMachineBuffer,
+    VendingMachineBufferV1,
+};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let mint_authority_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_writable(mint_authority_info)?;
+    assert_is_signer(mint_authority_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+    assert_is_owned_by(vm_mint_info, &spl_token::id())?;
+
+    // Older machines have no owner, the mint authority migrates them and becomes their owner
+    let vm_mint =
+        Mint::unpack(&vm_mint_info.try_borrow_data()?).map_err(|_| EchoError::InvalidMint)?;
+    assert_with_msg(
+        vm_mint.mint_authority == COption::Some(*mint_authority_info.key),
+        EchoError::InvalidMintAuthority.into(),
+        "Vending machine can only be migrated by the mint authority.",
+    )?;
+
+    // Read the version 1 layout, or the legacy layout without a header
+    let v1_struct = {
+        let vm_buffer_data = vm_buffer_info.try_borrow_data()?;
+        let v1_header = AccountHeader {
+            version: 1,
+            ..AccountHeader::new(AccountType::VendingMachineBuffer)
+        };
+        if vm_buffer_data.get(..AccountHeader::LEN) == Some(bytemuck::bytes_of(&v1_header)) {
+            deserialize_legacy_buffer::<VendingMachineBufferV1>(&vm_buffer_data)?
+        } else {
+            deserialize_legacy_buffer::<LegacyVendingMachineBuffer>(&vm_buffer_data)?.into()
+        }
+    };
+
+    // The old fields must derive the buffer address, which rules out accounts that are already migrated
+    let mut vm_buffer_struct = VendingMachineBuffer::from(&v1_struct);
+    assert_vending_machine_address(program_id, vm_buffer_info, &vm_buffer_struct, vm_mint_info)?;
+    vm_buffer_struct.owner = *mint_authority_info.key;
+
+    // Rewrite the account as the zero-copy header followed by the echo data, the new owner covers any extra rent
+    // and gets back any surplus
+    let new_size = VendingMachineBuffer::HEADER_SIZE + v1_struct.data.len();
+    rebalance_rent(
+        vm_buffer_info,
+        mint_authority_info,
+        system_program_info,
+        new_size,
+    )?;
+    vm_buffer_info.realloc(new_size, false)?;
+    let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
+    initialize_buffer(&mut vm_buffer_data, &vm_buffer_struct)?;
+    vm_buffer_data[VendingMachineBuffer::HEADER_SIZE..].copy_from_slice(&v1_struct.data);
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::set_return_data,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{Mailbox, Message};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let mailbox_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(mailbox_info)?;
+    assert_is_signer(recipient_info)?;
+    assert_is_owned_by(mailbox_info, program_id)?;
+
+    let mut mailbox_data = mailbox_info.try_borrow_mut_data()?;
+    let (mailbox_struct, queue) = load_buffer_mut::<Mailbox>(&mut mailbox_data)?;
+    assert_mailbox_recipient(program_id, mailbox_info, mailbox_struct, recipient_info)?;
+    let messages = u64::from(mailbox_struct.messages);
+    assert_with_msg(
+        messages > 0,
+        EchoError::MailboxEmpty.into(),
+        "The mailbox has no messages.",
+    )?;
+
+    // The oldest message is at the front of the queue
+    let len = u64::from(mailbox_struct.len) as usize;
+    let mut rest = queue.get(..len).ok_or(EchoError::InvalidBufferData)?;
+    Message::deserialize(&mut rest).map_err(|_| EchoError::InvalidBufferData)?;
+    let message_len = len - rest.len();
+    set_return_data(&queue[..message_len]);
+
+    // Move the other messages up and zero the space they leave
+    queue.copy_within(message_len..len, 0);
+    queue[len - message_len..len].fill(0);
+    mailbox_struct.len = ((len - message_len) as u64).into();
+    mailbox_struct.messages = (messages - 1).into();
+    Ok(())
+}
@@ -0,0 +1,46 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::{set_return_data, MAX_RETURN_DATA},
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{AccountType, AuthorizedBuffer, VendingMachineBuffer};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    offset: u64,
+    len: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let buffer_info = next_account_info(accounts_iter)?;
+
+    assert_is_owned_by(buffer_info, program_id)?;
+    assert_with_msg(
+        len <= MAX_RETURN_DATA as u64,
+        EchoError::ReadTooLarge.into(),
+        &format!("At most {} bytes can be read at once.", MAX_RETURN_DATA),
+    )?;
+
+    // Both buffer kinds are read by the same instruction, the account type picks the checks
+    let buffer_data = buffer_info.try_borrow_data()?;
+    let account_type = buffer_data.first().copied();
+    let echo_data = if account_type == Some(AccountType::AuthorizedBuffer as u8) {
+        let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&buffer_data)?;
+        assert_authorized_buffer_address(program_id, buffer_info, auth_buffer_struct)?;
+        echo_data
+    } else if account_type == Some(AccountType::VendingMachineBuffer as u8) {
+        let vm_mint_info = next_account_info(accounts_iter)?;
+        let (vm_buffer_struct, echo_data) = load_buffer::<VendingMachineBuffer>(&buffer_data)?;
+        assert_vending_machine_address(program_id, buffer_info, vm_buffer_struct, vm_mint_info)?;
+        echo_data
+    } else {
+        return Err(EchoError::InvalidAccountType.into());
+    };
+
+    set_return_data(read_at(echo_data, offset, len)?);
+    Ok(())
+}
@@ -0,0 +1,40 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs, anyone may reclaim so nobody needs to sign
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_writable(recipient_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    let auth_buffer_struct =
+        *load_buffer::<AuthorizedBuffer>(&authorized_buffer_info.try_borrow_data()?)?.0;
+    assert_authorized_buffer_address(program_id, authorized_buffer_info, &auth_buffer_struct)?;
+    assert_not_sealed(auth_buffer_struct.sealed)?;
+
+    assert_with_msg(
+        is_buffer_expired(&auth_buffer_struct)?,
+        EchoError::BufferNotExpired.into(),
+        "Only expired buffers can be reclaimed.",
+    )?;
+
+    // The authority the address was derived from paid for the buffer, authority transfers do not change that
+    assert_with_msg(
+        auth_buffer_struct.seed_authority == *recipient_info.key,
+        EchoError::InvalidRecipient.into(),
+        "Rent of an expired buffer goes back to the account that created it.",
+    )?;
+
+    close_account(authorized_buffer_info, recipient_info)
+}
@@ -0,0 +1,53 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{AuthorizedBuffer, BufferWriter};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let buffer_writer_info = next_account_info(accounts_iter)?;
+    let recipient_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_signer(authority_info)?;
+    assert_is_writable(buffer_writer_info)?;
+    assert_is_writable(recipient_info)?;
+    assert_is_owned_by(buffer_writer_info, program_id)?;
+
+    let buffer_writer_struct =
+        *load_buffer::<BufferWriter>(&buffer_writer_info.try_borrow_data()?)?.0;
+    assert_with_msg(
+        buffer_writer_struct.authorized_buffer == *authorized_buffer_info.key,
+        EchoError::InvalidWriter.into(),
+        "Writer does not belong to the authorized_buffer.",
+    )?;
+
+    // The authority that added the writer may always remove it, even once the buffer is closed. Anyone else must be
+    // the current authority.
+    if buffer_writer_struct.added_by != *authority_info.key {
+        assert_is_owned_by(authorized_buffer_info, program_id)?;
+        let auth_buffer_struct =
+            *load_buffer::<AuthorizedBuffer>(&authorized_buffer_info.try_borrow_data()?)?.0;
+        assert_buffer_authority(
+            program_id,
+            authorized_buffer_info,
+            &auth_buffer_struct,
+            authority_info,
+        )?;
+    }
+
+    // The rent goes back to whoever paid for the entry
+    assert_with_msg(
+        buffer_writer_struct.added_by == *recipient_info.key,
+        EchoError::InvalidRecipient.into(),
+        "Rent of a writer goes back to the authority that added it.",
+    )?;
+    close_account(buffer_writer_info, recipient_info)
+}
@@ -0,0 +1,68 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], new_size: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_writable(authority_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Confirm authority
+    {
+        let authorized_buffer_data = authorized_buffer_info.try_borrow_data()?;
+        let (auth_buffer_struct, _) = load_buffer::<AuthorizedBuffer>(&authorized_buffer_data)?;
+        assert_buffer_authority(
+            program_id,
+            authorized_buffer_info,
+            auth_buffer_struct,
+            authority_info,
+        )?;
+        assert_not_sealed(auth_buffer_struct.sealed)?;
+        assert_not_expired(auth_buffer_struct)?;
+    }
+
+    let new_size = usize::try_from(new_size).map_err(|_| EchoError::BufferGrowthTooLarge)?;
+    assert_with_msg(
+        new_size >= AuthorizedBuffer::HEADER_SIZE,
+        EchoError::BufferTooSmall.into(),
+        &format!("Buffer size must be >= {}.", AuthorizedBuffer::HEADER_SIZE),
+    )?;
+    assert_with_msg(
+        new_size <= authorized_buffer_info.data_len() + MAX_PERMITTED_DATA_INCREASE,
+        EchoError::BufferGrowthTooLarge.into(),
+        "Buffer can grow by at most 10KiB per instruction.",
+    )?;
+
+    // Top up or refund rent so the buffer stays exactly rent-exempt
+    rebalance_rent(
+        authorized_buffer_info,
+        authority_info,
+        system_program_info,
+        new_size,
+    )?;
+
+    // Resize the account, growing zero-fills the new echo data and shrinking truncates it
+    authorized_buffer_info.realloc(new_size, true)?;
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    let (auth_buffer_struct, echo_data) =
+        load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+    assert_valid_content(auth_buffer_struct.content_type, echo_data)?;
+    auth_buffer_struct.update_content_hash(echo_data);
+    // The echo data is no longer a Merkle root
+    auth_buffer_struct.merkle_leaf_count = 0.into();
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{AccountType, AuthorizedBuffer, VendingMachineBuffer};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_owned_by(buffer_info, program_id)?;
+
+    // Both buffer kinds are sealed by the same instruction, the account type picks the checks
+    let mut buffer_data = buffer_info.try_borrow_mut_data()?;
+    let account_type = buffer_data.first().copied();
+    if account_type == Some(AccountType::AuthorizedBuffer as u8) {
+        let (auth_buffer_struct, _) = load_buffer_mut::<AuthorizedBuffer>(&mut buffer_data)?;
+        assert_buffer_authority(program_id, buffer_info, auth_buffer_struct, authority_info)?;
+        assert_not_sealed(auth_buffer_struct.sealed)?;
+        auth_buffer_struct.sealed = 1;
+        Ok(())
+    } else if account_type == Some(AccountType::VendingMachineBuffer as u8) {
+        let vm_mint_info = next_account_info(accounts_iter)?;
+        let (vm_buffer_struct, _) = load_buffer_mut::<VendingMachineBuffer>(&mut buffer_data)?;
+        assert_vending_machine_address(program_id, buffer_info, vm_buffer_struct, vm_mint_info)?;
+        assert_vending_machine_owner(vm_buffer_struct, authority_info)?;
+        assert_not_sealed(vm_buffer_struct.sealed)?;
+        vm_buffer_struct.sealed = 1;
+        Ok(())
+    } else {
+        Err(EchoError::InvalidAccountType.into())
+    }
+}
@@ -0,0 +1,90 @@
+use borsh::BorshSerialize;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    program::MAX_RETURN_DATA,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{Mailbox, Message, MESSAGE_HEADER_SIZE};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: Vec<u8>,
+    postage: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let mailbox_info = next_account_info(accounts_iter)?;
+    let sender_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(mailbox_info)?;
+    assert_is_signer(sender_info)?;
+    assert_is_owned_by(mailbox_info, program_id)?;
+
+    let mut mailbox_data = mailbox_info.try_borrow_mut_data()?;
+    let (mailbox_struct, queue) = load_buffer_mut::<Mailbox>(&mut mailbox_data)?;
+    assert_mailbox_address(program_id, mailbox_info, mailbox_struct)?;
+
+    // Popped messages are returned whole, so they must fit in the return data
+    assert_with_msg(
+        MESSAGE_HEADER_SIZE + data.len() <= MAX_RETURN_DATA,
+        EchoError::MessageTooLarge.into(),
+        &format!(
+            "Messages can be at most {} bytes.",
+            MAX_RETURN_DATA - MESSAGE_HEADER_SIZE
+        ),
+    )?;
+    assert_with_msg(
+        postage >= u64::from(mailbox_struct.min_postage),
+        EchoError::InsufficientPostage.into(),
+        &format!(
+            "The mailbox asks for a postage of {}.",
+            u64::from(mailbox_struct.min_postage)
+        ),
+    )?;
+
+    // Postage goes straight to the recipient
+    if postage > 0 {
+        let sender_token_account_info = next_account_info(accounts_iter)?;
+        let postage_account_info = next_account_info(accounts_iter)?;
+        let token_program_info = next_account_info(accounts_iter)?;
+        assert_with_msg(
+            mailbox_struct.postage_account != Pubkey::default()
+                && mailbox_struct.postage_account == *postage_account_info.key,
+            EchoError::InvalidPostageAccount.into(),
+            &format!(
+                "Account {} is not the postage account of the mailbox.",
+                postage_account_info.key
+            ),
+        )?;
+        transfer_tokens(
+            sender_token_account_info,
+            postage_account_info,
+            sender_info,
+            token_program_info,
+            postage,
+            &[],
+        )?;
+    }
+
+    let message = Message {
+        sender: *sender_info.key,
+        postage,
+        data,
+    }
+    .try_to_vec()?;
+    let len = u64::from(mailbox_struct.len);
+    assert_with_msg(
+        len + message.len() as u64 <= queue.len() as u64,
+        EchoError::MailboxFull.into(),
+        "The mailbox has no room for the message.",
+    )?;
+    write_at(queue, len, &message)?;
+    mailbox_struct.len = (len + message.len() as u64).into();
+    mailbox_struct.messages = (u64::from(mailbox_struct.messages) + 1).into();
+    Ok(())
+}
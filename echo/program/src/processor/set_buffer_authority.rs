@@ -0,0 +1,36 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    new_authority: &Pubkey,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    // Confirm authority
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    let (auth_buffer_struct, _) = load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+    assert_buffer_authority(
+        program_id,
+        authorized_buffer_info,
+        auth_buffer_struct,
+        authority_info,
+    )?;
+
+    auth_buffer_struct.pending_authority = *new_authority;
+    Ok(())
+}
@@ -0,0 +1,59 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash::{Hash, HASH_BYTES},
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    root: Hash,
+    leaf_count: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(authorized_buffer_info)?;
+    assert_is_signer(authority_info)?;
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+    assert_with_msg(
+        leaf_count > 0,
+        EchoError::EmptyMerkleTree.into(),
+        "A Merkle tree needs at least one leaf.",
+    )?;
+
+    // Confirm authority, or a writer on the allow-list of the buffer
+    let mut authorized_buffer_data = authorized_buffer_info.try_borrow_mut_data()?;
+    let (auth_buffer_struct, echo_data) =
+        load_buffer_mut::<AuthorizedBuffer>(&mut authorized_buffer_data)?;
+    assert_buffer_writer(
+        program_id,
+        authorized_buffer_info,
+        auth_buffer_struct,
+        authority_info,
+        accounts_iter.next(),
+    )?;
+    assert_not_sealed(auth_buffer_struct.sealed)?;
+    assert_not_expired(auth_buffer_struct)?;
+    assert_with_msg(
+        echo_data.len() >= HASH_BYTES,
+        EchoError::BufferTooSmall.into(),
+        &format!("A Merkle root takes {} bytes.", HASH_BYTES),
+    )?;
+
+    // The root replaces the echo data
+    let (root_data, rest) = echo_data.split_at_mut(HASH_BYTES);
+    root_data.copy_from_slice(root.as_ref());
+    rest.fill(0);
+    auth_buffer_struct.merkle_leaf_count = leaf_count.into();
+    assert_valid_content(auth_buffer_struct.content_type, echo_data)?;
+    auth_buffer_struct.update_content_hash(echo_data);
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::VendingMachineBuffer;
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    credit_lifetime: u64,
+    refund_credits: bool,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+
+    let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
+    let (vm_buffer_struct, _) = load_buffer_mut::<VendingMachineBuffer>(&mut vm_buffer_data)?;
+    assert_vending_machine_address(program_id, vm_buffer_info, vm_buffer_struct, vm_mint_info)?;
+    assert_vending_machine_owner(vm_buffer_struct, owner_info)?;
+
+    // Refunds come out of the treasury, so it must still hold every credit that could be refunded
+    if refund_credits {
+        let treasury_info = next_account_info(accounts_iter)?;
+        assert_treasury(vm_buffer_struct, treasury_info)?;
+        assert_with_msg(
+            treasury_balance(treasury_info)? >= u64::from(vm_buffer_struct.outstanding_credits),
+            EchoError::ProceedsReservedForCredits.into(),
+            "Treasury holds fewer tokens than the outstanding credits.",
+        )?;
+    }
+
+    vm_buffer_struct.credit_lifetime = credit_lifetime.into();
+    vm_buffer_struct.refund_credits = refund_credits as u8;
+    Ok(())
+}
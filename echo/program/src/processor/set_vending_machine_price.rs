@@ -0,0 +1,29 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::{Pricing, VendingMachineBuffer};
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], pricing: Pricing) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+
+    let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
+    let (vm_buffer_struct, _) = load_buffer_mut::<VendingMachineBuffer>(&mut vm_buffer_data)?;
+    assert_vending_machine_address(program_id, vm_buffer_info, vm_buffer_struct, vm_mint_info)?;
+    assert_vending_machine_owner(vm_buffer_struct, owner_info)?;
+
+    // The price is not part of the address, so it can change in place
+    vm_buffer_struct.price = pricing.base.into();
+    vm_buffer_struct.price_per_byte = pricing.per_byte.into();
+    Ok(())
+}
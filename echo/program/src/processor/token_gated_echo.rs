@@ -0,0 +1,37 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::TokenGatedBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let token_gated_buffer_info = next_account_info(accounts_iter)?;
+    let holder_info = next_account_info(accounts_iter)?;
+    let holder_token_account_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(token_gated_buffer_info)?;
+    assert_is_signer(holder_info)?;
+    assert_is_owned_by(token_gated_buffer_info, program_id)?;
+
+    // Holding the tokens is enough, unlike the vending machine nothing is burned or moved
+    let mut token_gated_buffer_data = token_gated_buffer_info.try_borrow_mut_data()?;
+    let (token_gated_buffer_struct, echo_data) =
+        load_buffer_mut::<TokenGatedBuffer>(&mut token_gated_buffer_data)?;
+    assert_token_gate_holder(
+        program_id,
+        token_gated_buffer_info,
+        token_gated_buffer_struct,
+        holder_info,
+        holder_token_account_info,
+    )?;
+
+    write_repeated(echo_data, data)?;
+    assert_valid_content(token_gated_buffer_struct.content_type, echo_data)?;
+    token_gated_buffer_struct.update_content_hash(echo_data);
+    Ok(())
+}
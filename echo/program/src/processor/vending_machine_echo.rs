@@ -0,0 +1,63 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::state::VendingMachineBuffer;
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+    max_charge: u64,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let user_info = next_account_info(accounts_iter)?;
+    let user_token_account_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let token_program_info = next_account_info(accounts_iter)?;
+
+    assert_is_writable(vm_buffer_info)?;
+    assert_is_writable(user_token_account_info)?;
+    assert_is_writable(vm_mint_info)?;
+    assert_is_signer(user_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+
+    let mut vm_buffer_data = vm_buffer_info.try_borrow_mut_data()?;
+    let (vm_buffer_struct, echo_data) =
+        load_buffer_mut::<VendingMachineBuffer>(&mut vm_buffer_data)?;
+    assert_vending_machine_address(program_id, vm_buffer_info, vm_buffer_struct, vm_mint_info)?;
+    assert_not_sealed(vm_buffer_struct.sealed)?;
+
+    // Check the data before taking payment
+    write_repeated(echo_data, data)?;
+    assert_valid_content(vm_buffer_struct.content_type, echo_data)?;
+    vm_buffer_struct.update_content_hash(echo_data);
+
+    // All checks done, take payment for the whole echo data. Users with prepaid credits pass their credits account
+    // in place of a token account.
+    let charge = charge_for_write(vm_buffer_struct, echo_data.len(), max_charge)?;
+    if user_token_account_info.owner == program_id {
+        spend_credits(
+            program_id,
+            vm_buffer_info.key,
+            vm_buffer_struct,
+            user_info,
+            user_token_account_info,
+            charge,
+        )
+    } else {
+        pay_vending_machine(
+            vm_buffer_struct,
+            charge,
+            user_token_account_info,
+            vm_mint_info,
+            user_info,
+            token_program_info,
+            accounts_iter.next(),
+        )
+    }
+}
@@ -0,0 +1,42 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash::{Hash, HASH_BYTES},
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::merkle::verify_proof;
+use crate::state::AuthorizedBuffer;
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u64,
+    leaf: &[u8],
+    proof: &[Hash],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+
+    assert_is_owned_by(authorized_buffer_info, program_id)?;
+
+    let authorized_buffer_data = authorized_buffer_info.try_borrow_data()?;
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&authorized_buffer_data)?;
+    let leaf_count = u64::from(auth_buffer_struct.merkle_leaf_count);
+    let root = echo_data
+        .get(..HASH_BYTES)
+        .filter(|_| leaf_count > 0)
+        .map(Hash::new)
+        .ok_or(EchoError::NotMerkleBuffer)?;
+
+    assert_with_msg(
+        verify_proof(&root, leaf_count, index, leaf, proof),
+        EchoError::InvalidMerkleProof.into(),
+        &format!(
+            "Proof does not show the leaf at index {} of {}.",
+            index, leaf_count
+        ),
+    )
+}
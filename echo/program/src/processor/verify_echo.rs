@@ -0,0 +1,54 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    hash::Hash,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::{
+    AccountType, AuthorizedBuffer, LogBuffer, TokenGatedBuffer, VendingMachineBuffer,
+};
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    expected_hash: Hash,
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let buffer_info = next_account_info(accounts_iter)?;
+
+    assert_is_owned_by(buffer_info, program_id)?;
+
+    // Every buffer kind records the hash of its data, the account type picks the header
+    let buffer_data = buffer_info.try_borrow_data()?;
+    let account_type = buffer_data.first().copied();
+    let content_hash = if account_type == Some(AccountType::AuthorizedBuffer as u8) {
+        load_buffer::<AuthorizedBuffer>(&buffer_data)?
+            .0
+            .content_hash
+    } else if account_type == Some(AccountType::VendingMachineBuffer as u8) {
+        load_buffer::<VendingMachineBuffer>(&buffer_data)?
+            .0
+            .content_hash
+    } else if account_type == Some(AccountType::LogBuffer as u8) {
+        load_buffer::<LogBuffer>(&buffer_data)?.0.content_hash
+    } else if account_type == Some(AccountType::TokenGatedBuffer as u8) {
+        load_buffer::<TokenGatedBuffer>(&buffer_data)?
+            .0
+            .content_hash
+    } else {
+        return Err(EchoError::InvalidAccountType.into());
+    };
+
+    // The recorded hash is compared, so the check costs the same for any buffer size
+    assert_with_msg(
+        content_hash == expected_hash.to_bytes(),
+        EchoError::ContentHashMismatch.into(),
+        &format!(
+            "Buffer content hash is {}.",
+            Hash::new_from_array(content_hash)
+        ),
+    )
+}
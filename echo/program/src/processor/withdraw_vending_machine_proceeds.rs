@@ -0,0 +1,61 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::pda::VENDING_MACHINE_SEED;
+use crate::state::VendingMachineBuffer;
+use crate::utils::*;
+
+pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], amount: u64) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let vm_buffer_info = next_account_info(accounts_iter)?;
+    let vm_mint_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+    let treasury_info = next_account_info(accounts_iter)?;
+    let destination_info = next_account_info(accounts_iter)?;
+    let token_program_info = next_account_info(accounts_iter)?;
+
+    assert_is_signer(owner_info)?;
+    assert_is_writable(treasury_info)?;
+    assert_is_writable(destination_info)?;
+    assert_is_owned_by(vm_buffer_info, program_id)?;
+
+    // Copy the header out, the buffer signs the transfer below
+    let vm_buffer_struct =
+        *load_buffer::<VendingMachineBuffer>(&vm_buffer_info.try_borrow_data()?)?.0;
+    assert_vending_machine_address(program_id, vm_buffer_info, &vm_buffer_struct, vm_mint_info)?;
+    assert_vending_machine_owner(&vm_buffer_struct, owner_info)?;
+    assert_treasury(&vm_buffer_struct, treasury_info)?;
+
+    // Tokens paid for unspent credits stay in the treasury while they can still be refunded
+    if vm_buffer_struct.refund_credits != 0 {
+        let available = treasury_balance(treasury_info)?
+            .saturating_sub(vm_buffer_struct.outstanding_credits.into());
+        assert_with_msg(
+            amount <= available,
+            EchoError::ProceedsReservedForCredits.into(),
+            &format!(
+                "Only {} tokens can be withdrawn, the rest is held back for refundable credits.",
+                available
+            ),
+        )?;
+    }
+
+    // The treasury is owned by the machine PDA, so the program signs for it
+    transfer_tokens(
+        treasury_info,
+        destination_info,
+        vm_buffer_info,
+        token_program_info,
+        amount,
+        &[&[
+            VENDING_MACHINE_SEED,
+            vm_mint_info.key.as_ref(),
+            &u64::from(vm_buffer_struct.machine_id).to_le_bytes(),
+            &[vm_buffer_struct.bump_seed],
+        ]],
+    )
+}
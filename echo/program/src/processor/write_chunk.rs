@@ -0,0 +1,76 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+    pubkey::Pubkey,
+};
+
+use crate::error::EchoError;
+use crate::state::StagingBuffer;
+use crate::utils::*;
+
+pub fn process(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    index: u64,
+    bytes: &[u8],
+) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let staging_info = next_account_info(accounts_iter)?;
+    let owner_info = next_account_info(accounts_iter)?;
+    let system_program_info = next_account_info(accounts_iter)?;
+
+    // Validate account inputs
+    assert_is_writable(staging_info)?;
+    assert_is_writable(owner_info)?;
+    assert_is_signer(owner_info)?;
+    assert_is_system_program(system_program_info)?;
+    assert_is_owned_by(staging_info, program_id)?;
+
+    let (offset, end, staged_len) = {
+        let staging_data = staging_info.try_borrow_data()?;
+        let (staging_struct, staged_data) = load_buffer::<StagingBuffer>(&staging_data)?;
+        assert_staging_buffer(program_id, staging_info, staging_struct, owner_info)?;
+
+        let chunk_size = u64::from(staging_struct.chunk_size);
+        assert_with_msg(
+            bytes.len() as u64 <= chunk_size,
+            EchoError::ChunkTooLarge.into(),
+            &format!("Chunks can be at most {} bytes.", chunk_size),
+        )?;
+        let offset = index
+            .checked_mul(chunk_size)
+            .ok_or(EchoError::WriteOutOfBounds)?;
+        let end = offset
+            .checked_add(bytes.len() as u64)
+            .ok_or(EchoError::WriteOutOfBounds)?;
+        let len = u64::from(staging_struct.len);
+        assert_with_msg(
+            end <= len,
+            EchoError::WriteOutOfBounds.into(),
+            &format!(
+                "Cannot write {} bytes at offset {} of a {} byte upload.",
+                bytes.len(),
+                offset,
+                len
+            ),
+        )?;
+        (offset, end as usize, staged_data.len())
+    };
+
+    // The staging buffer is created empty and grows with the chunks, the owner pays for the growth
+    if end > staged_len {
+        assert_with_msg(
+            end - staged_len <= MAX_PERMITTED_DATA_INCREASE,
+            EchoError::BufferGrowthTooLarge.into(),
+            "Staging buffer can grow by at most 10KiB per instruction, send the earlier chunks first.",
+        )?;
+        let new_size = StagingBuffer::HEADER_SIZE + end;
+        rebalance_rent(staging_info, owner_info, system_program_info, new_size)?;
+        staging_info.realloc(new_size, true)?;
+    }
+
+    // Chunks can arrive in any order and be written again, only the hash at commit matters
+    let mut staging_data = staging_info.try_borrow_mut_data()?;
+    let (_, staged_data) = load_buffer_mut::<StagingBuffer>(&mut staging_data)?;
+    write_at(staged_data, offset, bytes)
+}
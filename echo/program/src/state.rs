@@ -1,15 +1,921 @@
+use std::mem::size_of;
+
 use borsh::{BorshDeserialize, BorshSerialize};
+use bytemuck::{Pod, Zeroable};
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+use solana_program::{clock::Clock, hash::hash, pubkey::Pubkey};
 
-#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+use crate::error::EchoError;
+
+/// Layout version written by this program. Accounts with any other version must be migrated before use.
+///
+/// Version 1 stored buffers as Borsh structs with a `Vec<u8>` of echo data, version 2 stores a fixed header that is
+/// read in place followed by the raw echo data.
+pub const CURRENT_VERSION: u8 = 2;
+
+/// Kind of echo account, stored in the first byte of every account the program initializes.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AccountType {
+    Uninitialized,
+    AuthorizedBuffer,
+    VendingMachineBuffer,
+    VendingMachineCredits,
+    BufferWriter,
+    StagingBuffer,
+    LogBuffer,
+    Mailbox,
+    TokenGatedBuffer,
+}
+
+/// Discriminator and layout version at the start of every account the program initializes.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct AccountHeader {
+    pub account_type: u8,
+    pub version: u8,
+}
+
+impl AccountHeader {
+    pub const LEN: usize = size_of::<Self>();
+
+    pub fn new(account_type: AccountType) -> Self {
+        Self {
+            account_type: account_type as u8,
+            version: CURRENT_VERSION,
+        }
+    }
+}
+
+/// Little-endian `u64` with an alignment of 1, so headers can be read in place from any account data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct PodU64([u8; 8]);
+
+impl From<u64> for PodU64 {
+    fn from(value: u64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodU64> for u64 {
+    fn from(value: PodU64) -> Self {
+        u64::from_le_bytes(value.0)
+    }
+}
+
+/// Little-endian `i64` with an alignment of 1, see `PodU64`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Pod, Zeroable)]
+#[repr(transparent)]
+pub struct PodI64([u8; 8]);
+
+impl From<i64> for PodI64 {
+    fn from(value: i64) -> Self {
+        Self(value.to_le_bytes())
+    }
+}
+
+impl From<PodI64> for i64 {
+    fn from(value: PodI64) -> Self {
+        i64::from_le_bytes(value.0)
+    }
+}
+
+/// Implemented by the fixed headers that start with an `AccountHeader`. The rest of the account after the header is
+/// the raw echo data.
+pub trait EchoAccount: Pod {
+    const ACCOUNT_TYPE: AccountType;
+}
+
+/// Header of an `authorized_buffer`, followed by the echo data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
 pub struct AuthorizedBuffer {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    pub buffer_seed: PodU64,
+    /// Authority the buffer address was derived from. It never changes, so the buffer keeps its address when the
+    /// authority is transferred.
+    pub seed_authority: Pubkey,
+    /// Pubkey with sole write access to the buffer.
+    pub authority: Pubkey,
+    /// Pubkey proposed by `authority` to take over the buffer, or `Pubkey::default()` if there is none.
+    pub pending_authority: Pubkey,
+    /// Non-zero once `SealBuffer` made the buffer permanently read-only.
+    pub sealed: u8,
+    /// `ExpiryKind` of the buffer, `ExpiryKind::None` if it never expires.
+    pub expiry_kind: u8,
+    /// Last unix timestamp or slot at which the buffer accepts writes, depending on `expiry_kind`.
+    pub expires_at: PodU64,
+    /// Nonce the next `AuthorizedEchoSigned` must be signed with, so a signed write can only be submitted once.
+    pub nonce: PodU64,
+    /// SHA-256 hash of the echo data, updated by every instruction that changes it. Zero for buffers that were not
+    /// written since hashes were added.
+    pub content_hash: [u8; 32],
+    /// Number of leaves of the Merkle tree whose root starts the echo data, set by `SetMerkleRoot`. Zero outside of
+    /// Merkle mode.
+    pub merkle_leaf_count: PodU64,
+    /// `ContentType` of the echo data, `ContentType::Bytes` unless set at initialization.
+    pub content_type: u8,
+    /// Schema of the echo data chosen by the initializer, or zero if none.
+    pub schema_id: PodU64,
+    /// Zeroed space for later header fields, so they can be added without moving the echo data.
+    pub reserved: [u8; 61],
+}
+
+impl AuthorizedBuffer {
+    /// Number of bytes in front of the echo data.
+    pub const HEADER_SIZE: usize = size_of::<Self>();
+
+    pub fn content_header(&self) -> Option<ContentHeader> {
+        ContentHeader::from_fields(self.content_type, self.schema_id)
+    }
+
+    pub fn set_content_header(&mut self, content: Option<ContentHeader>) {
+        let (content_type, schema_id) = ContentHeader::to_fields(content);
+        self.content_type = content_type;
+        self.schema_id = schema_id;
+    }
+
+    /// Decodes `echo_data` according to the content type of the buffer.
+    pub fn content<'a>(&self, echo_data: &'a [u8]) -> Result<EchoContent<'a>, EchoError> {
+        EchoContent::decode(self.content_header(), echo_data)
+    }
+
+    /// Records the hash of `echo_data` after a change to it.
+    pub fn update_content_hash(&mut self, echo_data: &[u8]) {
+        self.content_hash = hash(echo_data).to_bytes();
+    }
+
+    pub fn expiry(&self) -> Option<Expiry> {
+        match ExpiryKind::from_u8(self.expiry_kind)? {
+            ExpiryKind::None => None,
+            ExpiryKind::UnixTimestamp => {
+                Some(Expiry::UnixTimestamp(u64::from(self.expires_at) as i64))
+            }
+            ExpiryKind::Slot => Some(Expiry::Slot(self.expires_at.into())),
+        }
+    }
+
+    pub fn set_expiry(&mut self, expiry: Option<Expiry>) {
+        let (expiry_kind, expires_at) = match expiry {
+            None => (ExpiryKind::None, 0),
+            Some(Expiry::UnixTimestamp(unix_timestamp)) => {
+                (ExpiryKind::UnixTimestamp, unix_timestamp as u64)
+            }
+            Some(Expiry::Slot(slot)) => (ExpiryKind::Slot, slot),
+        };
+        self.expiry_kind = expiry_kind as u8;
+        self.expires_at = expires_at.into();
+    }
+}
+
+impl EchoAccount for AuthorizedBuffer {
+    const ACCOUNT_TYPE: AccountType = AccountType::AuthorizedBuffer;
+}
+
+/// When an `authorized_buffer` stops accepting writes, chosen at `InitializeAuthorizedEcho`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// Writes are rejected once the cluster unix timestamp is past this value.
+    UnixTimestamp(i64),
+    /// Writes are rejected once the slot is past this value.
+    Slot(u64),
+}
+
+impl Expiry {
+    pub fn is_expired(&self, clock: &Clock) -> bool {
+        match *self {
+            Expiry::UnixTimestamp(unix_timestamp) => clock.unix_timestamp > unix_timestamp,
+            Expiry::Slot(slot) => clock.slot > slot,
+        }
+    }
+}
+
+/// How the `expires_at` of an `AuthorizedBuffer` is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum ExpiryKind {
+    None,
+    UnixTimestamp,
+    Slot,
+}
+
+/// How a vending machine collects its `price`, chosen at `InitializeVendingMachineEcho`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum PaymentMode {
+    /// The tokens are burned.
+    Burn,
+    /// The tokens are transferred to the treasury token account of the machine, where the owner can withdraw them.
+    Treasury,
+}
+
+/// What the echo data of a buffer holds, chosen at initialization so consumers do not have to guess.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[repr(u8)]
+pub enum ContentType {
+    /// Untyped bytes, the content type of buffers initialized without a `ContentHeader`.
+    Bytes,
+    /// UTF-8 text, checked on-chain by every write. Trailing zero bytes are padding.
+    Utf8,
+    /// Borsh-encoded value, of the type the schema id stands for.
+    Borsh,
+    /// JSON document. Trailing zero bytes are padding.
+    Json,
+}
+
+/// Content type and schema of the echo data of a buffer, set at initialization.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContentHeader {
+    pub content_type: ContentType,
+    /// Identifier of the schema of the data, agreed on off-chain, or zero if none.
+    pub schema_id: u64,
+}
+
+impl ContentHeader {
+    /// Reads the header fields of a buffer, `None` for untyped buffers without a schema.
+    fn from_fields(content_type: u8, schema_id: PodU64) -> Option<Self> {
+        let header = Self {
+            content_type: ContentType::from_u8(content_type)?,
+            schema_id: schema_id.into(),
+        };
+        (header.content_type != ContentType::Bytes || header.schema_id != 0).then(|| header)
+    }
+
+    fn to_fields(content: Option<Self>) -> (u8, PodU64) {
+        content.map_or((ContentType::Bytes as u8, 0.into()), |header| {
+            (header.content_type as u8, header.schema_id.into())
+        })
+    }
+}
+
+/// Echo data decoded according to the `ContentHeader` of its buffer, see `AuthorizedBuffer::content`,
+/// `VendingMachineBuffer::content` and `TokenGatedBuffer::content`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EchoContent<'a> {
+    Bytes(&'a [u8]),
+    Text(&'a str),
+    Borsh { schema_id: u64, data: &'a [u8] },
+    Json { schema_id: u64, text: &'a str },
+}
+
+impl<'a> EchoContent<'a> {
+    fn decode(header: Option<ContentHeader>, echo_data: &'a [u8]) -> Result<Self, EchoError> {
+        let header = match header {
+            Some(header) => header,
+            None => return Ok(EchoContent::Bytes(echo_data)),
+        };
+        let text = || {
+            let end = echo_data.iter().rposition(|&b| b != 0).map_or(0, |i| i + 1);
+            std::str::from_utf8(&echo_data[..end]).map_err(|_| EchoError::InvalidUtf8)
+        };
+        Ok(match header.content_type {
+            ContentType::Bytes => EchoContent::Bytes(echo_data),
+            ContentType::Utf8 => EchoContent::Text(text()?),
+            ContentType::Borsh => EchoContent::Borsh {
+                schema_id: header.schema_id,
+                data: echo_data,
+            },
+            ContentType::Json => EchoContent::Json {
+                schema_id: header.schema_id,
+                text: text()?,
+            },
+        })
+    }
+
+    /// Deserializes Borsh content as a `T`, ignoring the zero padding after it. The caller picks `T` from the
+    /// `schema_id`.
+    pub fn deserialize<T: BorshDeserialize>(&self) -> Result<T, EchoError> {
+        match self {
+            EchoContent::Borsh { data, .. } => {
+                T::deserialize(&mut &data[..]).map_err(|_| EchoError::InvalidBufferData)
+            }
+            _ => Err(EchoError::UnexpectedContentType),
+        }
+    }
+}
+
+/// What a vending machine charges for a write: a `base` fee plus `per_byte` for every byte written.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Pricing {
+    pub base: u64,
+    pub per_byte: u64,
+}
+
+impl Pricing {
+    /// Number of tokens a write of `bytes_written` bytes costs, or `None` if it does not fit in a `u64`.
+    ///
+    /// `VendingMachineEchoAt` writes `data.len()` bytes, `VendingMachineEcho` always fills the whole echo data.
+    /// Clients can price a write before sending it with the header read by `load_buffer`.
+    pub fn charge(&self, bytes_written: usize) -> Option<u64> {
+        u64::try_from(bytes_written)
+            .ok()?
+            .checked_mul(self.per_byte)?
+            .checked_add(self.base)
+    }
+}
+
+/// Allow-list entry that lets `writer` write to `authorized_buffer` like its authority, created by `AddBufferWriter`.
+///
+/// The entry is only honoured while `added_by` is still the authority of the buffer, so writers do not carry over
+/// to a new authority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct BufferWriter {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    pub authorized_buffer: Pubkey,
+    pub writer: Pubkey,
+    /// Authority that added the writer and paid its rent.
+    pub added_by: Pubkey,
+    /// Zeroed space for later fields.
+    pub reserved: [u8; 32],
+}
+
+impl BufferWriter {
+    pub const LEN: usize = size_of::<Self>();
+}
+
+impl EchoAccount for BufferWriter {
+    const ACCOUNT_TYPE: AccountType = AccountType::BufferWriter;
+}
+
+/// Upload to an `authorized_buffer` opened by `BeginWrite`, followed by the staged bytes.
+///
+/// `WriteChunk` grows the account up to the `len` staged bytes as it fills them, and `CommitWrite` copies them to the
+/// buffer in one instruction, so the buffer never holds a partial upload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct StagingBuffer {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    pub authorized_buffer: Pubkey,
+    /// Authority or writer that opened the upload, paid its rent and alone writes the chunks.
+    pub owner: Pubkey,
+    /// Length of the upload, which replaces the whole echo data. Bytes past it are zeroed on commit.
+    pub len: PodU64,
+    /// Bytes per chunk, chunk `index` starts at byte `index * chunk_size` of the upload.
+    pub chunk_size: PodU64,
+    /// SHA-256 hash the staged bytes must have to be committed.
+    pub hash: [u8; 32],
+    /// Zeroed space for later header fields, so they can be added without moving the staged data.
+    pub reserved: [u8; 32],
+}
+
+impl StagingBuffer {
+    /// Number of bytes in front of the staged data.
+    pub const HEADER_SIZE: usize = size_of::<Self>();
+}
+
+impl EchoAccount for StagingBuffer {
+    const ACCOUNT_TYPE: AccountType = AccountType::StagingBuffer;
+}
+
+/// Size of the little-endian `u32` length in front of every record of a `LogBuffer`.
+pub const LOG_RECORD_PREFIX_SIZE: usize = 4;
+
+/// Header of a `log_buffer`, followed by a ring of length-prefixed records.
+///
+/// Records run from `head` to `tail` and wrap around the end of the ring, a record can be split across it. When the
+/// ring is full, `AppendEcho` drops the oldest records to make room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct LogBuffer {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    pub buffer_seed: PodU64,
+    /// Key that created the buffer and alone appends to it.
+    pub authority: Pubkey,
+    /// Offset in the ring of the oldest record.
+    pub head: PodU64,
+    /// Offset in the ring where the next record is written.
+    pub tail: PodU64,
+    /// Number of records in the ring, which tells a full ring from an empty one when `head == tail`.
+    pub records: PodU64,
+    /// Number of records ever appended, so readers can tell how many were dropped.
+    pub appended: PodU64,
+    /// SHA-256 hash of the whole ring, updated by every append.
+    pub content_hash: [u8; 32],
+    /// Zeroed space for later header fields, so they can be added without moving the ring.
+    pub reserved: [u8; 32],
+}
+
+impl LogBuffer {
+    /// Number of bytes in front of the ring.
+    pub const HEADER_SIZE: usize = size_of::<Self>();
+
+    /// Records the hash of `log_data` after a change to it.
+    pub fn update_content_hash(&mut self, log_data: &[u8]) {
+        self.content_hash = hash(log_data).to_bytes();
+    }
+
+    /// Appends `record` to the ring in `log_data`, dropping the oldest records until it fits.
+    pub fn append(&mut self, log_data: &mut [u8], record: &[u8]) -> Result<(), EchoError> {
+        let capacity = log_data.len();
+        let record_size = LOG_RECORD_PREFIX_SIZE + record.len();
+        let record_len = u32::try_from(record.len())
+            .ok()
+            .filter(|_| record_size <= capacity)
+            .ok_or(EchoError::RecordTooLarge)?;
+
+        let mut head = u64::from(self.head) as usize;
+        let mut records = u64::from(self.records);
+        let mut used = self.used_len(capacity);
+        while capacity - used < record_size {
+            let dropped = LOG_RECORD_PREFIX_SIZE + read_record_len(log_data, head);
+            head = (head + dropped) % capacity;
+            used -= dropped;
+            records -= 1;
+        }
+
+        let tail = (head + used) % capacity;
+        copy_to_ring(log_data, tail, &record_len.to_le_bytes());
+        copy_to_ring(log_data, (tail + LOG_RECORD_PREFIX_SIZE) % capacity, record);
+        self.head = (head as u64).into();
+        self.tail = (((tail + record_size) % capacity) as u64).into();
+        self.records = (records + 1).into();
+        self.appended = (u64::from(self.appended) + 1).into();
+        Ok(())
+    }
+
+    /// Iterates over the records in the ring in `log_data`, oldest first.
+    pub fn records<'a>(&self, log_data: &'a [u8]) -> LogRecords<'a> {
+        LogRecords {
+            log_data,
+            offset: u64::from(self.head) as usize,
+            remaining: u64::from(self.records),
+        }
+    }
+
+    /// Bytes of the ring taken by records, prefixes included.
+    fn used_len(&self, capacity: usize) -> usize {
+        let head = u64::from(self.head) as usize;
+        let tail = u64::from(self.tail) as usize;
+        match u64::from(self.records) {
+            0 => 0,
+            _ if tail > head => tail - head,
+            _ => capacity - head + tail,
+        }
+    }
+}
+
+impl EchoAccount for LogBuffer {
+    const ACCOUNT_TYPE: AccountType = AccountType::LogBuffer;
+}
+
+/// Size of the sender, postage and data length in front of the data of every `Message`.
+pub const MESSAGE_HEADER_SIZE: usize = 44;
+
+/// Header of the `mailbox` of a recipient, followed by its queue of Borsh-encoded `Message`s, oldest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct Mailbox {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    /// Wallet the mailbox belongs to, the only one that can pop or clear messages and close the mailbox.
+    pub recipient: Pubkey,
+    /// Token account that receives postage, or `Pubkey::default()` if the mailbox takes no postage. It is a token
+    /// account of the mint of a vending machine.
+    pub postage_account: Pubkey,
+    /// Postage every message must pay, in tokens of the mint of `postage_account`.
+    pub min_postage: PodU64,
+    /// Bytes of the queue taken by messages.
+    pub len: PodU64,
+    /// Number of messages in the queue.
+    pub messages: PodU64,
+    /// Zeroed space for later header fields, so they can be added without moving the queue.
+    pub reserved: [u8; 32],
+}
+
+impl Mailbox {
+    /// Number of bytes in front of the queue.
+    pub const HEADER_SIZE: usize = size_of::<Self>();
+
+    /// Iterates over the messages in the queue in `mailbox_data`, oldest first.
+    pub fn messages<'a>(&self, mailbox_data: &'a [u8]) -> MailboxMessages<'a> {
+        let len = (u64::from(self.len) as usize).min(mailbox_data.len());
+        MailboxMessages {
+            queue: &mailbox_data[..len],
+            remaining: u64::from(self.messages),
+        }
+    }
+}
+
+impl EchoAccount for Mailbox {
+    const ACCOUNT_TYPE: AccountType = AccountType::Mailbox;
+}
+
+/// Header of a `token_gated_buffer`, followed by the echo data.
+///
+/// Whoever holds at least `min_balance` tokens of `mint` can write, so control moves with the tokens. With a supply-1
+/// mint and a `min_balance` of 1, that is the holder of the NFT.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct TokenGatedBuffer {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    pub buffer_seed: PodU64,
+    /// Key that created the buffer, part of its address. It cannot write to the buffer, only close it with
+    /// `CloseTokenGatedEcho`.
+    pub creator: Pubkey,
+    /// Mint whose holders can write to the buffer.
+    pub mint: Pubkey,
+    /// Tokens of `mint` a writer must hold. Never zero.
+    pub min_balance: PodU64,
+    /// SHA-256 hash of the echo data, updated by every instruction that changes it.
+    pub content_hash: [u8; 32],
+    /// `ContentType` of the echo data, `ContentType::Bytes` unless set at initialization.
+    pub content_type: u8,
+    /// Schema of the echo data chosen by the initializer, or zero if none.
+    pub schema_id: PodU64,
+    /// Zeroed space for later header fields, so they can be added without moving the echo data.
+    pub reserved: [u8; 23],
+}
+
+impl TokenGatedBuffer {
+    /// Number of bytes in front of the echo data.
+    pub const HEADER_SIZE: usize = size_of::<Self>();
+
+    pub fn content_header(&self) -> Option<ContentHeader> {
+        ContentHeader::from_fields(self.content_type, self.schema_id)
+    }
+
+    pub fn set_content_header(&mut self, content: Option<ContentHeader>) {
+        let (content_type, schema_id) = ContentHeader::to_fields(content);
+        self.content_type = content_type;
+        self.schema_id = schema_id;
+    }
+
+    /// Decodes `echo_data` according to the content type of the buffer.
+    pub fn content<'a>(&self, echo_data: &'a [u8]) -> Result<EchoContent<'a>, EchoError> {
+        EchoContent::decode(self.content_header(), echo_data)
+    }
+
+    /// Records the hash of `echo_data` after a change to it.
+    pub fn update_content_hash(&mut self, echo_data: &[u8]) {
+        self.content_hash = hash(echo_data).to_bytes();
+    }
+}
+
+impl EchoAccount for TokenGatedBuffer {
+    const ACCOUNT_TYPE: AccountType = AccountType::TokenGatedBuffer;
+}
+
+/// Message sent to a `Mailbox` with `SendMessage`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub sender: Pubkey,
+    /// Tokens the sender paid to the `postage_account` of the mailbox.
+    pub postage: u64,
+    pub data: Vec<u8>,
+}
+
+/// Iterator over the messages of a `Mailbox`, see `Mailbox::messages`. Stops early at a message that does not decode.
+#[derive(Debug, Clone)]
+pub struct MailboxMessages<'a> {
+    queue: &'a [u8],
+    remaining: u64,
+}
+
+impl Iterator for MailboxMessages<'_> {
+    type Item = Message;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let message = Message::deserialize(&mut self.queue).ok();
+        if message.is_none() {
+            self.remaining = 0;
+        }
+        message
+    }
+}
+
+/// Iterator over the records of a `LogBuffer`, see `LogBuffer::records`.
+///
+/// Records split across the end of the ring are stitched back together. The iterator stops early at a length that
+/// does not fit in the ring, so it is safe to run on any account data.
+#[derive(Debug, Clone)]
+pub struct LogRecords<'a> {
+    log_data: &'a [u8],
+    offset: usize,
+    remaining: u64,
+}
+
+impl Iterator for LogRecords<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let capacity = self.log_data.len();
+        if self.remaining == 0 || self.offset >= capacity || capacity < LOG_RECORD_PREFIX_SIZE {
+            return None;
+        }
+        let record_len = read_record_len(self.log_data, self.offset);
+        if LOG_RECORD_PREFIX_SIZE + record_len > capacity {
+            self.remaining = 0;
+            return None;
+        }
+
+        let mut record = vec![0; record_len];
+        copy_from_ring(
+            self.log_data,
+            (self.offset + LOG_RECORD_PREFIX_SIZE) % capacity,
+            &mut record,
+        );
+        self.offset = (self.offset + LOG_RECORD_PREFIX_SIZE + record_len) % capacity;
+        self.remaining -= 1;
+        Some(record)
+    }
+}
+
+/// Reads the length prefix of the record at `offset` of the ring.
+fn read_record_len(ring: &[u8], offset: usize) -> usize {
+    let mut prefix = [0; LOG_RECORD_PREFIX_SIZE];
+    copy_from_ring(ring, offset, &mut prefix);
+    u32::from_le_bytes(prefix) as usize
+}
+
+/// Copies `bytes` into the ring at `offset`, wrapping around its end. `bytes` must fit in the ring.
+fn copy_to_ring(ring: &mut [u8], offset: usize, bytes: &[u8]) {
+    let (first, second) = bytes.split_at(bytes.len().min(ring.len() - offset));
+    ring[offset..offset + first.len()].copy_from_slice(first);
+    ring[..second.len()].copy_from_slice(second);
+}
+
+/// Fills `bytes` from the ring at `offset`, wrapping around its end. `bytes` must fit in the ring.
+fn copy_from_ring(ring: &[u8], offset: usize, bytes: &mut [u8]) {
+    let split = bytes.len().min(ring.len() - offset);
+    let (first, second) = bytes.split_at_mut(split);
+    first.copy_from_slice(&ring[offset..offset + split]);
+    second.copy_from_slice(&ring[..second.len()]);
+}
+
+/// Header of a `vending_machine_buffer`, followed by the echo data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct VendingMachineBuffer {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    /// Base fee of every write, see `Pricing`.
+    pub price: PodU64,
+    /// Pubkey that may withdraw the proceeds of the machine, or `Pubkey::default()` for machines migrated from an
+    /// older layout.
+    pub owner: Pubkey,
+    /// `PaymentMode` of the machine.
+    pub payment_mode: u8,
+    /// Token account that collects payments in `PaymentMode::Treasury`, or `Pubkey::default()` otherwise.
+    pub treasury: Pubkey,
+    /// Seed of the buffer address next to the mint. Machines migrated from an older layout use their original
+    /// price, which was the seed back then.
+    pub machine_id: PodU64,
+    /// Fee for every byte written, see `Pricing`. Zero for machines migrated from an older layout.
+    pub price_per_byte: PodU64,
+    /// Seconds prepaid credits stay spendable after a purchase, or zero if they never expire.
+    pub credit_lifetime: PodU64,
+    /// Whether unspent credits are refunded from the treasury when a credits account is closed.
+    pub refund_credits: u8,
+    /// Credits bought and neither spent nor closed yet. While refunds are enabled, the treasury keeps this many
+    /// tokens back from withdrawals.
+    pub outstanding_credits: PodU64,
+    /// Non-zero once `SealBuffer` made the buffer permanently read-only.
+    pub sealed: u8,
+    /// `ContentType` of the echo data, `ContentType::Bytes` unless set at initialization.
+    pub content_type: u8,
+    /// Schema of the echo data chosen by the initializer, or zero if none.
+    pub schema_id: PodU64,
+    /// SHA-256 hash of the echo data, updated by every instruction that changes it.
+    pub content_hash: [u8; 32],
+    /// Zeroed space for later header fields, so they can be added without moving the echo data.
+    pub reserved: [u8; 20],
+}
+
+impl VendingMachineBuffer {
+    /// Number of bytes in front of the echo data.
+    pub const HEADER_SIZE: usize = size_of::<Self>();
+
+    pub fn payment_mode(&self) -> Option<PaymentMode> {
+        PaymentMode::from_u8(self.payment_mode)
+    }
+
+    pub fn content_header(&self) -> Option<ContentHeader> {
+        ContentHeader::from_fields(self.content_type, self.schema_id)
+    }
+
+    pub fn set_content_header(&mut self, content: Option<ContentHeader>) {
+        let (content_type, schema_id) = ContentHeader::to_fields(content);
+        self.content_type = content_type;
+        self.schema_id = schema_id;
+    }
+
+    /// Decodes `echo_data` according to the content type of the buffer.
+    pub fn content<'a>(&self, echo_data: &'a [u8]) -> Result<EchoContent<'a>, EchoError> {
+        EchoContent::decode(self.content_header(), echo_data)
+    }
+
+    /// Records the hash of `echo_data` after a change to it.
+    pub fn update_content_hash(&mut self, echo_data: &[u8]) {
+        self.content_hash = hash(echo_data).to_bytes();
+    }
+
+    pub fn pricing(&self) -> Pricing {
+        Pricing {
+            base: self.price.into(),
+            per_byte: self.price_per_byte.into(),
+        }
+    }
+}
+
+impl EchoAccount for VendingMachineBuffer {
+    const ACCOUNT_TYPE: AccountType = AccountType::VendingMachineBuffer;
+}
+
+/// Prepaid credits of one `user` at one vending machine, bought with `BuyVendingMachineCredits`.
+///
+/// A credit is worth one token, writes spend their `Pricing::charge` in credits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+#[repr(C)]
+pub struct VendingMachineCredits {
+    pub header: AccountHeader,
+    pub bump_seed: u8,
+    pub vending_machine: Pubkey,
+    pub user: Pubkey,
+    pub credits: PodU64,
+    /// Unix timestamp after which the credits can no longer be spent or refunded, or zero if they never expire.
+    pub expires_at: PodI64,
+    /// Zeroed space for later fields.
+    pub reserved: [u8; 32],
+}
+
+impl VendingMachineCredits {
+    pub const LEN: usize = size_of::<Self>();
+
+    pub fn is_expired(&self, unix_timestamp: i64) -> bool {
+        let expires_at = i64::from(self.expires_at);
+        expires_at != 0 && unix_timestamp > expires_at
+    }
+}
+
+impl EchoAccount for VendingMachineCredits {
+    const ACCOUNT_TYPE: AccountType = AccountType::VendingMachineCredits;
+}
+
+/// Borsh `AuthorizedBuffer` layout of version 1. Only read by `MigrateAuthorizedEcho`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct AuthorizedBufferV1 {
+    pub header: AccountHeader,
     pub bump_seed: u8,
     pub buffer_seed: u64,
+    pub seed_authority: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
     pub data: Vec<u8>,
 }
 
+/// Message the authority signs off-chain to allow `AuthorizedEchoSigned` to write `data` to `authorized_buffer`:
+/// the buffer address, then `nonce` in little-endian, then `data`.
+pub fn signed_echo_message(authorized_buffer: &Pubkey, nonce: u64, data: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(40 + data.len());
+    message.extend_from_slice(authorized_buffer.as_ref());
+    message.extend_from_slice(&nonce.to_le_bytes());
+    message.extend_from_slice(data);
+    message
+}
+
+impl From<&AuthorizedBufferV1> for AuthorizedBuffer {
+    fn from(v1: &AuthorizedBufferV1) -> Self {
+        Self {
+            header: AccountHeader::new(AccountType::AuthorizedBuffer),
+            bump_seed: v1.bump_seed,
+            buffer_seed: v1.buffer_seed.into(),
+            seed_authority: v1.seed_authority,
+            authority: v1.authority,
+            pending_authority: v1.pending_authority,
+            sealed: 0,
+            expiry_kind: ExpiryKind::None as u8,
+            expires_at: 0.into(),
+            nonce: 0.into(),
+            content_hash: hash(&v1.data).to_bytes(),
+            merkle_leaf_count: 0.into(),
+            content_type: ContentType::Bytes as u8,
+            schema_id: 0.into(),
+            reserved: [0; 61],
+        }
+    }
+}
+
+/// Borsh `VendingMachineBuffer` layout of version 1. Only read by `MigrateVendingMachineEcho`.
 #[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
-pub struct VendingMachineBuffer {
+pub struct VendingMachineBufferV1 {
+    pub header: AccountHeader,
     pub bump_seed: u8,
     pub price: u64,
     pub data: Vec<u8>,
 }
+
+impl From<&VendingMachineBufferV1> for VendingMachineBuffer {
+    fn from(v1: &VendingMachineBufferV1) -> Self {
+        Self {
+            header: AccountHeader::new(AccountType::VendingMachineBuffer),
+            bump_seed: v1.bump_seed,
+            price: v1.price.into(),
+            owner: Pubkey::default(),
+            payment_mode: PaymentMode::Burn as u8,
+            treasury: Pubkey::default(),
+            machine_id: v1.price.into(),
+            price_per_byte: 0.into(),
+            credit_lifetime: 0.into(),
+            refund_credits: 0,
+            outstanding_credits: 0.into(),
+            sealed: 0,
+            content_type: ContentType::Bytes as u8,
+            schema_id: 0.into(),
+            content_hash: hash(&v1.data).to_bytes(),
+            reserved: [0; 20],
+        }
+    }
+}
+
+/// `AuthorizedBuffer` layout of the first release of the program, before authorities were stored. Only read by
+/// `MigrateAuthorizedEcho`.
+///
+/// The authority is only known from the PDA seeds, so it must sign the migration for the address to be derived.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct OriginalAuthorizedBuffer {
+    pub bump_seed: u8,
+    pub buffer_seed: u64,
+    pub data: Vec<u8>,
+}
+
+impl OriginalAuthorizedBuffer {
+    /// Converts the buffer to the layout of version 1, with `authority` as the key its address was derived from.
+    pub fn into_v1(self, authority: Pubkey) -> AuthorizedBufferV1 {
+        AuthorizedBufferV1 {
+            header: AccountHeader {
+                account_type: AccountType::AuthorizedBuffer as u8,
+                version: 1,
+            },
+            bump_seed: self.bump_seed,
+            buffer_seed: self.buffer_seed,
+            seed_authority: authority,
+            authority,
+            pending_authority: Pubkey::default(),
+            data: self.data,
+        }
+    }
+}
+
+/// `AuthorizedBuffer` layout with stored authorities but without an `AccountHeader`. Only read by
+/// `MigrateAuthorizedEcho`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacyAuthorizedBuffer {
+    pub bump_seed: u8,
+    pub buffer_seed: u64,
+    pub seed_authority: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+    pub data: Vec<u8>,
+}
+
+impl From<LegacyAuthorizedBuffer> for AuthorizedBufferV1 {
+    fn from(legacy: LegacyAuthorizedBuffer) -> Self {
+        Self {
+            header: AccountHeader {
+                account_type: AccountType::AuthorizedBuffer as u8,
+                version: 1,
+            },
+            bump_seed: legacy.bump_seed,
+            buffer_seed: legacy.buffer_seed,
+            seed_authority: legacy.seed_authority,
+            authority: legacy.authority,
+            pending_authority: legacy.pending_authority,
+            data: legacy.data,
+        }
+    }
+}
+
+/// `VendingMachineBuffer` layout used before accounts had an `AccountHeader`. Only read by
+/// `MigrateVendingMachineEcho`.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone)]
+pub struct LegacyVendingMachineBuffer {
+    pub bump_seed: u8,
+    pub price: u64,
+    pub data: Vec<u8>,
+}
+
+impl From<LegacyVendingMachineBuffer> for VendingMachineBufferV1 {
+    fn from(legacy: LegacyVendingMachineBuffer) -> Self {
+        Self {
+            header: AccountHeader {
+                account_type: AccountType::VendingMachineBuffer as u8,
+                version: 1,
+            },
+            bump_seed: legacy.bump_seed,
+            price: legacy.price,
+            data: legacy.data,
+        }
+    }
+}
@@ -0,0 +1,905 @@
+use std::cmp::Ordering;
+use std::mem::size_of;
+
+use borsh::BorshDeserialize;
+use solana_program::{
+    account_info::AccountInfo,
+    ed25519_program,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    system_instruction, system_program,
+    sysvar::{
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        rent::Rent,
+        Sysvar,
+    },
+};
+use spl_token::instruction::{burn, close_account as close_token_account_instruction, transfer};
+
+use crate::error::EchoError;
+use crate::pda::{
+    create_authorized_buffer_address, create_buffer_writer_address, create_credits_address,
+    create_log_buffer_address, create_mailbox_address, create_staging_address,
+    create_token_gated_address, create_vending_machine_address,
+};
+use crate::state::{
+    AccountHeader, AccountType, AuthorizedBuffer, BufferWriter, ContentType, EchoAccount,
+    LogBuffer, Mailbox, PaymentMode, StagingBuffer, TokenGatedBuffer, VendingMachineBuffer,
+    VendingMachineCredits, CURRENT_VERSION,
+};
+
+pub fn assert_with_msg(statement: bool, err: ProgramError, msg: &str) -> ProgramResult {
+    if !statement {
+        msg!(msg);
+        Err(err)
+    } else {
+        Ok(())
+    }
+}
+
+pub fn assert_is_signer(account_info: &AccountInfo) -> ProgramResult {
+    assert_with_msg(
+        account_info.is_signer,
+        EchoError::MissingRequiredSignature.into(),
+        &format!("Missing signature for account {}.", account_info.key),
+    )
+}
+
+pub fn assert_is_writable(account_info: &AccountInfo) -> ProgramResult {
+    assert_with_msg(
+        account_info.is_writable,
+        EchoError::AccountMustBeWritable.into(),
+        &format!("Account {} must be writable.", account_info.key),
+    )
+}
+
+pub fn assert_is_system_program(account_info: &AccountInfo) -> ProgramResult {
+    assert_with_msg(
+        *account_info.key == system_program::id(),
+        EchoError::InvalidSystemProgram.into(),
+        &format!("Expected System Program, received: {}", account_info.key),
+    )
+}
+
+pub fn assert_is_token_program(account_info: &AccountInfo) -> ProgramResult {
+    assert_with_msg(
+        *account_info.key == spl_token::id(),
+        EchoError::InvalidTokenProgram.into(),
+        &format!("Expected Token Program, received: {}", account_info.key),
+    )
+}
+
+pub fn assert_is_initialized(account_info: &AccountInfo) -> ProgramResult {
+    assert_with_msg(
+        **account_info.lamports.borrow() > 0,
+        EchoError::AccountNotInitialized.into(),
+        &format!("Account {} is uninitialized.", account_info.key),
+    )
+}
+
+pub fn assert_is_owned_by(account_info: &AccountInfo, owner: &Pubkey) -> ProgramResult {
+    assert_with_msg(
+        account_info.owner == owner,
+        EchoError::InvalidAccountOwner.into(),
+        &format!("Account {} must be owned by {}.", account_info.key, owner),
+    )
+}
+
+/// Checks that `authorized_buffer_info` is the PDA recorded in `auth_buffer_struct`. The PDA is derived from
+/// `seed_authority`, which survives authority transfers.
+pub fn assert_authorized_buffer_address(
+    program_id: &Pubkey,
+    authorized_buffer_info: &AccountInfo,
+    auth_buffer_struct: &AuthorizedBuffer,
+) -> ProgramResult {
+    let authorized_buffer_key = create_authorized_buffer_address(
+        program_id,
+        &auth_buffer_struct.seed_authority,
+        auth_buffer_struct.buffer_seed.into(),
+        auth_buffer_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidBufferAddress)?;
+
+    // Confirm that the PDA address we found matches the one passed into the program
+    assert_with_msg(
+        authorized_buffer_key == *authorized_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid authorized_buffer address.",
+    )
+}
+
+/// Checks that `authorized_buffer_info` is the PDA recorded in `auth_buffer_struct` and that `authority_info` is the
+/// current authority of the buffer.
+pub fn assert_buffer_authority(
+    program_id: &Pubkey,
+    authorized_buffer_info: &AccountInfo,
+    auth_buffer_struct: &AuthorizedBuffer,
+    authority_info: &AccountInfo,
+) -> ProgramResult {
+    assert_authorized_buffer_address(program_id, authorized_buffer_info, auth_buffer_struct)?;
+    assert_with_msg(
+        auth_buffer_struct.authority == *authority_info.key,
+        EchoError::InvalidAuthority.into(),
+        &format!(
+            "Account {} is not the authority of the authorized_buffer.",
+            authority_info.key
+        ),
+    )
+}
+
+/// Checks that `writer_info` may write to the `authorized_buffer`: it is either the current authority, or
+/// `buffer_writer_info` is its allow-list entry added by the current authority.
+pub fn assert_buffer_writer(
+    program_id: &Pubkey,
+    authorized_buffer_info: &AccountInfo,
+    auth_buffer_struct: &AuthorizedBuffer,
+    writer_info: &AccountInfo,
+    buffer_writer_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    let buffer_writer_info = match buffer_writer_info {
+        Some(buffer_writer_info) if auth_buffer_struct.authority != *writer_info.key => {
+            buffer_writer_info
+        }
+        _ => {
+            return assert_buffer_authority(
+                program_id,
+                authorized_buffer_info,
+                auth_buffer_struct,
+                writer_info,
+            )
+        }
+    };
+    assert_authorized_buffer_address(program_id, authorized_buffer_info, auth_buffer_struct)?;
+    assert_is_owned_by(buffer_writer_info, program_id)?;
+
+    let buffer_writer_data = buffer_writer_info.try_borrow_data()?;
+    let (buffer_writer_struct, _) = load_buffer::<BufferWriter>(&buffer_writer_data)?;
+    let buffer_writer_key = create_buffer_writer_address(
+        program_id,
+        authorized_buffer_info.key,
+        writer_info.key,
+        buffer_writer_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidWriter)?;
+    assert_with_msg(
+        buffer_writer_key == *buffer_writer_info.key
+            && buffer_writer_struct.added_by == auth_buffer_struct.authority,
+        EchoError::InvalidWriter.into(),
+        &format!(
+            "Account {} is not a writer of the authorized_buffer.",
+            writer_info.key
+        ),
+    )
+}
+
+/// Checks that `staging_info` is the staging buffer PDA recorded in `staging_struct` and that `owner_info` opened it.
+pub fn assert_staging_buffer(
+    program_id: &Pubkey,
+    staging_info: &AccountInfo,
+    staging_struct: &StagingBuffer,
+    owner_info: &AccountInfo,
+) -> ProgramResult {
+    let staging_key = create_staging_address(
+        program_id,
+        &staging_struct.authorized_buffer,
+        owner_info.key,
+        staging_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidStagingBuffer)?;
+    assert_with_msg(
+        staging_key == *staging_info.key && staging_struct.owner == *owner_info.key,
+        EchoError::InvalidStagingBuffer.into(),
+        &format!(
+            "Account {} is not the staging buffer of {}.",
+            staging_info.key, owner_info.key
+        ),
+    )
+}
+
+/// Checks that `log_buffer_info` is the PDA recorded in `log_buffer_struct` and that `authority_info` created it.
+pub fn assert_log_buffer_authority(
+    program_id: &Pubkey,
+    log_buffer_info: &AccountInfo,
+    log_buffer_struct: &LogBuffer,
+    authority_info: &AccountInfo,
+) -> ProgramResult {
+    let log_buffer_key = create_log_buffer_address(
+        program_id,
+        &log_buffer_struct.authority,
+        log_buffer_struct.buffer_seed.into(),
+        log_buffer_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidBufferAddress)?;
+    assert_with_msg(
+        log_buffer_key == *log_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid log_buffer address.",
+    )?;
+    assert_with_msg(
+        log_buffer_struct.authority == *authority_info.key,
+        EchoError::InvalidAuthority.into(),
+        &format!(
+            "Account {} is not the authority of the log_buffer.",
+            authority_info.key
+        ),
+    )
+}
+
+/// Checks that `mailbox_info` is the PDA recorded in `mailbox_struct`.
+pub fn assert_mailbox_address(
+    program_id: &Pubkey,
+    mailbox_info: &AccountInfo,
+    mailbox_struct: &Mailbox,
+) -> ProgramResult {
+    let mailbox_key = create_mailbox_address(
+        program_id,
+        &mailbox_struct.recipient,
+        mailbox_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidMailbox)?;
+    assert_with_msg(
+        mailbox_key == *mailbox_info.key,
+        EchoError::InvalidMailbox.into(),
+        "Invalid mailbox address.",
+    )
+}
+
+/// Checks that `mailbox_info` is the PDA recorded in `mailbox_struct` and that `recipient_info` owns it.
+pub fn assert_mailbox_recipient(
+    program_id: &Pubkey,
+    mailbox_info: &AccountInfo,
+    mailbox_struct: &Mailbox,
+    recipient_info: &AccountInfo,
+) -> ProgramResult {
+    assert_mailbox_address(program_id, mailbox_info, mailbox_struct)?;
+    assert_with_msg(
+        mailbox_struct.recipient == *recipient_info.key,
+        EchoError::InvalidMailbox.into(),
+        &format!(
+            "Account {} is not the recipient of the mailbox.",
+            recipient_info.key
+        ),
+    )
+}
+
+/// Returns the key and message of the signature checked by the ed25519 program instruction right before the current
+/// instruction, read from the Instructions sysvar.
+///
+/// The transaction only lands if the ed25519 program accepted the signature, so the message is known to be signed by
+/// the key. The instruction must check a single signature whose key, signature and message are all in its own data,
+/// otherwise its offsets could point at bytes of other instructions.
+pub fn load_ed25519_signed_message(
+    instructions_info: &AccountInfo,
+) -> Result<(Pubkey, Vec<u8>), ProgramError> {
+    let current_index = load_current_index_checked(instructions_info)?;
+    let previous_index = current_index
+        .checked_sub(1)
+        .ok_or(EchoError::InvalidSignatureInstruction)?;
+    let instruction = load_instruction_at_checked(previous_index as usize, instructions_info)?;
+    assert_with_msg(
+        instruction.program_id == ed25519_program::id(),
+        EchoError::InvalidSignatureInstruction.into(),
+        "The instruction before must be an ed25519 program instruction.",
+    )?;
+
+    // Signature count and a padding byte, then the offsets as little-endian u16s: signature, signature instruction
+    // index, public key, public key instruction index, message, message size and message instruction index
+    let data = &instruction.data;
+    let mut offsets = [0; 7];
+    for (i, offset) in offsets.iter_mut().enumerate() {
+        let bytes = data
+            .get(2 + 2 * i..4 + 2 * i)
+            .ok_or(EchoError::InvalidSignatureInstruction)?;
+        *offset = u16::from_le_bytes([bytes[0], bytes[1]]);
+    }
+    let [_, signature_index, public_key_offset, public_key_index, message_offset, message_size, message_index] =
+        offsets;
+    assert_with_msg(
+        data[0] == 1 && [signature_index, public_key_index, message_index] == [u16::MAX; 3],
+        EchoError::InvalidSignatureInstruction.into(),
+        "The ed25519 instruction must check one signature with all its data inline.",
+    )?;
+    let (public_key_offset, message_offset) = (public_key_offset as usize, message_offset as usize);
+
+    let signer = data
+        .get(public_key_offset..public_key_offset + 32)
+        .map(Pubkey::new)
+        .ok_or(EchoError::InvalidSignatureInstruction)?;
+    let message = data
+        .get(message_offset..message_offset + message_size as usize)
+        .ok_or(EchoError::InvalidSignatureInstruction)?;
+    Ok((signer, message.to_vec()))
+}
+
+/// Checks that `token_gated_buffer_info` is the PDA recorded in `token_gated_buffer_struct`.
+pub fn assert_token_gated_address(
+    program_id: &Pubkey,
+    token_gated_buffer_info: &AccountInfo,
+    token_gated_buffer_struct: &TokenGatedBuffer,
+) -> ProgramResult {
+    let token_gated_buffer_key = create_token_gated_address(
+        program_id,
+        &token_gated_buffer_struct.mint,
+        &token_gated_buffer_struct.creator,
+        token_gated_buffer_struct.buffer_seed.into(),
+        token_gated_buffer_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidBufferAddress)?;
+    assert_with_msg(
+        token_gated_buffer_key == *token_gated_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid token_gated_buffer address.",
+    )
+}
+
+/// Checks that `token_gated_buffer_info` is the PDA recorded in `token_gated_buffer_struct` and that `creator_info`
+/// created it.
+pub fn assert_token_gated_creator(
+    program_id: &Pubkey,
+    token_gated_buffer_info: &AccountInfo,
+    token_gated_buffer_struct: &TokenGatedBuffer,
+    creator_info: &AccountInfo,
+) -> ProgramResult {
+    assert_token_gated_address(
+        program_id,
+        token_gated_buffer_info,
+        token_gated_buffer_struct,
+    )?;
+    assert_with_msg(
+        token_gated_buffer_struct.creator == *creator_info.key,
+        EchoError::InvalidAuthority.into(),
+        &format!(
+            "Account {} is not the creator of the token_gated_buffer.",
+            creator_info.key
+        ),
+    )
+}
+
+/// Checks that `token_gated_buffer_info` is the PDA recorded in `token_gated_buffer_struct`, and that
+/// `token_account_info` holds at least its `min_balance` of its mint for `holder_info`.
+pub fn assert_token_gate_holder(
+    program_id: &Pubkey,
+    token_gated_buffer_info: &AccountInfo,
+    token_gated_buffer_struct: &TokenGatedBuffer,
+    holder_info: &AccountInfo,
+    token_account_info: &AccountInfo,
+) -> ProgramResult {
+    assert_token_gated_address(
+        program_id,
+        token_gated_buffer_info,
+        token_gated_buffer_struct,
+    )?;
+
+    // Only the Token Program can vouch for the balance, a copy of its layout in another account proves nothing
+    assert_is_owned_by(token_account_info, &spl_token::id())?;
+    let token_account = spl_token::state::Account::unpack(&token_account_info.try_borrow_data()?)
+        .map_err(|_| EchoError::InvalidGateTokenAccount)?;
+    assert_with_msg(
+        token_account.mint == token_gated_buffer_struct.mint
+            && token_account.owner == *holder_info.key,
+        EchoError::InvalidGateTokenAccount.into(),
+        &format!(
+            "Token account {} is not a {} account of {}.",
+            token_account_info.key, token_gated_buffer_struct.mint, holder_info.key
+        ),
+    )?;
+    let min_balance = u64::from(token_gated_buffer_struct.min_balance);
+    assert_with_msg(
+        token_account.amount >= min_balance,
+        EchoError::InsufficientGateBalance.into(),
+        &format!(
+            "Token account holds {} tokens, {} are needed.",
+            token_account.amount, min_balance
+        ),
+    )
+}
+
+/// Checks that `vm_buffer_info` is the PDA of the vending machine for `vm_mint_info` recorded in `vm_buffer_struct`.
+pub fn assert_vending_machine_address(
+    program_id: &Pubkey,
+    vm_buffer_info: &AccountInfo,
+    vm_buffer_struct: &VendingMachineBuffer,
+    vm_mint_info: &AccountInfo,
+) -> ProgramResult {
+    let vm_buffer_key = create_vending_machine_address(
+        program_id,
+        vm_mint_info.key,
+        vm_buffer_struct.machine_id.into(),
+        vm_buffer_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidBufferAddress)?;
+
+    // Confirm that the PDA address we found matches the one passed into the program
+    assert_with_msg(
+        vm_buffer_key == *vm_buffer_info.key,
+        EchoError::InvalidBufferAddress.into(),
+        "Invalid vm_buffer address.",
+    )
+}
+
+/// Checks that `owner_info` is the recorded owner of the vending machine.
+pub fn assert_vending_machine_owner(
+    vm_buffer_struct: &VendingMachineBuffer,
+    owner_info: &AccountInfo,
+) -> ProgramResult {
+    assert_with_msg(
+        vm_buffer_struct.owner != Pubkey::default() && vm_buffer_struct.owner == *owner_info.key,
+        EchoError::InvalidOwner.into(),
+        &format!(
+            "Account {} is not the owner of the vending machine.",
+            owner_info.key
+        ),
+    )
+}
+
+/// Checks that the buffer was not sealed by `SealBuffer`, given the `sealed` flag of its header.
+pub fn assert_not_sealed(sealed: u8) -> ProgramResult {
+    assert_with_msg(
+        sealed == 0,
+        EchoError::BufferSealed.into(),
+        "Buffer is sealed and can no longer change.",
+    )
+}
+
+/// Whether the expiry of the `authorized_buffer` has passed, according to the Clock sysvar.
+pub fn is_buffer_expired(auth_buffer_struct: &AuthorizedBuffer) -> Result<bool, ProgramError> {
+    match auth_buffer_struct.expiry() {
+        Some(expiry) => Ok(expiry.is_expired(&Clock::get()?)),
+        None => Ok(false),
+    }
+}
+
+/// Checks that the `authorized_buffer` has no expiry, or that it has not passed yet.
+pub fn assert_not_expired(auth_buffer_struct: &AuthorizedBuffer) -> ProgramResult {
+    assert_with_msg(
+        !is_buffer_expired(auth_buffer_struct)?,
+        EchoError::BufferExpired.into(),
+        "Buffer has expired and no longer accepts writes.",
+    )
+}
+
+/// Checks that the vending machine collects payments in `PaymentMode::Treasury` and that `treasury_info` is its
+/// treasury token account.
+pub fn assert_treasury(
+    vm_buffer_struct: &VendingMachineBuffer,
+    treasury_info: &AccountInfo,
+) -> ProgramResult {
+    assert_with_msg(
+        vm_buffer_struct.payment_mode() == Some(PaymentMode::Treasury)
+            && vm_buffer_struct.treasury == *treasury_info.key,
+        EchoError::InvalidTreasury.into(),
+        &format!(
+            "Account {} is not the treasury of the vending machine.",
+            treasury_info.key
+        ),
+    )
+}
+
+/// Reads the token balance of the treasury token account.
+pub fn treasury_balance(treasury_info: &AccountInfo) -> Result<u64, ProgramError> {
+    spl_token::state::Account::unpack(&treasury_info.try_borrow_data()?)
+        .map(|treasury| treasury.amount)
+        .map_err(|_| EchoError::InvalidTreasury.into())
+}
+
+/// Checks that `credits_info` is the PDA recorded in `credits_struct` for `user_key` at the vending machine
+/// `vm_buffer_key`.
+pub fn assert_credits(
+    program_id: &Pubkey,
+    credits_info: &AccountInfo,
+    credits_struct: &VendingMachineCredits,
+    vm_buffer_key: &Pubkey,
+    user_key: &Pubkey,
+) -> ProgramResult {
+    let credits_key = create_credits_address(
+        program_id,
+        vm_buffer_key,
+        user_key,
+        credits_struct.bump_seed,
+    )
+    .map_err(|_| EchoError::InvalidCredits)?;
+    assert_with_msg(
+        credits_key == *credits_info.key
+            && credits_struct.vending_machine == *vm_buffer_key
+            && credits_struct.user == *user_key,
+        EchoError::InvalidCredits.into(),
+        &format!(
+            "Account {} is not the credits account of {} at the vending machine.",
+            credits_info.key, user_key
+        ),
+    )
+}
+
+/// Tops up or refunds the lamports of `account_info` from/to `payer_info` so that it is exactly rent-exempt at
+/// `data_len` bytes.
+pub fn rebalance_rent<'a>(
+    account_info: &AccountInfo<'a>,
+    payer_info: &AccountInfo<'a>,
+    system_program_info: &AccountInfo<'a>,
+    data_len: usize,
+) -> ProgramResult {
+    let current_lamports = account_info.lamports();
+    let required_lamports = Rent::get()?.minimum_balance(data_len);
+    match required_lamports.cmp(&current_lamports) {
+        Ordering::Greater => invoke(
+            &system_instruction::transfer(
+                payer_info.key,
+                account_info.key,
+                required_lamports - current_lamports,
+            ),
+            &[
+                payer_info.clone(),
+                account_info.clone(),
+                system_program_info.clone(),
+            ],
+        ),
+        Ordering::Less => {
+            **account_info.try_borrow_mut_lamports()? = required_lamports;
+            **payer_info.try_borrow_mut_lamports()? = payer_info
+                .lamports()
+                .checked_add(current_lamports - required_lamports)
+                .ok_or(EchoError::ArithmeticOverflow)?;
+            Ok(())
+        }
+        Ordering::Equal => Ok(()),
+    }
+}
+
+/// Moves all lamports of `account_info` to `recipient_info`, then zeroes and shrinks its data and hands it back to
+/// the System Program. Once the account is empty and system-owned, no echo instruction will accept it again.
+pub fn close_account(account_info: &AccountInfo, recipient_info: &AccountInfo) -> ProgramResult {
+    assert_with_msg(
+        account_info.key != recipient_info.key,
+        EchoError::InvalidRecipient.into(),
+        "Recipient cannot be the account being closed.",
+    )?;
+
+    let recipient_lamports = recipient_info
+        .lamports()
+        .checked_add(account_info.lamports())
+        .ok_or(EchoError::ArithmeticOverflow)?;
+    **recipient_info.try_borrow_mut_lamports()? = recipient_lamports;
+    **account_info.try_borrow_mut_lamports()? = 0;
+
+    account_info.try_borrow_mut_data()?.fill(0);
+    account_info.realloc(0, false)?;
+    account_info.assign(&system_program::id());
+    Ok(())
+}
+
+/// Copies `data` into `buffer` starting at `offset`, leaving the rest of `buffer` untouched.
+pub fn write_at(buffer: &mut [u8], offset: u64, data: &[u8]) -> ProgramResult {
+    let start = usize::try_from(offset).map_err(|_| EchoError::WriteOutOfBounds)?;
+    let end = start
+        .checked_add(data.len())
+        .ok_or(EchoError::WriteOutOfBounds)?;
+    assert_with_msg(
+        end <= buffer.len(),
+        EchoError::WriteOutOfBounds.into(),
+        &format!(
+            "Cannot write {} bytes at offset {} of a {} byte buffer.",
+            data.len(),
+            offset,
+            buffer.len()
+        ),
+    )?;
+    buffer[start..end].copy_from_slice(data);
+    Ok(())
+}
+
+/// Returns the `len` bytes of `buffer` starting at `offset`.
+pub fn read_at(buffer: &[u8], offset: u64, len: u64) -> Result<&[u8], ProgramError> {
+    let start = usize::try_from(offset).map_err(|_| EchoError::ReadOutOfBounds)?;
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .ok_or(EchoError::ReadOutOfBounds)?;
+    assert_with_msg(
+        end <= buffer.len(),
+        EchoError::ReadOutOfBounds.into(),
+        &format!(
+            "Cannot read {} bytes at offset {} of a {} byte buffer.",
+            len,
+            offset,
+            buffer.len()
+        ),
+    )?;
+    Ok(&buffer[start..end])
+}
+
+/// Checks that the `data` of a write is not empty.
+pub fn assert_not_empty(data: &[u8]) -> ProgramResult {
+    assert_with_msg(
+        !data.is_empty(),
+        EchoError::EmptyData.into(),
+        "Echo data must not be empty.",
+    )
+}
+
+/// Fills `buffer` by repeating `data`, as `AuthorizedEcho` and `VendingMachineEcho` do.
+///
+/// The filled prefix is doubled on every pass, so a large buffer takes a logarithmic number of copies rather than
+/// one step per byte.
+pub fn write_repeated(buffer: &mut [u8], data: &[u8]) -> ProgramResult {
+    assert_not_empty(data)?;
+    let mut filled = data.len().min(buffer.len());
+    buffer[..filled].copy_from_slice(&data[..filled]);
+    while filled < buffer.len() {
+        let len = filled.min(buffer.len() - filled);
+        buffer.copy_within(..len, filled);
+        filled += len;
+    }
+    Ok(())
+}
+
+/// Checks that the echo data of a buffer after a write is valid for its `content_type`. Only UTF-8 content is checked,
+/// as a whole so that a write cannot leave a character split at either end.
+pub fn assert_valid_content(content_type: u8, echo_data: &[u8]) -> ProgramResult {
+    assert_with_msg(
+        content_type != ContentType::Utf8 as u8 || std::str::from_utf8(echo_data).is_ok(),
+        EchoError::InvalidUtf8.into(),
+        "Echo data of a UTF-8 buffer must be valid UTF-8.",
+    )
+}
+
+/// Checks that `data` starts with the `AccountHeader` of an `account_type` account at `CURRENT_VERSION`.
+pub fn assert_account_header(data: &[u8], account_type: AccountType) -> ProgramResult {
+    let header: &AccountHeader = data
+        .get(..AccountHeader::LEN)
+        .and_then(|bytes| bytemuck::try_from_bytes(bytes).ok())
+        .ok_or(EchoError::InvalidAccountType)?;
+    assert_with_msg(
+        header.account_type == account_type as u8,
+        EchoError::InvalidAccountType.into(),
+        &format!(
+            "Expected a {:?} account. Accounts created before layout versioning must be migrated.",
+            account_type
+        ),
+    )?;
+    assert_with_msg(
+        header.version == CURRENT_VERSION,
+        EchoError::UnsupportedAccountVersion.into(),
+        &format!(
+            "Account layout version {} is not supported, expected {}. Older accounts must be migrated.",
+            header.version, CURRENT_VERSION
+        ),
+    )
+}
+
+/// Splits account data into its `T` header, read in place after checking its `AccountHeader`, and the echo data
+/// that follows it.
+pub fn load_buffer<T: EchoAccount>(data: &[u8]) -> Result<(&T, &[u8]), ProgramError> {
+    assert_account_header(data, T::ACCOUNT_TYPE)?;
+    assert_with_msg(
+        data.len() >= size_of::<T>(),
+        EchoError::InvalidBufferData.into(),
+        "Buffer is smaller than its header.",
+    )?;
+    let (header, echo_data) = data.split_at(size_of::<T>());
+    let header = bytemuck::try_from_bytes(header).map_err(|_| EchoError::InvalidBufferData)?;
+    Ok((header, echo_data))
+}
+
+/// Mutable version of `load_buffer`. Changes to the header and echo data are written straight to the account.
+pub fn load_buffer_mut<T: EchoAccount>(
+    data: &mut [u8],
+) -> Result<(&mut T, &mut [u8]), ProgramError> {
+    assert_account_header(data, T::ACCOUNT_TYPE)?;
+    assert_with_msg(
+        data.len() >= size_of::<T>(),
+        EchoError::InvalidBufferData.into(),
+        "Buffer is smaller than its header.",
+    )?;
+    let (header, echo_data) = data.split_at_mut(size_of::<T>());
+    let header = bytemuck::try_from_bytes_mut(header).map_err(|_| EchoError::InvalidBufferData)?;
+    Ok((header, echo_data))
+}
+
+/// Writes the `T` header of a newly created buffer. The echo data after it is left as is.
+pub fn initialize_buffer<T: EchoAccount>(data: &mut [u8], header: &T) -> ProgramResult {
+    let header_bytes = bytemuck::bytes_of(header);
+    data.get_mut(..header_bytes.len())
+        .ok_or(EchoError::BufferTooSmall)?
+        .copy_from_slice(header_bytes);
+    Ok(())
+}
+
+/// Reads a Borsh encoded struct from account data. Only meant for the layouts that predate zero-copy buffers.
+pub fn deserialize_legacy_buffer<T: BorshDeserialize>(data: &[u8]) -> Result<T, ProgramError> {
+    T::try_from_slice(data).map_err(|_| {
+        msg!("Buffer data could not be deserialized.");
+        EchoError::InvalidBufferData.into()
+    })
+}
+
+/// Number of tokens the vending machine charges for writing `bytes_written` bytes, which must not be above
+/// `max_charge`.
+pub fn charge_for_write(
+    vm_buffer_struct: &VendingMachineBuffer,
+    bytes_written: usize,
+    max_charge: u64,
+) -> Result<u64, ProgramError> {
+    let charge = vm_buffer_struct
+        .pricing()
+        .charge(bytes_written)
+        .ok_or_else(|| {
+            msg!("Charge for {} bytes does not fit in a u64.", bytes_written);
+            ProgramError::from(EchoError::ArithmeticOverflow)
+        })?;
+    assert_with_msg(
+        charge <= max_charge,
+        EchoError::ChargeExceedsMax.into(),
+        &format!(
+            "Charge of {} tokens is above the maximum of {}.",
+            charge, max_charge
+        ),
+    )?;
+    Ok(charge)
+}
+
+/// Collects `amount` tokens for the vending machine from `token_account_info` according to its `PaymentMode`.
+/// `treasury_info` is only read in `PaymentMode::Treasury`.
+pub fn pay_vending_machine<'a>(
+    vm_buffer_struct: &VendingMachineBuffer,
+    amount: u64,
+    token_account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    treasury_info: Option<&AccountInfo<'a>>,
+) -> ProgramResult {
+    match vm_buffer_struct.payment_mode() {
+        Some(PaymentMode::Burn) => burn_tokens(
+            token_account_info,
+            mint_info,
+            owner_info,
+            token_program_info,
+            amount,
+        ),
+        Some(PaymentMode::Treasury) => {
+            let treasury_info = treasury_info.ok_or(ProgramError::NotEnoughAccountKeys)?;
+            assert_treasury(vm_buffer_struct, treasury_info)?;
+            transfer_tokens(
+                token_account_info,
+                treasury_info,
+                owner_info,
+                token_program_info,
+                amount,
+                &[],
+            )
+        }
+        None => Err(EchoError::InvalidBufferData.into()),
+    }
+}
+
+/// Spends `amount` prepaid credits from `credits_info` instead of collecting tokens, without any CPI.
+pub fn spend_credits(
+    program_id: &Pubkey,
+    vm_buffer_key: &Pubkey,
+    vm_buffer_struct: &mut VendingMachineBuffer,
+    user_info: &AccountInfo,
+    credits_info: &AccountInfo,
+    amount: u64,
+) -> ProgramResult {
+    let mut credits_data = credits_info.try_borrow_mut_data()?;
+    let (credits_struct, _) = load_buffer_mut::<VendingMachineCredits>(&mut credits_data)?;
+    assert_credits(
+        program_id,
+        credits_info,
+        credits_struct,
+        vm_buffer_key,
+        user_info.key,
+    )?;
+    assert_with_msg(
+        !credits_struct.is_expired(Clock::get()?.unix_timestamp),
+        EchoError::CreditsExpired.into(),
+        "Prepaid credits have expired.",
+    )?;
+
+    let credits = u64::from(credits_struct.credits)
+        .checked_sub(amount)
+        .ok_or_else(|| {
+            msg!(
+                "Write costs {} credits, only {} left.",
+                amount,
+                u64::from(credits_struct.credits)
+            );
+            EchoError::InsufficientCredits
+        })?;
+    credits_struct.credits = credits.into();
+    vm_buffer_struct.outstanding_credits = u64::from(vm_buffer_struct.outstanding_credits)
+        .checked_sub(amount)
+        .ok_or(EchoError::ArithmeticOverflow)?
+        .into();
+    Ok(())
+}
+
+/// Burns `amount` tokens of `mint_info` from `token_account_info`, signed by its `owner_info`.
+pub fn burn_tokens<'a>(
+    token_account_info: &AccountInfo<'a>,
+    mint_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    amount: u64,
+) -> ProgramResult {
+    assert_is_token_program(token_program_info)?;
+    invoke(
+        &burn(
+            token_program_info.key,
+            token_account_info.key,
+            mint_info.key,
+            owner_info.key,
+            &[owner_info.key],
+            amount,
+        )?,
+        &[
+            token_account_info.clone(),
+            mint_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+    )
+}
+
+/// Transfers `amount` tokens from `source_info` to `destination_info`. `signer_seeds` are only needed when
+/// `authority_info` is a PDA of the program.
+pub fn transfer_tokens<'a>(
+    source_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    authority_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    assert_is_token_program(token_program_info)?;
+    invoke_signed(
+        &transfer(
+            token_program_info.key,
+            source_info.key,
+            destination_info.key,
+            authority_info.key,
+            &[],
+            amount,
+        )?,
+        &[
+            source_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_program_info.clone(),
+        ],
+        signer_seeds,
+    )
+}
+
+/// Closes the token account `account_info`, which must hold no tokens, and sends its lamports to
+/// `destination_info`. `signer_seeds` are only needed when `owner_info` is a PDA of the program.
+pub fn close_token_account<'a>(
+    account_info: &AccountInfo<'a>,
+    destination_info: &AccountInfo<'a>,
+    owner_info: &AccountInfo<'a>,
+    token_program_info: &AccountInfo<'a>,
+    signer_seeds: &[&[&[u8]]],
+) -> ProgramResult {
+    assert_is_token_program(token_program_info)?;
+    invoke_signed(
+        &close_token_account_instruction(
+            token_program_info.key,
+            account_info.key,
+            destination_info.key,
+            owner_info.key,
+            &[],
+        )?,
+        &[
+            account_info.clone(),
+            destination_info.clone(),
+            owner_info.clone(),
+            token_program_info.clone(),
+        ],
+        signer_seeds,
+    )
+}
@@ -0,0 +1,152 @@
+#![cfg(feature = "cpi")]
+
+use {
+    echo::{
+        cpi::{self, CpiContext},
+        error::EchoError,
+        pda::find_authorized_buffer_address,
+        processor::Processor,
+        state::{AccountHeader, AccountType, AuthorizedBuffer, ContentType, ExpiryKind},
+        utils::load_buffer,
+    },
+    solana_program::{
+        account_info::{next_account_info, AccountInfo},
+        entrypoint::ProgramResult,
+        instruction::{AccountMeta, Instruction, InstructionError},
+        pubkey::Pubkey,
+        rent::Rent,
+    },
+    solana_program_test::{processor, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        signature::Signer,
+        transaction::{Transaction, TransactionError},
+    },
+};
+
+const AUTHORITY_SEED: &[u8] = b"authority";
+
+/// Program that writes its instruction data to an authorized buffer, signing as its `AUTHORITY_SEED` PDA.
+///
+/// Accounts: echo program, authorized buffer, PDA authority.
+fn process_caller(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts_iter = &mut accounts.iter();
+    let echo_program_info = next_account_info(accounts_iter)?;
+    let authorized_buffer_info = next_account_info(accounts_iter)?;
+    let authority_info = next_account_info(accounts_iter)?;
+
+    let (_, bump_seed) = Pubkey::find_program_address(&[AUTHORITY_SEED], program_id);
+    cpi::authorized_echo(
+        CpiContext::new_with_signer(
+            echo_program_info.clone(),
+            cpi::AuthorizedEcho {
+                authorized_buffer: authorized_buffer_info.clone(),
+                authority: authority_info.clone(),
+                buffer_writer: None,
+            },
+            &[&[AUTHORITY_SEED, &[bump_seed]]],
+        ),
+        data.to_vec(),
+    )
+}
+
+fn add_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    data_len: usize,
+) -> Pubkey {
+    let (authorized_buffer, bump_seed) = find_authorized_buffer_address(program_id, authority, 1);
+    let mut data = bytemuck::bytes_of(&AuthorizedBuffer {
+        header: AccountHeader::new(AccountType::AuthorizedBuffer),
+        bump_seed,
+        buffer_seed: 1.into(),
+        seed_authority: *authority,
+        authority: *authority,
+        pending_authority: Pubkey::default(),
+        sealed: 0,
+        expiry_kind: ExpiryKind::None as u8,
+        expires_at: 0.into(),
+        nonce: 0.into(),
+        content_hash: [0; 32],
+        merkle_leaf_count: 0.into(),
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        reserved: [0; 61],
+    })
+    .to_vec();
+    data.resize(AuthorizedBuffer::HEADER_SIZE + data_len, 0);
+    program_test.add_account(
+        authorized_buffer,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+    authorized_buffer
+}
+
+#[tokio::test]
+async fn test_authorized_echo_from_program() {
+    let program_id = Pubkey::new_unique();
+    let caller_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    program_test.add_program("caller", caller_id, processor!(process_caller));
+    let (caller_authority, _) = Pubkey::find_program_address(&[AUTHORITY_SEED], &caller_id);
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &caller_authority, 4);
+    let other_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &Pubkey::new_unique(), 4);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let caller_instruction = |authorized_buffer: &Pubkey, data: &[u8]| Instruction {
+        program_id: caller_id,
+        accounts: vec![
+            AccountMeta::new_readonly(program_id, false),
+            AccountMeta::new(*authorized_buffer, false),
+            AccountMeta::new_readonly(caller_authority, false),
+        ],
+        data: data.to_vec(),
+    };
+
+    // The caller program signs for its PDA, which is the authority of the buffer
+    let transaction = Transaction::new_signed_with_payer(
+        &[caller_instruction(&authorized_buffer, b"echo")],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"echo");
+
+    // Its PDA is not the authority of other buffers
+    let transaction = Transaction::new_signed_with_payer(
+        &[caller_instruction(&other_buffer, b"echo")],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidAuthority as u32)
+        )
+    );
+}
@@ -1,16 +1,14 @@
 // #![cfg(feature = "test-bpf")]
 use anyhow::anyhow;
-use echo::state::{AuthorizedBuffer, VendingMachineBuffer};
+use echo::state::{AuthorizedBuffer, PaymentMode, Pricing, VendingMachineBuffer};
 // use solana_sdk::transaction::Transaction;
 use std::path::{Path, PathBuf};
 
 use assert_matches::*;
-use borsh::{BorshDeserialize, BorshSerialize};
+use echo::utils::load_buffer;
 use solana_client::client_error::{ClientError, ClientErrorKind};
 use solana_client::rpc_client::RpcClient;
 use solana_sdk::account::ReadableAccount;
-use solana_sdk::instruction::AccountMeta;
-use solana_sdk::instruction::Instruction;
 use solana_sdk::message::Message;
 use solana_sdk::program_error::ProgramError;
 use solana_sdk::program_pack::Pack;
@@ -18,13 +16,16 @@ use solana_sdk::pubkey::Pubkey;
 use solana_sdk::rent::Rent;
 use solana_sdk::signature::Keypair;
 use solana_sdk::system_instruction;
-use solana_sdk::system_program;
 use solana_sdk::sysvar;
 use solana_sdk::{signature::Signer, transaction::Transaction};
 use solana_validator::test_validator::*;
 use spl_token::instruction::initialize_mint;
 
-use echo::instruction::EchoInstruction;
+use echo::instruction::{
+    authorized_echo, decode_read_echo, echo, initialize_authorized_echo,
+    initialize_vending_machine_echo, read_authorized_echo, vending_machine_echo,
+};
+use echo::pda::{find_authorized_buffer_address, find_vending_machine_address};
 
 #[test]
 fn test_echo() -> anyhow::Result<()> {
@@ -57,11 +58,7 @@ fn test_echo() -> anyhow::Result<()> {
                 &program_id,
             ),
             // Instruction to write to buffer
-            Instruction {
-                program_id,
-                accounts: vec![AccountMeta::new(echo_buffer.pubkey(), false)],
-                data: EchoInstruction::Echo { data }.try_to_vec()?,
-            },
+            echo(&program_id, &echo_buffer.pubkey(), data),
         ],
         Some(&payer.pubkey()),
         &vec![&payer, &echo_buffer],
@@ -102,11 +99,7 @@ fn test_echo_uninitialized() -> anyhow::Result<()> {
     let mut transaction = Transaction::new_signed_with_payer(
         &[
             // Instruction to write to buffer
-            Instruction {
-                program_id,
-                accounts: vec![AccountMeta::new(echo_buffer.pubkey(), false)],
-                data: EchoInstruction::Echo { data }.try_to_vec()?,
-            },
+            echo(&program_id, &echo_buffer.pubkey(), data),
         ],
         Some(&payer.pubkey()),
         &vec![&payer],
@@ -152,16 +145,8 @@ fn test_echo_nonzero() -> anyhow::Result<()> {
                 4,
                 &program_id,
             ),
-            Instruction {
-                program_id,
-                accounts: vec![AccountMeta::new(echo_buffer.pubkey(), false)],
-                data: EchoInstruction::Echo { data }.try_to_vec().unwrap(),
-            },
-            Instruction {
-                program_id,
-                accounts: vec![AccountMeta::new(echo_buffer.pubkey(), false)],
-                data: EchoInstruction::Echo { data: data2 }.try_to_vec().unwrap(),
-            },
+            echo(&program_id, &echo_buffer.pubkey(), data),
+            echo(&program_id, &echo_buffer.pubkey(), data2),
         ],
         Some(&payer.pubkey()),
         &vec![&payer, &echo_buffer],
@@ -188,32 +173,20 @@ fn test_authorized_echo() -> anyhow::Result<()> {
     let rpc_client = test_validator.get_rpc_client();
 
     let buffer_seed = 1u64;
-    let (pda, _) = Pubkey::find_program_address(
-        &[
-            b"authority",
-            payer.pubkey().as_ref(),
-            &buffer_seed.to_le_bytes(),
-        ],
-        &program_id,
-    );
+    let (pda, _) = find_authorized_buffer_address(&program_id, &payer.pubkey(), buffer_seed);
 
     let data = b"authorized".to_vec();
 
     let blockhash = rpc_client.get_latest_blockhash()?;
     let mut transaction = Transaction::new_signed_with_payer(
-        &[Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(pda, false),
-                AccountMeta::new(payer.pubkey(), true),
-                AccountMeta::new_readonly(system_program::id(), false),
-            ],
-            data: EchoInstruction::InitializeAuthorizedEcho {
-                buffer_seed,
-                buffer_size: 13 + data.len() as u64,
-            }
-            .try_to_vec()?,
-        }],
+        &[initialize_authorized_echo(
+            &program_id,
+            &payer.pubkey(),
+            buffer_seed,
+            (AuthorizedBuffer::HEADER_SIZE + data.len()) as u64,
+            None,
+            None,
+        )],
         Some(&payer.pubkey()),
         &vec![&payer],
         blockhash,
@@ -224,14 +197,7 @@ fn test_authorized_echo() -> anyhow::Result<()> {
 
     let blockhash = rpc_client.get_latest_blockhash()?;
     let mut transaction = Transaction::new_signed_with_payer(
-        &[Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(pda, false),
-                AccountMeta::new_readonly(payer.pubkey(), true),
-            ],
-            data: EchoInstruction::AuthorizedEcho { data }.try_to_vec()?,
-        }],
+        &[authorized_echo(&program_id, &pda, &payer.pubkey(), data)],
         Some(&payer.pubkey()),
         &vec![&payer],
         blockhash,
@@ -239,9 +205,32 @@ fn test_authorized_echo() -> anyhow::Result<()> {
     transaction.sign(&[&payer], blockhash);
     rpc_client.send_and_confirm_transaction(&transaction)?;
     let echo_data = rpc_client.get_account(&pda)?.data;
-    let echo_buffer = AuthorizedBuffer::try_from_slice(&echo_data)?.data;
+    let (_, echo_buffer) = load_buffer::<AuthorizedBuffer>(&echo_data)?;
     let string = std::str::from_utf8(&echo_buffer)?;
     assert_matches!(string, "authorized");
+
+    // Read part of the buffer back through the return data, which the runtime logs as
+    // "Program return: <program_id> <base64 data>"
+    let blockhash = rpc_client.get_latest_blockhash()?;
+    let transaction = Transaction::new_signed_with_payer(
+        &[read_authorized_echo(&program_id, &pda, 4, 6)],
+        Some(&payer.pubkey()),
+        &vec![&payer],
+        blockhash,
+    );
+    let logs = rpc_client
+        .simulate_transaction(&transaction)?
+        .value
+        .logs
+        .unwrap_or_default();
+    let return_data = logs.iter().find_map(|log| {
+        let mut fields = log.strip_prefix("Program return: ")?.split(' ');
+        let return_program_id = fields.next()?.parse::<Pubkey>().ok()?;
+        let data = base64::decode(fields.next()?).ok()?;
+        Some((return_program_id, data))
+    });
+    let read = decode_read_echo(&program_id, return_data, 6)?;
+    assert_eq!(read, b"orized");
     Ok(())
 }
 
@@ -257,15 +246,10 @@ fn test_vending_machine() -> anyhow::Result<()> {
         .start();
     let rpc_client = test_validator.get_rpc_client();
 
+    let machine_id = 1u64;
     let price = 42u64;
-    let (pda, _) = Pubkey::find_program_address(
-        &[
-            b"vending_machine",
-            vending_machine_mint.pubkey().as_ref(),
-            &price.to_le_bytes(),
-        ],
-        &program_id,
-    );
+    let (pda, _) =
+        find_vending_machine_address(&program_id, &vending_machine_mint.pubkey(), machine_id);
 
     let blockhash = rpc_client.get_latest_blockhash()?;
     let mut transaction = Transaction::new_signed_with_payer(
@@ -306,20 +290,19 @@ fn test_vending_machine() -> anyhow::Result<()> {
                 &[&payer.pubkey()],
                 42,
             )?,
-            Instruction {
-                program_id,
-                accounts: vec![
-                    AccountMeta::new(pda, false),
-                    AccountMeta::new_readonly(vending_machine_mint.pubkey(), false),
-                    AccountMeta::new(payer.pubkey(), true),
-                    AccountMeta::new_readonly(system_program::id(), false),
-                ],
-                data: EchoInstruction::InitializeVendingMachineEcho {
-                    price,
-                    buffer_size: b"vending_machine".len() + 4 + 9,
-                }
-                .try_to_vec()?,
-            },
+            initialize_vending_machine_echo(
+                &program_id,
+                &vending_machine_mint.pubkey(),
+                &payer.pubkey(),
+                machine_id,
+                Pricing {
+                    base: price,
+                    per_byte: 0,
+                },
+                VendingMachineBuffer::HEADER_SIZE + b"vending machine".len(),
+                PaymentMode::Burn,
+                None,
+            ),
         ],
         Some(&payer.pubkey()),
         &vec![&payer, &vending_machine_mint, &user_token_account],
@@ -340,20 +323,15 @@ fn test_vending_machine() -> anyhow::Result<()> {
 
     let blockhash = rpc_client.get_latest_blockhash()?;
     let mut transaction = Transaction::new_signed_with_payer(
-        &[Instruction {
-            program_id,
-            accounts: vec![
-                AccountMeta::new(pda, false),
-                AccountMeta::new_readonly(payer.pubkey(), true),
-                AccountMeta::new(user_token_account.pubkey(), false),
-                AccountMeta::new(vending_machine_mint.pubkey(), false),
-                AccountMeta::new_readonly(spl_token::id(), false),
-            ],
-            data: EchoInstruction::VendingMachineEcho {
-                data: b"vending machine".to_vec(),
-            }
-            .try_to_vec()?,
-        }],
+        &[vending_machine_echo(
+            &program_id,
+            &pda,
+            &payer.pubkey(),
+            &user_token_account.pubkey(),
+            &vending_machine_mint.pubkey(),
+            b"vending machine".to_vec(),
+            price,
+        )],
         Some(&payer.pubkey()),
         &vec![&payer],
         blockhash,
@@ -366,7 +344,7 @@ fn test_vending_machine() -> anyhow::Result<()> {
     .amount;
     assert!(ta_final_amount == ta_initial_amount - price);
     let vm_data = rpc_client.get_account(&pda)?.data;
-    let vm_buffer = VendingMachineBuffer::try_from_slice(&vm_data)?.data;
+    let (_, vm_buffer) = load_buffer::<VendingMachineBuffer>(&vm_data)?;
     let string = std::str::from_utf8(&vm_buffer)?;
     assert_matches!(string, "vending machine");
 
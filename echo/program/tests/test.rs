@@ -1,11 +1,545 @@
 #![cfg(feature = "test-bpf")]
 
 use {
-    solana_program::{pubkey::Pubkey, system_program},
-    solana_program_test::ProgramTest,
-    solana_sdk::signature::{Keypair, Signer},
+    borsh::BorshSerialize,
+    echo::{
+        error::EchoError,
+        instruction::{
+            accept_buffer_authority, add_buffer_writer, append_echo, authorized_echo,
+            authorized_echo_at, authorized_echo_signed, authorized_writer_echo,
+            authorized_writer_echo_at, begin_write, buy_vending_machine_credits, cancel_write,
+            clear_mailbox, close_authorized_echo, close_log_echo, close_mailbox,
+            close_token_gated_echo, close_vending_machine_credits, close_vending_machine_echo,
+            commit_write, echo, ed25519_signature_instruction, initialize_authorized_echo,
+            initialize_log_echo, initialize_mailbox, initialize_token_gated_echo,
+            migrate_authorized_echo, pop_message, read_authorized_echo, read_vending_machine_echo,
+            reclaim_expired_buffer, remove_buffer_writer, resize_authorized_echo,
+            seal_authorized_buffer, seal_vending_machine_buffer, send_message,
+            send_message_with_postage, set_buffer_authority, set_merkle_root,
+            set_vending_machine_credit_policy, set_vending_machine_price, staged_write,
+            token_gated_echo, vending_machine_echo, vending_machine_echo_at, verify_chunk,
+            verify_echo, withdraw_vending_machine_proceeds, write_chunk,
+        },
+        merkle::{verify_proof, MerkleTree},
+        pda::{
+            find_authorized_buffer_address, find_buffer_writer_address, find_credits_address,
+            find_log_buffer_address, find_mailbox_address, find_staging_address,
+            find_token_gated_address, find_treasury_address, find_vending_machine_address,
+        },
+        processor::Processor,
+        state::{
+            signed_echo_message, AccountHeader, AccountType, AuthorizedBuffer, AuthorizedBufferV1,
+            BufferWriter, ContentHeader, ContentType, EchoContent, Expiry, ExpiryKind,
+            LegacyAuthorizedBuffer, LogBuffer, Mailbox, Message, OriginalAuthorizedBuffer,
+            PaymentMode, Pricing, StagingBuffer, TokenGatedBuffer, VendingMachineBuffer,
+            VendingMachineCredits, CURRENT_VERSION,
+        },
+        utils::load_buffer,
+    },
+    solana_program::{
+        hash::hash, instruction::InstructionError, program_option::COption, program_pack::Pack,
+        pubkey::Pubkey, rent::Rent, system_instruction, system_program,
+    },
+    solana_program_test::{processor, BanksClient, ProgramTest},
+    solana_sdk::{
+        account::Account,
+        hash::Hash,
+        signature::{Keypair, Signer},
+        transaction::{Transaction, TransactionError},
+    },
 };
 
+async fn initialize_authorized_buffer(
+    banks_client: &mut BanksClient,
+    program_id: &Pubkey,
+    authority: &Keypair,
+    recent_blockhash: Hash,
+    buffer_seed: u64,
+    buffer_size: u64,
+) -> Pubkey {
+    let (authorized_buffer, _) =
+        find_authorized_buffer_address(program_id, &authority.pubkey(), buffer_seed);
+    let transaction = Transaction::new_signed_with_payer(
+        &[initialize_authorized_echo(
+            program_id,
+            &authority.pubkey(),
+            buffer_seed,
+            buffer_size,
+            None,
+            None,
+        )],
+        Some(&authority.pubkey()),
+        &[authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    authorized_buffer
+}
+
+fn add_buffer_account(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    address: Pubkey,
+    data: Vec<u8>,
+) {
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: *program_id,
+            ..Account::default()
+        },
+    );
+}
+
+fn authorized_buffer_header(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+) -> (Pubkey, AuthorizedBuffer) {
+    let (authorized_buffer, bump_seed) =
+        find_authorized_buffer_address(program_id, authority, buffer_seed);
+    let auth_buffer_struct = AuthorizedBuffer {
+        header: AccountHeader::new(AccountType::AuthorizedBuffer),
+        bump_seed,
+        buffer_seed: buffer_seed.into(),
+        seed_authority: *authority,
+        authority: *authority,
+        pending_authority: Pubkey::default(),
+        sealed: 0,
+        expiry_kind: ExpiryKind::None as u8,
+        expires_at: 0.into(),
+        nonce: 0.into(),
+        content_hash: [0; 32],
+        merkle_leaf_count: 0.into(),
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        reserved: [0; 61],
+    };
+    (authorized_buffer, auth_buffer_struct)
+}
+
+fn add_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data_len: usize,
+) -> Pubkey {
+    let (authorized_buffer, auth_buffer_struct) =
+        authorized_buffer_header(program_id, authority, buffer_seed);
+    let mut data = bytemuck::bytes_of(&auth_buffer_struct).to_vec();
+    data.resize(AuthorizedBuffer::HEADER_SIZE + data_len, 0);
+    add_buffer_account(program_test, program_id, authorized_buffer, data);
+    authorized_buffer
+}
+
+fn add_expiring_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data_len: usize,
+    expiry: Expiry,
+) -> Pubkey {
+    let (authorized_buffer, mut auth_buffer_struct) =
+        authorized_buffer_header(program_id, authority, buffer_seed);
+    auth_buffer_struct.set_expiry(Some(expiry));
+    let mut data = bytemuck::bytes_of(&auth_buffer_struct).to_vec();
+    data.resize(AuthorizedBuffer::HEADER_SIZE + data_len, 0);
+    add_buffer_account(program_test, program_id, authorized_buffer, data);
+    authorized_buffer
+}
+
+/// Adds `writer` to the allow-list of `authorized_buffer` as if `added_by` had signed `AddBufferWriter`.
+fn add_typed_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data_len: usize,
+    content: ContentHeader,
+) -> Pubkey {
+    let (authorized_buffer, mut auth_buffer_struct) =
+        authorized_buffer_header(program_id, authority, buffer_seed);
+    auth_buffer_struct.set_content_header(Some(content));
+    let mut data = bytemuck::bytes_of(&auth_buffer_struct).to_vec();
+    data.resize(AuthorizedBuffer::HEADER_SIZE + data_len, 0);
+    add_buffer_account(program_test, program_id, authorized_buffer, data);
+    authorized_buffer
+}
+
+async fn authorized_buffer_state(
+    banks_client: &mut BanksClient,
+    authorized_buffer: Pubkey,
+) -> (AuthorizedBuffer, Vec<u8>) {
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    (*auth_buffer_struct, echo_data.to_vec())
+}
+
+fn add_buffer_writer_account(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    writer: &Pubkey,
+    added_by: &Pubkey,
+) {
+    let (buffer_writer, bump_seed) =
+        find_buffer_writer_address(program_id, authorized_buffer, writer);
+    let data = bytemuck::bytes_of(&BufferWriter {
+        header: AccountHeader::new(AccountType::BufferWriter),
+        bump_seed,
+        authorized_buffer: *authorized_buffer,
+        writer: *writer,
+        added_by: *added_by,
+        reserved: [0; 32],
+    })
+    .to_vec();
+    add_buffer_account(program_test, program_id, buffer_writer, data);
+}
+
+/// Adds an upload of `staged` to `authorized_buffer` as if `owner` had signed `BeginWrite` and written every chunk.
+fn add_staging_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authorized_buffer: &Pubkey,
+    owner: &Pubkey,
+    staged: &[u8],
+    chunk_size: u64,
+) -> Pubkey {
+    let (staging_buffer, bump_seed) = find_staging_address(program_id, authorized_buffer, owner);
+    let mut data = bytemuck::bytes_of(&StagingBuffer {
+        header: AccountHeader::new(AccountType::StagingBuffer),
+        bump_seed,
+        authorized_buffer: *authorized_buffer,
+        owner: *owner,
+        len: (staged.len() as u64).into(),
+        chunk_size: chunk_size.into(),
+        hash: hash(staged).to_bytes(),
+        reserved: [0; 32],
+    })
+    .to_vec();
+    data.extend_from_slice(staged);
+    add_buffer_account(program_test, program_id, staging_buffer, data);
+    staging_buffer
+}
+
+fn add_log_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    ring_len: usize,
+) -> Pubkey {
+    let (log_buffer, bump_seed) = find_log_buffer_address(program_id, authority, buffer_seed);
+    let mut data = bytemuck::bytes_of(&LogBuffer {
+        header: AccountHeader::new(AccountType::LogBuffer),
+        bump_seed,
+        buffer_seed: buffer_seed.into(),
+        authority: *authority,
+        head: 0.into(),
+        tail: 0.into(),
+        records: 0.into(),
+        appended: 0.into(),
+        content_hash: [0; 32],
+        reserved: [0; 32],
+    })
+    .to_vec();
+    data.resize(LogBuffer::HEADER_SIZE + ring_len, 0);
+    add_buffer_account(program_test, program_id, log_buffer, data);
+    log_buffer
+}
+
+async fn log_records(
+    banks_client: &mut BanksClient,
+    log_buffer: Pubkey,
+) -> (LogBuffer, Vec<Vec<u8>>) {
+    let account = banks_client.get_account(log_buffer).await.unwrap().unwrap();
+    let (log_buffer_struct, log_data) = load_buffer::<LogBuffer>(&account.data).unwrap();
+    (
+        *log_buffer_struct,
+        log_buffer_struct.records(log_data).collect(),
+    )
+}
+
+fn add_mailbox(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    recipient: &Pubkey,
+    postage_account: &Pubkey,
+    min_postage: u64,
+    queue_len: usize,
+) -> Pubkey {
+    let (mailbox, bump_seed) = find_mailbox_address(program_id, recipient);
+    let mut data = bytemuck::bytes_of(&Mailbox {
+        header: AccountHeader::new(AccountType::Mailbox),
+        bump_seed,
+        recipient: *recipient,
+        postage_account: *postage_account,
+        min_postage: min_postage.into(),
+        len: 0.into(),
+        messages: 0.into(),
+        reserved: [0; 32],
+    })
+    .to_vec();
+    data.resize(Mailbox::HEADER_SIZE + queue_len, 0);
+    add_buffer_account(program_test, program_id, mailbox, data);
+    mailbox
+}
+
+async fn mailbox_messages(banks_client: &mut BanksClient, mailbox: Pubkey) -> Vec<Message> {
+    let account = banks_client.get_account(mailbox).await.unwrap().unwrap();
+    let (mailbox_struct, mailbox_data) = load_buffer::<Mailbox>(&account.data).unwrap();
+    mailbox_struct.messages(mailbox_data).collect()
+}
+
+fn legacy_authorized_buffer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data: &[u8],
+) -> (Pubkey, LegacyAuthorizedBuffer) {
+    let (authorized_buffer, bump_seed) =
+        find_authorized_buffer_address(program_id, authority, buffer_seed);
+    let legacy_struct = LegacyAuthorizedBuffer {
+        bump_seed,
+        buffer_seed,
+        seed_authority: *authority,
+        authority: *authority,
+        pending_authority: Pubkey::default(),
+        data: data.to_vec(),
+    };
+    (authorized_buffer, legacy_struct)
+}
+
+fn add_legacy_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data: &[u8],
+) -> Pubkey {
+    let (authorized_buffer, legacy_struct) =
+        legacy_authorized_buffer(program_id, authority, buffer_seed, data);
+    add_buffer_account(
+        program_test,
+        program_id,
+        authorized_buffer,
+        legacy_struct.try_to_vec().unwrap(),
+    );
+    authorized_buffer
+}
+
+fn add_original_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data: &[u8],
+) -> Pubkey {
+    let (authorized_buffer, bump_seed) =
+        find_authorized_buffer_address(program_id, authority, buffer_seed);
+    let original_struct = OriginalAuthorizedBuffer {
+        bump_seed,
+        buffer_seed,
+        data: data.to_vec(),
+    };
+    add_buffer_account(
+        program_test,
+        program_id,
+        authorized_buffer,
+        original_struct.try_to_vec().unwrap(),
+    );
+    authorized_buffer
+}
+
+fn add_v1_authorized_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    buffer_seed: u64,
+    data: &[u8],
+) -> Pubkey {
+    let (authorized_buffer, legacy_struct) =
+        legacy_authorized_buffer(program_id, authority, buffer_seed, data);
+    add_buffer_account(
+        program_test,
+        program_id,
+        authorized_buffer,
+        AuthorizedBufferV1::from(legacy_struct)
+            .try_to_vec()
+            .unwrap(),
+    );
+    authorized_buffer
+}
+
+fn add_token_account(
+    program_test: &mut ProgramTest,
+    address: Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) {
+    let mut data = vec![0; spl_token::state::Account::LEN];
+    spl_token::state::Account::pack(
+        spl_token::state::Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..spl_token::state::Account::default()
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        address,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+}
+
+fn add_mint(program_test: &mut ProgramTest, mint_authority: &Pubkey, supply: u64) -> Pubkey {
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; spl_token::state::Mint::LEN];
+    spl_token::state::Mint::pack(
+        spl_token::state::Mint {
+            mint_authority: COption::Some(*mint_authority),
+            supply,
+            decimals: 0,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &mut data,
+    )
+    .unwrap();
+    program_test.add_account(
+        mint,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: spl_token::id(),
+            ..Account::default()
+        },
+    );
+    mint
+}
+
+fn add_token_gated_buffer(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    creator: &Pubkey,
+    min_balance: u64,
+    data_len: usize,
+    content: Option<ContentHeader>,
+) -> Pubkey {
+    let (token_gated_buffer, bump_seed) = find_token_gated_address(program_id, mint, creator, 1);
+    let mut token_gated_buffer_struct = TokenGatedBuffer {
+        header: AccountHeader::new(AccountType::TokenGatedBuffer),
+        bump_seed,
+        buffer_seed: 1.into(),
+        creator: *creator,
+        mint: *mint,
+        min_balance: min_balance.into(),
+        content_hash: [0; 32],
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        reserved: [0; 23],
+    };
+    token_gated_buffer_struct.set_content_header(content);
+    let mut data = bytemuck::bytes_of(&token_gated_buffer_struct).to_vec();
+    data.resize(TokenGatedBuffer::HEADER_SIZE + data_len, 0);
+    add_buffer_account(program_test, program_id, token_gated_buffer, data);
+    token_gated_buffer
+}
+
+/// Adds a vending machine as `InitializeVendingMachineEcho` would create it, including its treasury in
+/// `PaymentMode::Treasury`.
+fn add_vending_machine(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    pricing: Pricing,
+    payment_mode: PaymentMode,
+    data_len: usize,
+) -> Pubkey {
+    let machine_id = 1;
+    let (vm_buffer, bump_seed) = find_vending_machine_address(program_id, mint, machine_id);
+    let treasury = match payment_mode {
+        PaymentMode::Burn => Pubkey::default(),
+        PaymentMode::Treasury => {
+            let (treasury, _) = find_treasury_address(program_id, &vm_buffer);
+            add_token_account(program_test, treasury, mint, &vm_buffer, 0);
+            treasury
+        }
+    };
+    let mut data = bytemuck::bytes_of(&VendingMachineBuffer {
+        header: AccountHeader::new(AccountType::VendingMachineBuffer),
+        bump_seed,
+        price: pricing.base.into(),
+        owner: *owner,
+        payment_mode: payment_mode as u8,
+        treasury,
+        machine_id: machine_id.into(),
+        price_per_byte: pricing.per_byte.into(),
+        credit_lifetime: 0.into(),
+        refund_credits: 0,
+        outstanding_credits: 0.into(),
+        sealed: 0,
+        content_type: ContentType::Bytes as u8,
+        schema_id: 0.into(),
+        content_hash: [0; 32],
+        reserved: [0; 20],
+    })
+    .to_vec();
+    data.resize(VendingMachineBuffer::HEADER_SIZE + data_len, 0);
+    add_buffer_account(program_test, program_id, vm_buffer, data);
+    vm_buffer
+}
+
+/// Adds a credits account of `user` at `vm_buffer`. The credits are not counted as outstanding by the machine.
+fn add_credits(
+    program_test: &mut ProgramTest,
+    program_id: &Pubkey,
+    vm_buffer: &Pubkey,
+    user: &Pubkey,
+    credits: u64,
+    expires_at: i64,
+) -> Pubkey {
+    let (credits_address, bump_seed) = find_credits_address(program_id, vm_buffer, user);
+    let data = bytemuck::bytes_of(&VendingMachineCredits {
+        header: AccountHeader::new(AccountType::VendingMachineCredits),
+        bump_seed,
+        vending_machine: *vm_buffer,
+        user: *user,
+        credits: credits.into(),
+        expires_at: expires_at.into(),
+        reserved: [0; 32],
+    })
+    .to_vec();
+    add_buffer_account(program_test, program_id, credits_address, data);
+    credits_address
+}
+
+async fn token_balance(banks_client: &mut BanksClient, address: Pubkey) -> u64 {
+    let account = banks_client.get_account(address).await.unwrap().unwrap();
+    spl_token::state::Account::unpack(&account.data)
+        .unwrap()
+        .amount
+}
+
 #[test]
 fn test_echo() {
     let program_id = Pubkey::new_unique();
@@ -24,3 +558,4057 @@ fn test_echo() {
     );
     // INSERT TESTS HERE
 }
+
+// Closing reallocs and reassigns the buffer, which is only supported by the BPF runtime
+#[tokio::test]
+async fn test_close_authorized_echo() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let authorized_buffer = initialize_authorized_buffer(
+        &mut banks_client,
+        &program_id,
+        &payer,
+        recent_blockhash,
+        1,
+        AuthorizedBuffer::HEADER_SIZE as u64 + 64,
+    )
+    .await;
+    let buffer_lamports = banks_client.get_balance(authorized_buffer).await.unwrap();
+
+    // Only the authority may close the buffer
+    let impostor = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &impostor.pubkey(),
+            &impostor.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &impostor],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    let recipient = Pubkey::new_unique();
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &payer.pubkey(),
+            &recipient,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        banks_client.get_balance(recipient).await.unwrap(),
+        buffer_lamports
+    );
+    assert!(banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_close_authorized_echo_cannot_revive() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let authorized_buffer = initialize_authorized_buffer(
+        &mut banks_client,
+        &program_id,
+        &payer,
+        recent_blockhash,
+        2,
+        AuthorizedBuffer::HEADER_SIZE as u64 + 64,
+    )
+    .await;
+    let buffer_lamports = banks_client.get_balance(authorized_buffer).await.unwrap();
+
+    // Close the buffer, fund it again and try to write to it in the same transaction
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            close_authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                &payer.pubkey(),
+            ),
+            system_instruction::transfer(&payer.pubkey(), &authorized_buffer, buffer_lamports),
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                b"revived".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+}
+
+// Closing reallocs and reassigns the buffer, which is only supported by the BPF runtime
+#[tokio::test]
+async fn test_close_vending_machine_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("echo", program_id, None);
+    let mint_authority = Keypair::new();
+    let owner = Keypair::new();
+    let mint = add_mint(&mut program_test, &mint_authority.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing::default(),
+        PaymentMode::Burn,
+        4,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let buffer_lamports = banks_client.get_balance(vm_buffer).await.unwrap();
+
+    // The mint authority does not own the machine
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_vending_machine_echo(
+            &program_id,
+            &vm_buffer,
+            &mint,
+            &mint_authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &mint_authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidOwner as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_vending_machine_echo(
+            &program_id,
+            &vm_buffer,
+            &mint,
+            &owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &owner],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    assert_eq!(
+        banks_client.get_balance(owner.pubkey()).await.unwrap(),
+        buffer_lamports
+    );
+    assert!(banks_client.get_account(vm_buffer).await.unwrap().is_none());
+}
+
+// Resizing reallocs the buffer, which is only supported by the BPF runtime
+#[tokio::test]
+async fn test_resize_authorized_echo() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+
+    let authorized_buffer = initialize_authorized_buffer(
+        &mut banks_client,
+        &program_id,
+        &payer,
+        recent_blockhash,
+        1,
+        AuthorizedBuffer::HEADER_SIZE as u64 + 4,
+    )
+    .await;
+
+    // Grow the buffer, the existing data is kept
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                b"echo".to_vec(),
+            ),
+            resize_authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                AuthorizedBuffer::HEADER_SIZE as u64 + 8,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(account.data.len(), AuthorizedBuffer::HEADER_SIZE + 8);
+    assert_eq!(
+        account.lamports,
+        rent.minimum_balance(AuthorizedBuffer::HEADER_SIZE + 8)
+    );
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"echo\0\0\0\0");
+
+    // Shrink the buffer, the excess rent goes back to the authority
+    let transaction = Transaction::new_signed_with_payer(
+        &[resize_authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &payer.pubkey(),
+            AuthorizedBuffer::HEADER_SIZE as u64 + 2,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        account.lamports,
+        rent.minimum_balance(AuthorizedBuffer::HEADER_SIZE + 2)
+    );
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"ec");
+}
+
+// Migrating reallocs the buffer, which is only supported by the BPF runtime
+#[tokio::test]
+async fn test_migrate_authorized_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("echo", program_id, None);
+    let authority = Keypair::new();
+    let legacy_buffer = add_legacy_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        1,
+        b"echo",
+    );
+    let v1_buffer = add_v1_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        2,
+        b"echo",
+    );
+    let original_buffer = add_original_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        3,
+        b"echo",
+    );
+    // Original data that also parses as the legacy layout, with a `seed_authority` that does not derive the address
+    let mut ambiguous_data = vec![0; 92];
+    ambiguous_data.extend_from_slice(&4u32.to_le_bytes());
+    ambiguous_data.extend_from_slice(b"echo");
+    let ambiguous_buffer = add_original_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        4,
+        &ambiguous_data,
+    );
+    program_test.add_account(
+        authority.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let rent = banks_client.get_rent().await.unwrap();
+
+    for authorized_buffer in [legacy_buffer, v1_buffer, original_buffer] {
+        // Only the authority may migrate
+        let transaction = Transaction::new_signed_with_payer(
+            &[migrate_authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert!(banks_client.process_transaction(transaction).await.is_err());
+
+        // Migrate and write in the same transaction, the old data is kept
+        let transaction = Transaction::new_signed_with_payer(
+            &[
+                migrate_authorized_echo(&program_id, &authorized_buffer, &authority.pubkey()),
+                authorized_echo_at(
+                    &program_id,
+                    &authorized_buffer,
+                    &authority.pubkey(),
+                    2,
+                    b"ho".to_vec(),
+                ),
+            ],
+            Some(&payer.pubkey()),
+            &[&payer, &authority],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+
+        let account = banks_client
+            .get_account(authorized_buffer)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(account.data.len(), AuthorizedBuffer::HEADER_SIZE + 4);
+        assert_eq!(
+            account.lamports,
+            rent.minimum_balance(AuthorizedBuffer::HEADER_SIZE + 4)
+        );
+        let (auth_buffer_struct, echo_data) =
+            load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+        assert_eq!(auth_buffer_struct.authority, authority.pubkey());
+        assert_eq!(echo_data, b"echo");
+        assert_eq!(auth_buffer_struct.content_hash, hash(b"echo").to_bytes());
+
+        // A migrated buffer cannot be migrated again
+        let transaction = Transaction::new_signed_with_payer(
+            &[migrate_authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &authority],
+            recent_blockhash,
+        );
+        assert!(banks_client.process_transaction(transaction).await.is_err());
+    }
+
+    // The original layout is used when the legacy fields do not derive the address
+    let transaction = Transaction::new_signed_with_payer(
+        &[migrate_authorized_echo(
+            &program_id,
+            &ambiguous_buffer,
+            &authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(ambiguous_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(auth_buffer_struct.authority, authority.pubkey());
+    assert_eq!(echo_data, ambiguous_data);
+}
+
+// Buffers are written in place, so they are not limited by the 32KiB BPF heap
+#[tokio::test]
+async fn test_authorized_echo_large_buffer() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("echo", program_id, None);
+    let authority = Keypair::new();
+    let authorized_buffer = add_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        1,
+        64 * 1024,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                64 * 1024 - 4,
+                b"last".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert!(echo_data[..64 * 1024 - 4]
+        .chunks(4)
+        .all(|chunk| chunk == b"echo"));
+    assert_eq!(&echo_data[64 * 1024 - 4..], b"last");
+}
+
+#[tokio::test]
+async fn test_authorized_echo_at() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 8);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                b"abcdefgh".to_vec(),
+            ),
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                2,
+                b"XY".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"abXYefgh");
+
+    // Writes past the end of the echo data are rejected
+    let transaction = Transaction::new_signed_with_payer(
+        &[authorized_echo_at(
+            &program_id,
+            &authorized_buffer,
+            &authority.pubkey(),
+            7,
+            b"XY".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::WriteOutOfBounds as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_transfer_buffer_authority() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let old_authority = Keypair::new();
+    let new_authority = Keypair::new();
+    let authorized_buffer = add_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &old_authority.pubkey(),
+        1,
+        4,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Proposing a new authority does not hand over write access yet
+    let transaction = Transaction::new_signed_with_payer(
+        &[set_buffer_authority(
+            &program_id,
+            &authorized_buffer,
+            &old_authority.pubkey(),
+            &new_authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &old_authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &new_authority.pubkey(),
+            b"new".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &new_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    // Once accepted, only the new authority can write to the buffer at its original address
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            accept_buffer_authority(&program_id, &authorized_buffer, &new_authority.pubkey()),
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &new_authority.pubkey(),
+                b"new!".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &new_authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &old_authority.pubkey(),
+            b"old!".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &old_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(auth_buffer_struct.seed_authority, old_authority.pubkey());
+    assert_eq!(auth_buffer_struct.authority, new_authority.pubkey());
+    assert_eq!(auth_buffer_struct.pending_authority, Pubkey::default());
+    assert_eq!(echo_data, b"new!");
+}
+
+#[tokio::test]
+async fn test_buffer_writers() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let writer = Keypair::new();
+    let stale_writer = Keypair::new();
+    let impostor = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 4);
+    add_buffer_writer_account(
+        &mut program_test,
+        &program_id,
+        &authorized_buffer,
+        &writer.pubkey(),
+        &authority.pubkey(),
+    );
+    // Added by an earlier authority of the buffer
+    add_buffer_writer_account(
+        &mut program_test,
+        &program_id,
+        &authorized_buffer,
+        &stale_writer.pubkey(),
+        &impostor.pubkey(),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Writers and the authority can both write
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_writer_echo(
+                &program_id,
+                &authorized_buffer,
+                &writer.pubkey(),
+                b"ab".to_vec(),
+            ),
+            authorized_writer_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &writer.pubkey(),
+                2,
+                b"c".to_vec(),
+            ),
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                3,
+                b"d".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &writer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"abcd");
+
+    // Another writer's entry does not let the impostor in
+    let (buffer_writer, _) =
+        find_buffer_writer_address(&program_id, &authorized_buffer, &writer.pubkey());
+    let mut impostor_echo = authorized_writer_echo(
+        &program_id,
+        &authorized_buffer,
+        &impostor.pubkey(),
+        b"echo".to_vec(),
+    );
+    impostor_echo.accounts[2].pubkey = buffer_writer;
+    let cases = [
+        (
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &writer.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &writer,
+            EchoError::InvalidAuthority,
+        ),
+        (
+            authorized_writer_echo(
+                &program_id,
+                &authorized_buffer,
+                &stale_writer.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &stale_writer,
+            EchoError::InvalidWriter,
+        ),
+        (impostor_echo, &impostor, EchoError::InvalidWriter),
+    ];
+    for (instruction, signer, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+// Creating and closing writers is only supported by the BPF runtime
+#[tokio::test]
+async fn test_add_remove_buffer_writer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let writer = Keypair::new();
+
+    let authorized_buffer = initialize_authorized_buffer(
+        &mut banks_client,
+        &program_id,
+        &payer,
+        recent_blockhash,
+        1,
+        AuthorizedBuffer::HEADER_SIZE as u64 + 4,
+    )
+    .await;
+    let write = authorized_writer_echo(
+        &program_id,
+        &authorized_buffer,
+        &writer.pubkey(),
+        b"echo".to_vec(),
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            add_buffer_writer(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                &writer.pubkey(),
+            ),
+            write.clone(),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &writer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Once removed, the writer can no longer write
+    let remove = remove_buffer_writer(
+        &program_id,
+        &authorized_buffer,
+        &payer.pubkey(),
+        &writer.pubkey(),
+        &payer.pubkey(),
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[remove.clone(), write],
+        Some(&payer.pubkey()),
+        &[&payer, &writer],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_err());
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[remove],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (buffer_writer, _) =
+        find_buffer_writer_address(&program_id, &authorized_buffer, &writer.pubkey());
+    assert!(banks_client
+        .get_account(buffer_writer)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_seal_buffer() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let writer = Keypair::new();
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 4);
+    add_buffer_writer_account(
+        &mut program_test,
+        &program_id,
+        &authorized_buffer,
+        &writer.pubkey(),
+        &authority.pubkey(),
+    );
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: 1,
+            per_byte: 0,
+        },
+        PaymentMode::Burn,
+        4,
+    );
+    let user_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        10,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Only the authority and the owner can seal
+    let cases = [
+        (
+            seal_authorized_buffer(&program_id, &authorized_buffer, &writer.pubkey()),
+            &writer,
+            EchoError::InvalidAuthority,
+        ),
+        (
+            seal_vending_machine_buffer(&program_id, &vm_buffer, &user.pubkey(), &mint),
+            &user,
+            EchoError::InvalidOwner,
+        ),
+    ];
+    for (instruction, signer, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            seal_authorized_buffer(&program_id, &authorized_buffer, &authority.pubkey()),
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                b"echo".to_vec(),
+                1,
+            ),
+            seal_vending_machine_buffer(&program_id, &vm_buffer, &owner.pubkey(), &mint),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority, &user, &owner],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Every change to a sealed buffer fails, including sealing it again
+    let cases = [
+        (
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                b"edit".to_vec(),
+            ),
+            &authority,
+        ),
+        (
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                0,
+                b"e".to_vec(),
+            ),
+            &authority,
+        ),
+        (
+            authorized_writer_echo(
+                &program_id,
+                &authorized_buffer,
+                &writer.pubkey(),
+                b"edit".to_vec(),
+            ),
+            &writer,
+        ),
+        (
+            resize_authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                AuthorizedBuffer::HEADER_SIZE as u64 + 8,
+            ),
+            &authority,
+        ),
+        (
+            close_authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                &authority.pubkey(),
+            ),
+            &authority,
+        ),
+        (
+            seal_authorized_buffer(&program_id, &authorized_buffer, &authority.pubkey()),
+            &authority,
+        ),
+        (
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                b"edit".to_vec(),
+                1,
+            ),
+            &user,
+        ),
+        (
+            vending_machine_echo_at(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                0,
+                b"e".to_vec(),
+                1,
+            ),
+            &user,
+        ),
+        (
+            close_vending_machine_echo(&program_id, &vm_buffer, &mint, &owner.pubkey()),
+            &owner,
+        ),
+    ];
+    for (instruction, signer) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(EchoError::BufferSealed as u32)
+            )
+        );
+    }
+
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_ne!(auth_buffer_struct.sealed, 0);
+    assert_eq!(echo_data, b"echo");
+    let account = banks_client.get_account(vm_buffer).await.unwrap().unwrap();
+    let (vm_buffer_struct, echo_data) = load_buffer::<VendingMachineBuffer>(&account.data).unwrap();
+    assert_ne!(vm_buffer_struct.sealed, 0);
+    assert_eq!(echo_data, b"echo");
+}
+
+#[tokio::test]
+async fn test_buffer_expiry() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let writer = Keypair::new();
+    let expired_buffer = add_expiring_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        1,
+        4,
+        Expiry::UnixTimestamp(1),
+    );
+    add_buffer_writer_account(
+        &mut program_test,
+        &program_id,
+        &expired_buffer,
+        &writer.pubkey(),
+        &authority.pubkey(),
+    );
+    let live_buffer = add_expiring_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        2,
+        4,
+        Expiry::Slot(u64::MAX),
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Buffers accept writes until they expire
+    let transaction = Transaction::new_signed_with_payer(
+        &[authorized_echo(
+            &program_id,
+            &live_buffer,
+            &authority.pubkey(),
+            b"echo".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(live_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(auth_buffer_struct.expiry(), Some(Expiry::Slot(u64::MAX)));
+    assert_eq!(echo_data, b"echo");
+
+    let cases = [
+        (
+            authorized_echo(
+                &program_id,
+                &expired_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::BufferExpired,
+        ),
+        (
+            authorized_echo_at(
+                &program_id,
+                &expired_buffer,
+                &authority.pubkey(),
+                0,
+                b"e".to_vec(),
+            ),
+            &authority,
+            EchoError::BufferExpired,
+        ),
+        (
+            authorized_writer_echo(
+                &program_id,
+                &expired_buffer,
+                &writer.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &writer,
+            EchoError::BufferExpired,
+        ),
+        (
+            resize_authorized_echo(
+                &program_id,
+                &expired_buffer,
+                &authority.pubkey(),
+                AuthorizedBuffer::HEADER_SIZE as u64 + 8,
+            ),
+            &authority,
+            EchoError::BufferExpired,
+        ),
+        (
+            reclaim_expired_buffer(&program_id, &live_buffer, &authority.pubkey()),
+            &payer,
+            EchoError::BufferNotExpired,
+        ),
+        (
+            reclaim_expired_buffer(&program_id, &expired_buffer, &writer.pubkey()),
+            &payer,
+            EchoError::InvalidRecipient,
+        ),
+    ];
+    for (instruction, signer, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+// Reclaiming closes the buffer, which is only supported by the BPF runtime
+#[tokio::test]
+async fn test_reclaim_expired_buffer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let authority = Keypair::new();
+    let buffer_size = AuthorizedBuffer::HEADER_SIZE as u64 + 4;
+    let rent = Rent::default().minimum_balance(buffer_size as usize);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &payer.pubkey(),
+            &authority.pubkey(),
+            rent,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Already expired, so anyone can reclaim it right away
+    let instruction = initialize_authorized_echo(
+        &program_id,
+        &authority.pubkey(),
+        1,
+        buffer_size,
+        Some(Expiry::UnixTimestamp(0)),
+        None,
+    );
+    let authorized_buffer = instruction.accounts[0].pubkey;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(authority.pubkey()).await.unwrap(),
+        0
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[reclaim_expired_buffer(
+            &program_id,
+            &authorized_buffer,
+            &authority.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert!(banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        banks_client.get_balance(authority.pubkey()).await.unwrap(),
+        rent
+    );
+}
+
+// The native runtime drops return data, so only the checks of `ReadEcho` are covered here. The bytes read are
+// checked against a validator in tests/integration.rs.
+#[tokio::test]
+async fn test_read_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let owner = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 4);
+    add_buffer_writer_account(
+        &mut program_test,
+        &program_id,
+        &authorized_buffer,
+        &authority.pubkey(),
+        &authority.pubkey(),
+    );
+    let (buffer_writer, _) =
+        find_buffer_writer_address(&program_id, &authorized_buffer, &authority.pubkey());
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let other_mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing::default(),
+        PaymentMode::Burn,
+        4,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            read_authorized_echo(&program_id, &authorized_buffer, 0, 4),
+            read_authorized_echo(&program_id, &authorized_buffer, 4, 0),
+            read_vending_machine_echo(&program_id, &vm_buffer, &mint, 1, 2),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let cases = [
+        (
+            read_authorized_echo(&program_id, &authorized_buffer, 2, 3),
+            EchoError::ReadOutOfBounds,
+        ),
+        (
+            read_authorized_echo(&program_id, &authorized_buffer, u64::MAX, 1),
+            EchoError::ReadOutOfBounds,
+        ),
+        (
+            read_authorized_echo(&program_id, &authorized_buffer, 0, 1025),
+            EchoError::ReadTooLarge,
+        ),
+        (
+            read_vending_machine_echo(&program_id, &vm_buffer, &other_mint, 0, 4),
+            EchoError::InvalidBufferAddress,
+        ),
+        (
+            read_authorized_echo(&program_id, &buffer_writer, 0, 1),
+            EchoError::InvalidAccountType,
+        ),
+    ];
+    for (instruction, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+// Creating and closing the staging buffer needs the BPF runtime, so the upload is pre-seeded here and only the
+// chunk writes and the checks of `CommitWrite` are covered.
+#[tokio::test]
+async fn test_write_chunk() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let other = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 8);
+    let staging_buffer = add_staging_buffer(
+        &mut program_test,
+        &program_id,
+        &authorized_buffer,
+        &authority.pubkey(),
+        &[0; 6],
+        4,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Chunks land at `index * chunk_size`, in any order
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            write_chunk(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                1,
+                b"ho".to_vec(),
+            ),
+            write_chunk(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                0,
+                b"ecec".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(staging_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, staged_data) = load_buffer::<StagingBuffer>(&account.data).unwrap();
+    assert_eq!(staged_data, b"ececho");
+
+    let cases = [
+        (
+            write_chunk(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                0,
+                b"echoe".to_vec(),
+            ),
+            &authority,
+            EchoError::ChunkTooLarge,
+        ),
+        (
+            write_chunk(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                1,
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::WriteOutOfBounds,
+        ),
+        (
+            write_chunk(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                u64::MAX,
+                b"e".to_vec(),
+            ),
+            &authority,
+            EchoError::WriteOutOfBounds,
+        ),
+        (
+            // Only the owner of the upload can write to it
+            {
+                let mut instruction = write_chunk(
+                    &program_id,
+                    &authorized_buffer,
+                    &other.pubkey(),
+                    0,
+                    b"echo".to_vec(),
+                );
+                instruction.accounts[0].pubkey = staging_buffer;
+                instruction
+            },
+            &other,
+            EchoError::InvalidStagingBuffer,
+        ),
+        (
+            // The staged bytes no longer match the hash of the zeroed upload
+            commit_write(&program_id, &authorized_buffer, &authority.pubkey()),
+            &authority,
+            EchoError::StagedHashMismatch,
+        ),
+    ];
+    for (instruction, signer, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    // The live buffer is untouched until a commit goes through
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, &[0; 8]);
+}
+
+#[tokio::test]
+async fn test_staged_write() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let authorized_buffer = initialize_authorized_buffer(
+        &mut banks_client,
+        &program_id,
+        &payer,
+        recent_blockhash,
+        1,
+        AuthorizedBuffer::HEADER_SIZE as u64 + 16,
+    )
+    .await;
+    let (staging_buffer, _) =
+        find_staging_address(&program_id, &authorized_buffer, &payer.pubkey());
+    assert_eq!(
+        staged_write(
+            &program_id,
+            &authorized_buffer,
+            &payer.pubkey(),
+            b"chunked echo",
+            0
+        ),
+        None
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &payer.pubkey(),
+            b"old echo old ech".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Each step goes in its own transaction, as it would for a payload too large for one
+    for instruction in staged_write(
+        &program_id,
+        &authorized_buffer,
+        &payer.pubkey(),
+        b"chunked echo",
+        5,
+    )
+    .unwrap()
+    {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        banks_client.process_transaction(transaction).await.unwrap();
+    }
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"chunked echo\0\0\0\0");
+    assert!(banks_client
+        .get_account(staging_buffer)
+        .await
+        .unwrap()
+        .is_none());
+
+    // A cancelled upload leaves the buffer as it was
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            begin_write(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                4,
+                4,
+                hash(b"echo"),
+            ),
+            write_chunk(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                0,
+                b"echo".to_vec(),
+            ),
+            cancel_write(&program_id, &authorized_buffer, &payer.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"chunked echo\0\0\0\0");
+    assert!(banks_client
+        .get_account(staging_buffer)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Uploads cannot outgrow the buffer
+    let transaction = Transaction::new_signed_with_payer(
+        &[begin_write(
+            &program_id,
+            &authorized_buffer,
+            &payer.pubkey(),
+            17,
+            4,
+            hash(&[0; 17]),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::BufferTooSmall as u32)
+        )
+    );
+}
+
+// The staging buffer grows with reallocs, which are only supported by the BPF runtime
+#[tokio::test]
+async fn test_staged_write_large_upload() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("echo", program_id, None);
+    let authority = Keypair::new();
+    let len = 12 * 1024;
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, len);
+    program_test.add_account(
+        authority.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let data = (0..len).map(|i| i as u8).collect::<Vec<_>>();
+    let instructions = staged_write(
+        &program_id,
+        &authorized_buffer,
+        &authority.pubkey(),
+        &data,
+        1024,
+    )
+    .unwrap();
+    let send = |instruction| {
+        Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, &authority],
+            recent_blockhash,
+        )
+    };
+
+    // The staging buffer starts empty, so a chunk more than 10KiB past it cannot land yet
+    banks_client
+        .process_transaction(send(instructions[0].clone()))
+        .await
+        .unwrap();
+    let last_chunk = instructions[instructions.len() - 2].clone();
+    assert_eq!(
+        banks_client
+            .process_transaction(send(last_chunk))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::BufferGrowthTooLarge as u32)
+        )
+    );
+
+    // Sent in order, each chunk grows the staging buffer past the size a CPI could create
+    for instruction in instructions.into_iter().skip(1) {
+        banks_client
+            .process_transaction(send(instruction))
+            .await
+            .unwrap();
+    }
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, data);
+}
+
+#[tokio::test]
+async fn test_append_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let log_buffer = add_log_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 16);
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 16);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            append_echo(&program_id, &log_buffer, &authority.pubkey(), b"a".to_vec()),
+            append_echo(&program_id, &log_buffer, &authority.pubkey(), b"".to_vec()),
+            append_echo(
+                &program_id,
+                &log_buffer,
+                &authority.pubkey(),
+                b"bc".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (log_buffer_struct, records) = log_records(&mut banks_client, log_buffer).await;
+    assert_eq!(records, vec![b"a".to_vec(), b"".to_vec(), b"bc".to_vec()]);
+    assert_eq!(u64::from(log_buffer_struct.tail), 15);
+
+    // The oldest records make room, and the new one wraps around the end of the ring
+    let transaction = Transaction::new_signed_with_payer(
+        &[append_echo(
+            &program_id,
+            &log_buffer,
+            &authority.pubkey(),
+            b"defg".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (log_buffer_struct, records) = log_records(&mut banks_client, log_buffer).await;
+    assert_eq!(records, vec![b"bc".to_vec(), b"defg".to_vec()]);
+    assert_eq!(u64::from(log_buffer_struct.head), 9);
+    assert_eq!(u64::from(log_buffer_struct.tail), 7);
+    assert_eq!(u64::from(log_buffer_struct.appended), 4);
+
+    // A record can take the whole ring
+    let transaction = Transaction::new_signed_with_payer(
+        &[append_echo(
+            &program_id,
+            &log_buffer,
+            &authority.pubkey(),
+            b"hijklmnopqrs".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (log_buffer_struct, records) = log_records(&mut banks_client, log_buffer).await;
+    assert_eq!(records, vec![b"hijklmnopqrs".to_vec()]);
+    assert_eq!(log_buffer_struct.head, log_buffer_struct.tail);
+
+    let other = Keypair::new();
+    let cases = [
+        (
+            append_echo(
+                &program_id,
+                &log_buffer,
+                &authority.pubkey(),
+                b"hijklmnopqrst".to_vec(),
+            ),
+            &authority,
+            EchoError::RecordTooLarge,
+        ),
+        (
+            append_echo(&program_id, &log_buffer, &other.pubkey(), b"a".to_vec()),
+            &other,
+            EchoError::InvalidAuthority,
+        ),
+        (
+            append_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                b"a".to_vec(),
+            ),
+            &authority,
+            EchoError::InvalidAccountType,
+        ),
+    ];
+    for (instruction, signer, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_log_echo() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (log_buffer, _) = find_log_buffer_address(&program_id, &payer.pubkey(), 1);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            initialize_log_echo(
+                &program_id,
+                &payer.pubkey(),
+                1,
+                LogBuffer::HEADER_SIZE as u64 + 32,
+            ),
+            append_echo(&program_id, &log_buffer, &payer.pubkey(), b"first".to_vec()),
+            append_echo(
+                &program_id,
+                &log_buffer,
+                &payer.pubkey(),
+                b"second".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (_, records) = log_records(&mut banks_client, log_buffer).await;
+    assert_eq!(records, vec![b"first".to_vec(), b"second".to_vec()]);
+
+    // Log buffers have their own close instruction
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_authorized_echo(
+            &program_id,
+            &log_buffer,
+            &payer.pubkey(),
+            &payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidAccountType as u32)
+        )
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_log_echo(
+            &program_id,
+            &log_buffer,
+            &payer.pubkey(),
+            &payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert!(banks_client
+        .get_account(log_buffer)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+// The native runtime drops return data, so the message returned by `PopMessage` is not checked here.
+#[tokio::test]
+async fn test_mailbox() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let recipient = Keypair::new();
+    let sender = Keypair::new();
+    let mint = add_mint(&mut program_test, &recipient.pubkey(), 10);
+    let sender_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        sender_token_account,
+        &mint,
+        &sender.pubkey(),
+        10,
+    );
+    let postage_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        postage_account,
+        &mint,
+        &recipient.pubkey(),
+        0,
+    );
+    let mailbox = add_mailbox(
+        &mut program_test,
+        &program_id,
+        &recipient.pubkey(),
+        &postage_account,
+        2,
+        128,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            send_message_with_postage(
+                &program_id,
+                &recipient.pubkey(),
+                &sender.pubkey(),
+                &sender_token_account,
+                &postage_account,
+                b"hi".to_vec(),
+                2,
+            ),
+            send_message_with_postage(
+                &program_id,
+                &recipient.pubkey(),
+                &sender.pubkey(),
+                &sender_token_account,
+                &postage_account,
+                b"there".to_vec(),
+                3,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &sender],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        mailbox_messages(&mut banks_client, mailbox).await,
+        vec![
+            Message {
+                sender: sender.pubkey(),
+                postage: 2,
+                data: b"hi".to_vec(),
+            },
+            Message {
+                sender: sender.pubkey(),
+                postage: 3,
+                data: b"there".to_vec(),
+            },
+        ]
+    );
+    assert_eq!(token_balance(&mut banks_client, postage_account).await, 5);
+
+    let cases = [
+        (
+            send_message(
+                &program_id,
+                &recipient.pubkey(),
+                &sender.pubkey(),
+                b"free".to_vec(),
+            ),
+            &sender,
+            EchoError::InsufficientPostage,
+        ),
+        (
+            send_message_with_postage(
+                &program_id,
+                &recipient.pubkey(),
+                &sender.pubkey(),
+                &sender_token_account,
+                &sender_token_account,
+                b"hi".to_vec(),
+                2,
+            ),
+            &sender,
+            EchoError::InvalidPostageAccount,
+        ),
+        (
+            send_message_with_postage(
+                &program_id,
+                &recipient.pubkey(),
+                &sender.pubkey(),
+                &sender_token_account,
+                &postage_account,
+                vec![0; 981],
+                2,
+            ),
+            &sender,
+            EchoError::MessageTooLarge,
+        ),
+        (
+            send_message_with_postage(
+                &program_id,
+                &recipient.pubkey(),
+                &sender.pubkey(),
+                &sender_token_account,
+                &postage_account,
+                vec![0; 40],
+                2,
+            ),
+            &sender,
+            EchoError::MailboxFull,
+        ),
+        (
+            pop_message(&program_id, &sender.pubkey()),
+            &sender,
+            EchoError::InvalidMailbox,
+        ),
+    ];
+    for (mut instruction, signer, error) in cases {
+        // Point every case at the mailbox of the recipient
+        instruction.accounts[0].pubkey = mailbox;
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    // Popping takes the oldest message
+    let transaction = Transaction::new_signed_with_payer(
+        &[pop_message(&program_id, &recipient.pubkey())],
+        Some(&payer.pubkey()),
+        &[&payer, &recipient],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        mailbox_messages(&mut banks_client, mailbox).await,
+        vec![Message {
+            sender: sender.pubkey(),
+            postage: 3,
+            data: b"there".to_vec(),
+        }]
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[clear_mailbox(&program_id, &recipient.pubkey())],
+        Some(&payer.pubkey()),
+        &[&payer, &recipient],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(mailbox_messages(&mut banks_client, mailbox).await, vec![]);
+    let account = banks_client.get_account(mailbox).await.unwrap().unwrap();
+    assert!(account.data[Mailbox::HEADER_SIZE..].iter().all(|&b| b == 0));
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            clear_mailbox(&program_id, &recipient.pubkey()),
+            pop_message(&program_id, &recipient.pubkey()),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &recipient],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(EchoError::MailboxEmpty as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_initialize_mailbox() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (mailbox, _) = find_mailbox_address(&program_id, &payer.pubkey());
+    let sender = Keypair::new();
+
+    // Postage can only be asked for with an account to receive it
+    let transaction = Transaction::new_signed_with_payer(
+        &[initialize_mailbox(
+            &program_id,
+            &payer.pubkey(),
+            Mailbox::HEADER_SIZE as u64 + 64,
+            1,
+            None,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidPostageAccount as u32)
+        )
+    );
+
+    // Without postage, anyone can send
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            initialize_mailbox(
+                &program_id,
+                &payer.pubkey(),
+                Mailbox::HEADER_SIZE as u64 + 64,
+                0,
+                None,
+            ),
+            send_message(
+                &program_id,
+                &payer.pubkey(),
+                &sender.pubkey(),
+                b"hi".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &sender],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        mailbox_messages(&mut banks_client, mailbox).await,
+        vec![Message {
+            sender: sender.pubkey(),
+            postage: 0,
+            data: b"hi".to_vec(),
+        }]
+    );
+
+    // Only the recipient can close the mailbox
+    let mut instruction = close_mailbox(&program_id, &sender.pubkey(), &sender.pubkey());
+    instruction.accounts[0].pubkey = mailbox;
+    let transaction = Transaction::new_signed_with_payer(
+        &[instruction],
+        Some(&payer.pubkey()),
+        &[&payer, &sender],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidMailbox as u32)
+        )
+    );
+
+    // Closing drops the pending message and returns the rent
+    let mailbox_lamports = banks_client.get_balance(mailbox).await.unwrap();
+    let destination = Pubkey::new_unique();
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_mailbox(&program_id, &payer.pubkey(), &destination)],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        banks_client.get_balance(destination).await.unwrap(),
+        mailbox_lamports
+    );
+    assert!(banks_client.get_account(mailbox).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_initialize_mailbox_postage_account() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let owner = Pubkey::new_unique();
+    let mint = add_mint(&mut program_test, &owner, 0);
+    let other_mint = add_mint(&mut program_test, &owner, 0);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner,
+        Pricing::default(),
+        PaymentMode::Burn,
+        0,
+    );
+    let other_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        other_token_account,
+        &other_mint,
+        &owner,
+        0,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let cases = [
+        // Not a token account
+        (
+            (&vm_buffer, &vm_buffer, &mint),
+            EchoError::InvalidPostageAccount,
+        ),
+        // A token account of another mint than the machine's
+        (
+            (&other_token_account, &vm_buffer, &mint),
+            EchoError::InvalidPostageAccount,
+        ),
+        // A machine that is not derived from the mint
+        (
+            (&other_token_account, &vm_buffer, &other_mint),
+            EchoError::InvalidBufferAddress,
+        ),
+    ];
+    for (postage, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[initialize_mailbox(
+                &program_id,
+                &payer.pubkey(),
+                Mailbox::HEADER_SIZE as u64 + 64,
+                1,
+                Some(postage),
+            )],
+            Some(&payer.pubkey()),
+            &[&payer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32)),
+            "{:?}",
+            postage
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_authorized_echo_signed() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 4);
+    let (mut banks_client, relayer, recent_blockhash) = program_test.start().await;
+
+    // The authority only signs the message, the relayer signs and pays for the transaction
+    let signed_write = |signer: &Keypair, data: &[u8], nonce: u64| {
+        let message = signed_echo_message(&authorized_buffer, nonce, data);
+        let signature: [u8; 64] = signer.sign_message(&message).as_ref().try_into().unwrap();
+        ed25519_signature_instruction(&signer.pubkey(), &signature, &message)
+    };
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            signed_write(&authority, b"echo", 0),
+            authorized_echo_signed(&program_id, &authorized_buffer, b"echo".to_vec(), 0),
+        ],
+        Some(&relayer.pubkey()),
+        &[&relayer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"echo");
+    assert_eq!(u64::from(auth_buffer_struct.nonce), 1);
+
+    let other = Keypair::new();
+    let mut outlined_signature = signed_write(&authority, b"ohce", 1);
+    outlined_signature.data[4..6].copy_from_slice(&0u16.to_le_bytes());
+    let cases = [
+        (
+            // Replaying the first write, from a new relayer so the transaction differs
+            vec![
+                signed_write(&authority, b"echo", 0),
+                authorized_echo_signed(&program_id, &authorized_buffer, b"echo".to_vec(), 0),
+            ],
+            EchoError::InvalidNonce,
+        ),
+        (
+            vec![
+                signed_write(&authority, b"echo", 1),
+                authorized_echo_signed(&program_id, &authorized_buffer, b"ohce".to_vec(), 1),
+            ],
+            EchoError::InvalidSignedMessage,
+        ),
+        (
+            vec![
+                signed_write(&other, b"ohce", 1),
+                authorized_echo_signed(&program_id, &authorized_buffer, b"ohce".to_vec(), 1),
+            ],
+            EchoError::InvalidAuthority,
+        ),
+        (
+            vec![
+                outlined_signature,
+                authorized_echo_signed(&program_id, &authorized_buffer, b"ohce".to_vec(), 1),
+            ],
+            EchoError::InvalidSignatureInstruction,
+        ),
+        (
+            vec![
+                read_authorized_echo(&program_id, &authorized_buffer, 0, 4),
+                authorized_echo_signed(&program_id, &authorized_buffer, b"ohce".to_vec(), 1),
+            ],
+            EchoError::InvalidSignatureInstruction,
+        ),
+    ];
+    let new_relayer = Keypair::new();
+    let transaction = Transaction::new_signed_with_payer(
+        &[system_instruction::transfer(
+            &relayer.pubkey(),
+            &new_relayer.pubkey(),
+            1_000_000_000,
+        )],
+        Some(&relayer.pubkey()),
+        &[&relayer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    for (instructions, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &instructions,
+            Some(&new_relayer.pubkey()),
+            &[&new_relayer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(1, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_verify_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 8);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Every write records the hash of the whole echo data
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            verify_echo(&program_id, &authorized_buffer, hash(b"echoecho")),
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                4,
+                b"ohce".to_vec(),
+            ),
+            verify_echo(&program_id, &authorized_buffer, hash(b"echoohce")),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, _) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(
+        auth_buffer_struct.content_hash,
+        hash(b"echoohce").to_bytes()
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify_echo(
+            &program_id,
+            &authorized_buffer,
+            hash(b"echoecho"),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::ContentHashMismatch as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_verify_echo_buffer_kinds() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: 1,
+            per_byte: 0,
+        },
+        PaymentMode::Burn,
+        8,
+    );
+    let token_gated_buffer = add_token_gated_buffer(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &Pubkey::new_unique(),
+        1,
+        8,
+        None,
+    );
+    let user_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        10,
+    );
+    let log_buffer = add_log_buffer(&mut program_test, &program_id, &user.pubkey(), 1, 12);
+    let mailbox = add_mailbox(
+        &mut program_test,
+        &program_id,
+        &user.pubkey(),
+        &Pubkey::default(),
+        0,
+        8,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Each kind of buffer records the hash of its data on every write, a log buffer hashes its whole ring
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                b"echo".to_vec(),
+                1,
+            ),
+            verify_echo(&program_id, &vm_buffer, hash(b"echoecho")),
+            vending_machine_echo_at(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                4,
+                b"ohce".to_vec(),
+                1,
+            ),
+            verify_echo(&program_id, &vm_buffer, hash(b"echoohce")),
+            token_gated_echo(
+                &program_id,
+                &token_gated_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                b"echo".to_vec(),
+            ),
+            verify_echo(&program_id, &token_gated_buffer, hash(b"echoecho")),
+            append_echo(&program_id, &log_buffer, &user.pubkey(), b"echo".to_vec()),
+            verify_echo(&program_id, &log_buffer, hash(b"\x04\0\0\0echo\0\0\0\0")),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    // Mailboxes have no hash to check
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify_echo(&program_id, &mailbox, hash(&[0; 8]))],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidAccountType as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_merkle_root() {
+    // A tree needs leaves, and chunks need a size
+    assert_eq!(MerkleTree::new(Vec::<Vec<u8>>::new()), None);
+    assert_eq!(MerkleTree::from_chunks(b"", 4), None);
+    assert_eq!(MerkleTree::from_chunks(b"data", 0), None);
+
+    // Proofs of every leaf check out whatever the shape of the tree, and only for that leaf
+    for leaf_count in 1..=9u64 {
+        let leaves = (0..leaf_count)
+            .map(|i| vec![i as u8; 3])
+            .collect::<Vec<_>>();
+        let tree = MerkleTree::new(&leaves).unwrap();
+        assert_eq!(tree.leaf_count(), leaf_count);
+        for (index, leaf) in leaves.iter().enumerate() {
+            let proof = tree.proof(index);
+            let index = index as u64;
+            assert!(verify_proof(&tree.root(), leaf_count, index, leaf, &proof));
+            assert!(!verify_proof(
+                &tree.root(),
+                leaf_count,
+                index,
+                b"chunk",
+                &proof
+            ));
+            assert!(
+                leaf_count == 1
+                    || !verify_proof(
+                        &tree.root(),
+                        leaf_count,
+                        (index + 1) % leaf_count,
+                        leaf,
+                        &proof
+                    )
+            );
+            assert!(!verify_proof(
+                &tree.root(),
+                leaf_count,
+                leaf_count,
+                leaf,
+                &proof
+            ));
+        }
+    }
+
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 40);
+    let other_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 2, 40);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let data = b"chunks of data kept off-chain";
+    let tree = MerkleTree::from_chunks(data, 4).unwrap();
+    let verify = |index: usize| {
+        verify_chunk(
+            &program_id,
+            &authorized_buffer,
+            index as u64,
+            data.chunks(4).nth(index).unwrap().to_vec(),
+            tree.proof(index),
+        )
+    };
+
+    // The root replaces the echo data, then every chunk can be checked against it
+    let mut instructions = vec![set_merkle_root(
+        &program_id,
+        &authorized_buffer,
+        &authority.pubkey(),
+        tree.root(),
+        tree.leaf_count(),
+    )];
+    instructions.extend((0..tree.leaf_count() as usize).map(verify));
+    let transaction = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, echo_data) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(&echo_data[..32], tree.root().as_ref());
+    assert_eq!(echo_data[32..], [0; 8]);
+    assert_eq!(u64::from(auth_buffer_struct.merkle_leaf_count), 8);
+    assert_eq!(auth_buffer_struct.content_hash, hash(echo_data).to_bytes());
+
+    // A chunk with the proof of another one is rejected
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify_chunk(
+            &program_id,
+            &authorized_buffer,
+            1,
+            b"chun".to_vec(),
+            tree.proof(1),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidMerkleProof as u32)
+        )
+    );
+
+    // Buffers without a root cannot verify chunks
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify_chunk(
+            &program_id,
+            &other_buffer,
+            0,
+            b"chun".to_vec(),
+            tree.proof(0),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::NotMerkleBuffer as u32)
+        )
+    );
+
+    // Writing the buffer in another way clears the root
+    let transaction = Transaction::new_signed_with_payer(
+        &[authorized_echo(
+            &program_id,
+            &authorized_buffer,
+            &authority.pubkey(),
+            b"plain data".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(authorized_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (auth_buffer_struct, _) = load_buffer::<AuthorizedBuffer>(&account.data).unwrap();
+    assert_eq!(u64::from(auth_buffer_struct.merkle_leaf_count), 0);
+    let transaction = Transaction::new_signed_with_payer(
+        &[verify(0)],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::NotMerkleBuffer as u32)
+        )
+    );
+
+    // Only the authority sets the root, and a tree needs leaves
+    let transaction = Transaction::new_signed_with_payer(
+        &[set_merkle_root(
+            &program_id,
+            &other_buffer,
+            &payer.pubkey(),
+            tree.root(),
+            tree.leaf_count(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidAuthority as u32)
+        )
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[set_merkle_root(
+            &program_id,
+            &other_buffer,
+            &authority.pubkey(),
+            tree.root(),
+            0,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::EmptyMerkleTree as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_token_gated_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let holder = Keypair::new();
+    let other = Keypair::new();
+    let mint = add_mint(&mut program_test, &Pubkey::new_unique(), 1);
+    let other_mint = add_mint(&mut program_test, &Pubkey::new_unique(), 1);
+    let token_gated_buffer = add_token_gated_buffer(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &Pubkey::new_unique(),
+        1,
+        4,
+        Some(ContentHeader {
+            content_type: ContentType::Utf8,
+            schema_id: 0,
+        }),
+    );
+    let holder_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        holder_token_account,
+        &mint,
+        &holder.pubkey(),
+        1,
+    );
+    let other_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        other_token_account,
+        &mint,
+        &other.pubkey(),
+        0,
+    );
+    let other_mint_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        other_mint_token_account,
+        &other_mint,
+        &other.pubkey(),
+        1,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The holder of the token writes, and keeps it
+    let transaction = Transaction::new_signed_with_payer(
+        &[token_gated_echo(
+            &program_id,
+            &token_gated_buffer,
+            &holder.pubkey(),
+            &holder_token_account,
+            b"nft".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &holder],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(token_gated_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (_, echo_data) = load_buffer::<TokenGatedBuffer>(&account.data).unwrap();
+    assert_eq!(echo_data, b"nftn");
+    assert_eq!(
+        token_balance(&mut banks_client, holder_token_account).await,
+        1
+    );
+
+    // Others cannot write with an empty account, with the account of the holder, or with tokens of another mint
+    for (token_account, error) in [
+        (other_token_account, EchoError::InsufficientGateBalance),
+        (holder_token_account, EchoError::InvalidGateTokenAccount),
+        (other_mint_token_account, EchoError::InvalidGateTokenAccount),
+    ] {
+        let transaction = Transaction::new_signed_with_payer(
+            &[token_gated_echo(
+                &program_id,
+                &token_gated_buffer,
+                &other.pubkey(),
+                &token_account,
+                b"mine".to_vec(),
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &other],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+
+    // A gate needs a minimum balance, or anyone could write
+    let transaction = Transaction::new_signed_with_payer(
+        &[initialize_token_gated_echo(
+            &program_id,
+            &mint,
+            &payer.pubkey(),
+            2,
+            TokenGatedBuffer::HEADER_SIZE as u64,
+            0,
+            None,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::ZeroMinBalance as u32)
+        )
+    );
+
+    // Writes are checked against the content type of the buffer
+    let transaction = Transaction::new_signed_with_payer(
+        &[token_gated_echo(
+            &program_id,
+            &token_gated_buffer,
+            &holder.pubkey(),
+            &holder_token_account,
+            vec![0xff],
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &holder],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidUtf8 as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_initialize_token_gated_echo() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("echo", program_id, None);
+    let holder = Keypair::new();
+    let buyer = Keypair::new();
+    let mint = add_mint(&mut program_test, &Pubkey::new_unique(), 1);
+    let holder_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        holder_token_account,
+        &mint,
+        &holder.pubkey(),
+        1,
+    );
+    let buyer_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        buyer_token_account,
+        &mint,
+        &buyer.pubkey(),
+        0,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (token_gated_buffer, _) = find_token_gated_address(&program_id, &mint, &payer.pubkey(), 1);
+
+    // Control moves with the token, which stays in circulation
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            initialize_token_gated_echo(
+                &program_id,
+                &mint,
+                &payer.pubkey(),
+                1,
+                TokenGatedBuffer::HEADER_SIZE as u64 + 4,
+                1,
+                None,
+            ),
+            token_gated_echo(
+                &program_id,
+                &token_gated_buffer,
+                &holder.pubkey(),
+                &holder_token_account,
+                b"sold".to_vec(),
+            ),
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &holder_token_account,
+                &buyer_token_account,
+                &holder.pubkey(),
+                &[],
+                1,
+            )
+            .unwrap(),
+            token_gated_echo(
+                &program_id,
+                &token_gated_buffer,
+                &buyer.pubkey(),
+                &buyer_token_account,
+                b"mine".to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &holder, &buyer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let account = banks_client
+        .get_account(token_gated_buffer)
+        .await
+        .unwrap()
+        .unwrap();
+    let (token_gated_buffer_struct, echo_data) =
+        load_buffer::<TokenGatedBuffer>(&account.data).unwrap();
+    assert_eq!(token_gated_buffer_struct.mint, mint);
+    assert_eq!(u64::from(token_gated_buffer_struct.min_balance), 1);
+    assert_eq!(echo_data, b"mine");
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[token_gated_echo(
+            &program_id,
+            &token_gated_buffer,
+            &holder.pubkey(),
+            &holder_token_account,
+            b"back".to_vec(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &holder],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InsufficientGateBalance as u32)
+        )
+    );
+
+    // Only the creator closes the buffer, holding the token is not enough
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_token_gated_echo(
+            &program_id,
+            &token_gated_buffer,
+            &holder.pubkey(),
+            &holder.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &holder],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidAuthority as u32)
+        )
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_token_gated_echo(
+            &program_id,
+            &token_gated_buffer,
+            &payer.pubkey(),
+            &payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert!(banks_client
+        .get_account(token_gated_buffer)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_content_header() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let text = ContentHeader {
+        content_type: ContentType::Utf8,
+        schema_id: 0,
+    };
+    let borsh = ContentHeader {
+        content_type: ContentType::Borsh,
+        schema_id: 7,
+    };
+    let text_buffer = add_typed_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        1,
+        8,
+        text,
+    );
+    let borsh_buffer = add_typed_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        2,
+        16,
+        borsh,
+    );
+    let untyped_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 3, 4);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Typed values come back according to the header, text without its zero padding
+    let value = (42u32, "gm".to_string());
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            authorized_echo_at(
+                &program_id,
+                &text_buffer,
+                &authority.pubkey(),
+                0,
+                "héllo".as_bytes().to_vec(),
+            ),
+            authorized_echo_at(
+                &program_id,
+                &borsh_buffer,
+                &authority.pubkey(),
+                0,
+                value.try_to_vec().unwrap(),
+            ),
+            authorized_echo(
+                &program_id,
+                &untyped_buffer,
+                &authority.pubkey(),
+                vec![0xff],
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &authority],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (auth_buffer_struct, echo_data) =
+        authorized_buffer_state(&mut banks_client, text_buffer).await;
+    assert_eq!(auth_buffer_struct.content_header(), Some(text));
+    assert_eq!(
+        auth_buffer_struct.content(&echo_data).unwrap(),
+        EchoContent::Text("héllo")
+    );
+    let (auth_buffer_struct, echo_data) =
+        authorized_buffer_state(&mut banks_client, borsh_buffer).await;
+    let content = auth_buffer_struct.content(&echo_data).unwrap();
+    assert!(matches!(content, EchoContent::Borsh { schema_id: 7, .. }));
+    assert_eq!(content.deserialize::<(u32, String)>().unwrap(), value);
+    let (auth_buffer_struct, echo_data) =
+        authorized_buffer_state(&mut banks_client, untyped_buffer).await;
+    assert_eq!(auth_buffer_struct.content_header(), None);
+    assert_eq!(
+        auth_buffer_struct.content(&echo_data).unwrap(),
+        EchoContent::Bytes(&[0xff; 4])
+    );
+    assert_eq!(
+        auth_buffer_struct
+            .content(&echo_data)
+            .unwrap()
+            .deserialize::<u32>()
+            .unwrap_err(),
+        EchoError::UnexpectedContentType
+    );
+
+    // Writes to UTF-8 buffers must leave valid UTF-8, even when the data is repeated or written in the middle
+    for instruction in [
+        authorized_echo(
+            &program_id,
+            &text_buffer,
+            &authority.pubkey(),
+            "héllo".as_bytes().to_vec(),
+        ),
+        authorized_echo_at(
+            &program_id,
+            &text_buffer,
+            &authority.pubkey(),
+            2,
+            b"i".to_vec(),
+        ),
+    ] {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, &authority],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(EchoError::InvalidUtf8 as u32)
+            )
+        );
+    }
+    assert_eq!(
+        authorized_buffer_state(&mut banks_client, text_buffer)
+            .await
+            .1,
+        b"h\xc3\xa9llo\0\0"
+    );
+}
+
+#[tokio::test]
+async fn test_initialize_typed_buffer() {
+    let program_id = Pubkey::new_unique();
+    let program_test = ProgramTest::new("echo", program_id, None);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+    let (authorized_buffer, _) = find_authorized_buffer_address(&program_id, &payer.pubkey(), 1);
+    let json = ContentHeader {
+        content_type: ContentType::Json,
+        schema_id: 3,
+    };
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            initialize_authorized_echo(
+                &program_id,
+                &payer.pubkey(),
+                1,
+                AuthorizedBuffer::HEADER_SIZE as u64 + 16,
+                None,
+                Some(json),
+            ),
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &payer.pubkey(),
+                0,
+                br#"{"gm":true}"#.to_vec(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    let (auth_buffer_struct, echo_data) =
+        authorized_buffer_state(&mut banks_client, authorized_buffer).await;
+    assert_eq!(auth_buffer_struct.content_header(), Some(json));
+    assert_eq!(
+        auth_buffer_struct.content(&echo_data).unwrap(),
+        EchoContent::Json {
+            schema_id: 3,
+            text: r#"{"gm":true}"#,
+        }
+    );
+}
+
+#[tokio::test]
+async fn test_echo_errors() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let authority = Keypair::new();
+    let authorized_buffer =
+        add_authorized_buffer(&mut program_test, &program_id, &authority.pubkey(), 1, 4);
+    let legacy_buffer = add_legacy_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        2,
+        b"echo",
+    );
+    let v1_buffer = add_v1_authorized_buffer(
+        &mut program_test,
+        &program_id,
+        &authority.pubkey(),
+        3,
+        b"echo",
+    );
+    let (future_buffer, mut future_struct) =
+        authorized_buffer_header(&program_id, &authority.pubkey(), 4);
+    future_struct.header.version = CURRENT_VERSION + 1;
+    add_buffer_account(
+        &mut program_test,
+        &program_id,
+        future_buffer,
+        bytemuck::bytes_of(&future_struct).to_vec(),
+    );
+    let vm_buffer = Pubkey::new_unique();
+    add_buffer_account(
+        &mut program_test,
+        &program_id,
+        vm_buffer,
+        bytemuck::bytes_of(&VendingMachineBuffer {
+            header: AccountHeader::new(AccountType::VendingMachineBuffer),
+            bump_seed: 0,
+            price: 0.into(),
+            owner: Pubkey::default(),
+            payment_mode: PaymentMode::Burn as u8,
+            treasury: Pubkey::default(),
+            machine_id: 0.into(),
+            price_per_byte: 0.into(),
+            credit_lifetime: 0.into(),
+            refund_credits: 0,
+            outstanding_credits: 0.into(),
+            sealed: 0,
+            content_type: ContentType::Bytes as u8,
+            schema_id: 0.into(),
+            content_hash: [0; 32],
+            reserved: [0; 20],
+        })
+        .to_vec(),
+    );
+    let echo_buffer = Pubkey::new_unique();
+    program_test.add_account(
+        echo_buffer,
+        Account {
+            lamports: Rent::default().minimum_balance(4),
+            data: b"full".to_vec(),
+            owner: program_id,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let impostor = Keypair::new();
+    let cases = [
+        (
+            echo(&program_id, &echo_buffer, b"echo".to_vec()),
+            &payer,
+            EchoError::BufferNotEmpty,
+        ),
+        (
+            authorized_echo(&program_id, &authorized_buffer, &authority.pubkey(), vec![]),
+            &authority,
+            EchoError::EmptyData,
+        ),
+        (
+            authorized_echo_at(
+                &program_id,
+                &authorized_buffer,
+                &authority.pubkey(),
+                0,
+                vec![],
+            ),
+            &authority,
+            EchoError::EmptyData,
+        ),
+        (
+            vending_machine_echo_at(
+                &program_id,
+                &vm_buffer,
+                &authority.pubkey(),
+                &Pubkey::new_unique(),
+                &Pubkey::new_unique(),
+                0,
+                vec![],
+                0,
+            ),
+            &authority,
+            EchoError::EmptyData,
+        ),
+        (
+            authorized_echo(
+                &program_id,
+                &authorized_buffer,
+                &impostor.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &impostor,
+            EchoError::InvalidAuthority,
+        ),
+        (
+            authorized_echo(
+                &program_id,
+                &echo_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::InvalidAccountType,
+        ),
+        (
+            authorized_echo(
+                &program_id,
+                &vm_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::InvalidAccountType,
+        ),
+        (
+            authorized_echo(
+                &program_id,
+                &legacy_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::InvalidAccountType,
+        ),
+        (
+            authorized_echo(
+                &program_id,
+                &future_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::UnsupportedAccountVersion,
+        ),
+        (
+            authorized_echo(
+                &program_id,
+                &v1_buffer,
+                &authority.pubkey(),
+                b"echo".to_vec(),
+            ),
+            &authority,
+            EchoError::UnsupportedAccountVersion,
+        ),
+    ];
+    for (instruction, signer, error) in cases {
+        let transaction = Transaction::new_signed_with_payer(
+            &[instruction],
+            Some(&payer.pubkey()),
+            &[&payer, signer],
+            recent_blockhash,
+        );
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_vending_machine_treasury() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let price = 3;
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let impostor = Keypair::new();
+
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: price,
+            per_byte: 0,
+        },
+        PaymentMode::Treasury,
+        4,
+    );
+    let (treasury, _) = find_treasury_address(&program_id, &vm_buffer);
+    let user_token_account = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        10,
+    );
+    add_token_account(&mut program_test, destination, &mint, &owner.pubkey(), 0);
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Payments land in the treasury instead of being burned
+    let transaction = Transaction::new_signed_with_payer(
+        &[vending_machine_echo(
+            &program_id,
+            &vm_buffer,
+            &user.pubkey(),
+            &user_token_account,
+            &mint,
+            b"echo".to_vec(),
+            price,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        7
+    );
+    assert_eq!(token_balance(&mut banks_client, treasury).await, price);
+
+    // Only the owner can withdraw the proceeds
+    let transaction = Transaction::new_signed_with_payer(
+        &[withdraw_vending_machine_proceeds(
+            &program_id,
+            &vm_buffer,
+            &mint,
+            &impostor.pubkey(),
+            &destination,
+            price,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &impostor],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidOwner as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[withdraw_vending_machine_proceeds(
+            &program_id,
+            &vm_buffer,
+            &mint,
+            &owner.pubkey(),
+            &destination,
+            price,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &owner],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(token_balance(&mut banks_client, treasury).await, 0);
+    assert_eq!(token_balance(&mut banks_client, destination).await, price);
+}
+
+#[tokio::test]
+async fn test_set_vending_machine_price() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let impostor = Keypair::new();
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: 3,
+            per_byte: 0,
+        },
+        PaymentMode::Burn,
+        4,
+    );
+    let user_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        10,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[set_vending_machine_price(
+            &program_id,
+            &vm_buffer,
+            &mint,
+            &impostor.pubkey(),
+            Pricing::default(),
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &impostor],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::InvalidOwner as u32)
+        )
+    );
+
+    // A price raised in front of a write cannot charge more than the user agreed to
+    let raise_price = set_vending_machine_price(
+        &program_id,
+        &vm_buffer,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: 5,
+            per_byte: 0,
+        },
+    );
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            raise_price.clone(),
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                b"echo".to_vec(),
+                3,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &owner, &user],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(EchoError::ChargeExceedsMax as u32)
+        )
+    );
+
+    // The machine keeps its address and charges the new price
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            raise_price,
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                b"echo".to_vec(),
+                5,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &owner, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+
+    let account = banks_client.get_account(vm_buffer).await.unwrap().unwrap();
+    let (vm_buffer_struct, echo_data) = load_buffer::<VendingMachineBuffer>(&account.data).unwrap();
+    assert_eq!(u64::from(vm_buffer_struct.price), 5);
+    assert_eq!(echo_data, b"echo");
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        5
+    );
+}
+
+#[tokio::test]
+async fn test_vending_machine_per_byte_pricing() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 20);
+    let pricing = Pricing {
+        base: 1,
+        per_byte: 2,
+    };
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        pricing,
+        PaymentMode::Burn,
+        4,
+    );
+    let user_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        20,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Writing at an offset only pays for the bytes written
+    let transaction = Transaction::new_signed_with_payer(
+        &[vending_machine_echo_at(
+            &program_id,
+            &vm_buffer,
+            &user.pubkey(),
+            &user_token_account,
+            &mint,
+            1,
+            b"!".to_vec(),
+            3,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        17
+    );
+
+    // A full echo pays for the whole echo data, which clients can price from the header
+    let account = banks_client.get_account(vm_buffer).await.unwrap().unwrap();
+    let (vm_buffer_struct, echo_data) = load_buffer::<VendingMachineBuffer>(&account.data).unwrap();
+    let charge = vm_buffer_struct.pricing().charge(echo_data.len()).unwrap();
+    assert_eq!(charge, 9);
+
+    // Users cap what a write may charge them
+    let transaction = Transaction::new_signed_with_payer(
+        &[vending_machine_echo(
+            &program_id,
+            &vm_buffer,
+            &user.pubkey(),
+            &user_token_account,
+            &mint,
+            b"e".to_vec(),
+            charge - 1,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::ChargeExceedsMax as u32)
+        )
+    );
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[vending_machine_echo(
+            &program_id,
+            &vm_buffer,
+            &user.pubkey(),
+            &user_token_account,
+            &mint,
+            b"e".to_vec(),
+            charge,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        17 - charge
+    );
+
+    // Charges that do not fit in a u64 are rejected before any token moves
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            set_vending_machine_price(
+                &program_id,
+                &vm_buffer,
+                &mint,
+                &owner.pubkey(),
+                Pricing {
+                    base: 1,
+                    per_byte: u64::MAX,
+                },
+            ),
+            vending_machine_echo_at(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                0,
+                b"ab".to_vec(),
+                u64::MAX,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &owner, &user],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(EchoError::ArithmeticOverflow as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_vending_machine_credits() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "echo",
+        program_id,
+        processor!(Processor::process_instruction),
+    );
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let late_user = Keypair::new();
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: 2,
+            per_byte: 0,
+        },
+        PaymentMode::Treasury,
+        4,
+    );
+    let (treasury, _) = find_treasury_address(&program_id, &vm_buffer);
+    let user_token_account = Pubkey::new_unique();
+    let destination = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        10,
+    );
+    add_token_account(&mut program_test, destination, &mint, &owner.pubkey(), 0);
+    let credits = add_credits(
+        &mut program_test,
+        &program_id,
+        &vm_buffer,
+        &user.pubkey(),
+        0,
+        0,
+    );
+    let expired_credits = add_credits(
+        &mut program_test,
+        &program_id,
+        &vm_buffer,
+        &late_user.pubkey(),
+        10,
+        1,
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // Credits are paid for once, then writes spend them without touching the token account
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            set_vending_machine_credit_policy(
+                &program_id,
+                &vm_buffer,
+                &mint,
+                &owner.pubkey(),
+                0,
+                true,
+            ),
+            buy_vending_machine_credits(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                6,
+            ),
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &credits,
+                &mint,
+                b"echo".to_vec(),
+                2,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &owner, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        4
+    );
+    assert_eq!(token_balance(&mut banks_client, treasury).await, 6);
+
+    let account = banks_client.get_account(credits).await.unwrap().unwrap();
+    let (credits_struct, _) = load_buffer::<VendingMachineCredits>(&account.data).unwrap();
+    assert_eq!(u64::from(credits_struct.credits), 4);
+    let account = banks_client.get_account(vm_buffer).await.unwrap().unwrap();
+    let (vm_buffer_struct, echo_data) = load_buffer::<VendingMachineBuffer>(&account.data).unwrap();
+    assert_eq!(u64::from(vm_buffer_struct.outstanding_credits), 4);
+    assert_eq!(echo_data, b"echo");
+
+    // Refundable credits stay in the treasury
+    let withdraw = |amount| {
+        Transaction::new_signed_with_payer(
+            &[withdraw_vending_machine_proceeds(
+                &program_id,
+                &vm_buffer,
+                &mint,
+                &owner.pubkey(),
+                &destination,
+                amount,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, &owner],
+            recent_blockhash,
+        )
+    };
+    assert_eq!(
+        banks_client
+            .process_transaction(withdraw(3))
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(EchoError::ProceedsReservedForCredits as u32)
+        )
+    );
+    banks_client.process_transaction(withdraw(2)).await.unwrap();
+
+    let write = |user: &Keypair, credits: &Pubkey, data: &[u8]| {
+        Transaction::new_signed_with_payer(
+            &[vending_machine_echo_at(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                credits,
+                &mint,
+                0,
+                data.to_vec(),
+                2,
+            )],
+            Some(&payer.pubkey()),
+            &[&payer, user],
+            recent_blockhash,
+        )
+    };
+    banks_client
+        .process_transaction(write(&user, &credits, b"a"))
+        .await
+        .unwrap();
+    banks_client
+        .process_transaction(write(&user, &credits, b"b"))
+        .await
+        .unwrap();
+    let cases = [
+        (write(&user, &credits, b"c"), EchoError::InsufficientCredits),
+        (
+            write(&late_user, &expired_credits, b"c"),
+            EchoError::CreditsExpired,
+        ),
+        (write(&late_user, &credits, b"c"), EchoError::InvalidCredits),
+    ];
+    for (transaction, error) in cases {
+        assert_eq!(
+            banks_client
+                .process_transaction(transaction)
+                .await
+                .unwrap_err()
+                .unwrap(),
+            TransactionError::InstructionError(0, InstructionError::Custom(error as u32))
+        );
+    }
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        4
+    );
+}
+
+// Creating and closing the credits account is only supported by the BPF runtime
+#[tokio::test]
+async fn test_close_vending_machine_credits() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new("echo", program_id, None);
+    let owner = Keypair::new();
+    let user = Keypair::new();
+    let mint = add_mint(&mut program_test, &owner.pubkey(), 10);
+    let vm_buffer = add_vending_machine(
+        &mut program_test,
+        &program_id,
+        &mint,
+        &owner.pubkey(),
+        Pricing {
+            base: 1,
+            per_byte: 0,
+        },
+        PaymentMode::Treasury,
+        4,
+    );
+    let (treasury, _) = find_treasury_address(&program_id, &vm_buffer);
+    let (credits, _) = find_credits_address(&program_id, &vm_buffer, &user.pubkey());
+    let user_token_account = Pubkey::new_unique();
+    add_token_account(
+        &mut program_test,
+        user_token_account,
+        &mint,
+        &user.pubkey(),
+        10,
+    );
+    program_test.add_account(
+        user.pubkey(),
+        Account {
+            lamports: 1_000_000_000,
+            ..Account::default()
+        },
+    );
+    let (mut banks_client, payer, recent_blockhash) = program_test.start().await;
+
+    // The first purchase creates the credits account
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            set_vending_machine_credit_policy(
+                &program_id,
+                &vm_buffer,
+                &mint,
+                &owner.pubkey(),
+                0,
+                true,
+            ),
+            buy_vending_machine_credits(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &user_token_account,
+                &mint,
+                5,
+            ),
+            vending_machine_echo(
+                &program_id,
+                &vm_buffer,
+                &user.pubkey(),
+                &credits,
+                &mint,
+                b"echo".to_vec(),
+                1,
+            ),
+        ],
+        Some(&payer.pubkey()),
+        &[&payer, &owner, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        5
+    );
+    assert_eq!(token_balance(&mut banks_client, treasury).await, 5);
+    let credits_lamports = banks_client.get_balance(credits).await.unwrap();
+    let user_lamports = banks_client.get_balance(user.pubkey()).await.unwrap();
+
+    // Unspent credits are refunded from the treasury and the rent goes back to the user
+    let transaction = Transaction::new_signed_with_payer(
+        &[close_vending_machine_credits(
+            &program_id,
+            &vm_buffer,
+            &mint,
+            &user.pubkey(),
+            &user_token_account,
+        )],
+        Some(&payer.pubkey()),
+        &[&payer, &user],
+        recent_blockhash,
+    );
+    banks_client.process_transaction(transaction).await.unwrap();
+    assert_eq!(
+        token_balance(&mut banks_client, user_token_account).await,
+        9
+    );
+    assert_eq!(token_balance(&mut banks_client, treasury).await, 1);
+    assert_eq!(
+        banks_client.get_balance(user.pubkey()).await.unwrap(),
+        user_lamports + credits_lamports
+    );
+    assert!(banks_client.get_account(credits).await.unwrap().is_none());
+
+    let account = banks_client.get_account(vm_buffer).await.unwrap().unwrap();
+    let (vm_buffer_struct, _) = load_buffer::<VendingMachineBuffer>(&account.data).unwrap();
+    assert_eq!(u64::from(vm_buffer_struct.outstanding_credits), 0);
+}
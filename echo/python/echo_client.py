@@ -40,9 +40,15 @@ def echo(params: EchoParams) -> TransactionInstruction:
 def initialize_authorized_buffer(
     authorized_buffer_pk, authority_pk, program_id, buffer_seed, buffer_size
 ):
-    # pack data
+    # pack data, the trailing zeroes are an expiry and a content header of None
     data = b"".join(
-        [struct.pack("<B", 1), pack_u64(buffer_seed), pack_u64(buffer_size)]
+        [
+            struct.pack("<B", 1),
+            pack_u64(buffer_seed),
+            pack_u64(buffer_size),
+            struct.pack("<B", 0),
+            struct.pack("<B", 0),
+        ]
     )
 
     return TransactionInstruction(